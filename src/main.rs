@@ -2,21 +2,42 @@
 
 use dotenvy::dotenv;
 use fs2::FileExt;
-use serenity::all::UserId;
+use serenity::all::{ChannelId, CommandInteraction, Interaction, UserId};
 use serenity::{async_trait, model::channel::Message, model::gateway::Ready, prelude::*};
 use std::fs::File;
 use std::path::Path;
 
 use mintybot::discord;
 use mintybot::msg_context::MsgContextInfo;
-use mintybot::openai::get_openai_response;
+use mintybot::openai::{BotReply, ReplyOptions, continue_openai_response, get_openai_response};
 use mintybot::statics::{DISCORD_TOKEN, get_state_dir_name, is_dev_mode};
-use mintybot::utils::admin_commands::process_admin_command;
+use mintybot::utils::admin_commands::{is_admin, is_admin_command, process_admin_command};
+use mintybot::utils::attachments::{collect_image_urls, load_file_attachments};
+use mintybot::utils::backend_error::{FailedAction, report_backend_error};
+use mintybot::utils::budget::BudgetUsage;
 use mintybot::utils::conversation::ChatMessage;
-use mintybot::utils::persistence::add_message;
+use mintybot::utils::directives::{Directive, MessageDirectives, parse_directives};
+use mintybot::utils::image_gen::{InvalidPrompt, PromptSource, draw_and_post};
+use mintybot::utils::image_store::{PendingFiles, persist_files, persist_images};
+use mintybot::utils::moderation::{
+    ContentDirection, ModerationAction, moderate, moderation_notice,
+};
+use mintybot::utils::persistence::{
+    add_message, check_budgets, get_admin_only_directives, get_max_images_per_message,
+    is_batching_enabled, is_transcript_reply_enabled, take_budget_warnings,
+};
 use mintybot::utils::persistence::{load_state, save_state};
-
-fn clean_message_content(msg: &Message, user_id: UserId) -> String {
+use mintybot::utils::rate_limit::{RateLimitDecision, check_rate_limit};
+use mintybot::utils::request_queue::{
+    PendingMention, enqueue_channel_request, push_pending_mention, take_pending_mentions,
+};
+use mintybot::utils::slash_commands::{
+    UserCommand, handle_interaction, register_slash_commands, to_user_command,
+};
+use mintybot::utils::transcription::{is_audio_attachment, transcribe_attachment};
+
+/// Remove the mentions of the bot from a message and parse the directives at its start
+fn clean_message_content(msg: &Message, user_id: UserId) -> (String, MessageDirectives) {
     let mut content = msg.content.clone();
 
     // Remove bot mention
@@ -32,7 +53,14 @@ fn clean_message_content(msg: &Message, user_id: UserId) -> String {
         content = content.replace(&role_mention, "");
     }
 
-    content.trim().to_string()
+    let (directives, content) = parse_directives(content.trim());
+    (content.to_string(), directives)
+}
+
+/// Whether a message can mention the bot, decided from the message alone
+/// The bot's role isn't known without a request, so any role mention counts
+fn may_mention_bot(msg: &Message, bot_id: UserId) -> bool {
+    msg.mentions.iter().any(|user| user.id == bot_id) || !msg.mention_roles.is_empty()
 }
 
 async fn check_mentioned(ctx: &Context, msg: &Message) -> bool {
@@ -85,38 +113,331 @@ async fn check_mentioned(ctx: &Context, msg: &Message) -> bool {
     regular_mentioned || role_mentioned
 }
 
-/// Process a message that mentions the bot and send a response
+// Continues the last reply of the bot instead of answering a new message
+const CONTINUE_COMMAND: &str = "<continue>";
+
+/// Get the prompt of a `<draw> prompt` or `<draw prompt>` command
+fn parse_draw_command(content: &str) -> Option<&str> {
+    let rest = content.strip_prefix("<draw")?;
+    if let Some(prompt) = rest.strip_prefix('>') {
+        return Some(prompt.trim());
+    }
+    if rest.starts_with(char::is_whitespace) {
+        return rest.trim().strip_suffix('>').map(str::trim);
+    }
+    None
+}
+
+/// Process the mentions waiting in a channel and send a single response
 async fn process_bot_mention(
     ctx: &Context,
-    msg_ctx: &MsgContextInfo,
-    content: String,
-    name: String,
-    image_url: Option<String>,
+    mentions: Vec<PendingMention>,
+    directives: MessageDirectives,
 ) {
-    // Add the user's message to the conversation history
-    let message = if let Some(url) = image_url {
-        ChatMessage::user_with_image(content.clone(), name, url)
+    // A batch has a single author, the latest mention stands for the whole batch
+    let Some(msg_ctx) = mentions.last().map(|mention| mention.msg_ctx.clone()) else {
+        return;
+    };
+    let msg_ctx = &msg_ctx;
+    if mentions.len() > 1 {
+        tracing::info!(
+            "Answering {} mentions in channel {} together",
+            mentions.len(),
+            msg_ctx.channel_id
+        );
+    }
+
+    if refuse_if_over_budget(ctx, msg_ctx).await {
+        return;
+    }
+
+    // Add the users' messages to the conversation history, unless asked not to or private
+    let transient_messages = if directives.is_transient() {
+        Some(
+            mentions
+                .into_iter()
+                .map(|mention| mention.message)
+                .collect(),
+        )
     } else {
-        ChatMessage::user(content.clone(), name)
+        for mention in mentions {
+            // The stored files are released once the message refers to them
+            add_message(msg_ctx.channel_id, mention.message).await;
+        }
+        None
+    };
+    let reply_options = ReplyOptions {
+        model: directives.model,
+        transient_messages,
     };
-    add_message(msg_ctx.channel_id, message).await;
 
     // Send the message to OpenAI and handle the response
-    match get_openai_response(msg_ctx).await {
-        Ok(response) => {
+    let result = get_openai_response(ctx, msg_ctx, reply_options).await;
+    send_bot_reply(ctx, msg_ctx, result, directives.ephemeral).await;
+}
+
+/// Continue the last reply of the bot where it was cut off
+async fn continue_bot_reply(ctx: &Context, msg_ctx: &MsgContextInfo) {
+    if refuse_if_over_budget(ctx, msg_ctx).await {
+        return;
+    }
+
+    let result = continue_openai_response(ctx, msg_ctx).await;
+    send_bot_reply(ctx, msg_ctx, result, false).await;
+}
+
+/// Draw an image for a `<draw>` command and post it to the channel
+/// A `transient` request and its image are kept out of the history
+async fn draw_image(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    message: ChatMessage,
+    prompt: &str,
+    transient: bool,
+) {
+    if refuse_if_over_budget(ctx, msg_ctx).await {
+        return;
+    }
+
+    // Keep the request in the history so the model knows what was asked for
+    if !transient {
+        add_message(msg_ctx.channel_id, message).await;
+    }
+
+    match draw_and_post(ctx, msg_ctx, prompt, PromptSource::User, transient).await {
+        Ok(_) => notify_budget_warnings(ctx, msg_ctx).await,
+        Err(err) => match err.downcast_ref::<InvalidPrompt>() {
+            // Not a failure, just a prompt that can't be drawn
+            Some(invalid) => {
+                tracing::info!("Not drawing {:?}: {invalid}", prompt);
+                let reply_to = msg_ctx.reply_to(msg_ctx.channel_id);
+                if let Err(why) =
+                    discord::say_reply(ctx, msg_ctx.channel_id, reply_to, invalid.user_message())
+                        .await
+                {
+                    tracing::error!("Error sending invalid prompt reply: {:?}", why);
+                }
+            }
+            None => {
+                report_backend_error(ctx, msg_ctx, msg_ctx.channel_id, FailedAction::Draw, &err)
+                    .await;
+            }
+        },
+    }
+}
+
+/// Run `/draw` or `/continue` like the text command in a mention
+/// The result is posted in the channel, only the pending response is left to remove
+async fn run_user_command(ctx: &Context, interaction: &CommandInteraction, command: UserCommand) {
+    // Take a place in the channel's queue right away, like a mention
+    let ticket = enqueue_channel_request(interaction.channel_id);
+    if let Err(e) = interaction.defer_ephemeral(&ctx.http).await {
+        tracing::error!("Failed to defer slash command: {:?}", e);
+        return;
+    }
+
+    // Throttled like mentions, admins are exempt
+    let author = &interaction.user;
+    if !is_admin(author.id) {
+        let decision = check_rate_limit(author.id, interaction.channel_id).await;
+        if decision != RateLimitDecision::Allowed {
+            tracing::info!(
+                "Rate limited slash command by {}: {:?}",
+                author.name,
+                decision
+            );
+            let _ = discord::say_ephemeral(ctx, interaction, "⏳").await;
+            return;
+        }
+    }
+
+    let msg_ctx = MsgContextInfo::from_interaction(ctx, interaction).await;
+    match command {
+        UserCommand::Draw(prompt) => {
+            if !block_flagged_input(ctx, &msg_ctx, &prompt).await {
+                let selected_name = get_best_name_of_author(ctx, &msg_ctx).await;
+                let message = ChatMessage::user(format!("<draw> {prompt}"), selected_name);
+                ticket.wait_turn().await;
+                draw_image(ctx, &msg_ctx, message, &prompt, false).await;
+            }
+        }
+        UserCommand::Continue => {
+            ticket.wait_turn().await;
+            continue_bot_reply(ctx, &msg_ctx).await;
+        }
+    }
+
+    if let Err(e) = interaction.delete_response(&ctx.http).await {
+        tracing::error!("Failed to remove slash command response: {:?}", e);
+    }
+}
+
+/// Moderate a user's message, posting a notice if it is flagged
+/// Returns true if the message must not be answered
+async fn block_flagged_input(ctx: &Context, msg_ctx: &MsgContextInfo, text: &str) -> bool {
+    let Some(action) = moderate(ctx, msg_ctx, ContentDirection::Input, text).await else {
+        return false;
+    };
+
+    if action != ModerationAction::Log {
+        let notice = moderation_notice(ContentDirection::Input, action);
+        if let Err(why) =
+            discord::say_reply(ctx, msg_ctx.channel_id, msg_ctx.message_id, notice).await
+        {
+            tracing::error!("Error sending moderation notice: {:?}", why);
+        }
+    }
+    action == ModerationAction::Block
+}
+
+/// Ignore the directives of a message that only admins may use, telling the author
+async fn restrict_directives(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    directives: &mut MessageDirectives,
+) {
+    if is_admin(msg_ctx.author_id) {
+        return;
+    }
+
+    let admin_only = get_admin_only_directives().await;
+    let denied: Vec<Directive> = directives
+        .used()
+        .into_iter()
+        .filter(|directive| admin_only.contains(directive))
+        .collect();
+    if denied.is_empty() {
+        return;
+    }
+
+    for directive in &denied {
+        directives.remove(*directive);
+    }
+    let names: Vec<String> = denied
+        .iter()
+        .map(|directive| format!("`!{directive}`"))
+        .collect();
+    let message = format!(
+        "{} can only be used by admins, ignoring it.",
+        names.join(", ")
+    );
+    if let Err(why) = discord::say_reply(ctx, msg_ctx.channel_id, msg_ctx.message_id, message).await
+    {
+        tracing::error!("Error sending directive notice: {:?}", why);
+    }
+}
+
+/// Channel to answer in, the author's DMs for a private reply
+async fn reply_channel(ctx: &Context, msg_ctx: &MsgContextInfo, private: bool) -> ChannelId {
+    if !private {
+        return msg_ctx.channel_id;
+    }
+
+    match msg_ctx.author.create_dm_channel(&ctx.http).await {
+        Ok(channel) => channel.id,
+        Err(why) => {
+            tracing::error!(
+                "Error opening DM channel, replying in the channel: {:?}",
+                why
+            );
+            msg_ctx.channel_id
+        }
+    }
+}
+
+/// Send an in-character refusal if a budget is used up
+/// Returns true if the request was refused
+async fn refuse_if_over_budget(ctx: &Context, msg_ctx: &MsgContextInfo) -> bool {
+    let Some(exceeded) = check_budgets(msg_ctx)
+        .await
+        .into_iter()
+        .find(BudgetUsage::is_exceeded)
+    else {
+        return false;
+    };
+
+    tracing::info!(
+        "Budget exceeded ({} {}: {}), refusing to answer",
+        exceeded.budget.scope,
+        exceeded.budget.period,
+        exceeded.budget.limit.format_used(&exceeded.used)
+    );
+    if let Err(why) = discord::say_reply(
+        ctx,
+        msg_ctx.channel_id,
+        msg_ctx.message_id,
+        exceeded.refusal_message(),
+    )
+    .await
+    {
+        tracing::error!("Error sending budget refusal: {:?}", why);
+    }
+    true
+}
+
+/// Send the reply of the bot, or an error message if there is none
+/// A private reply is sent to the author by DM
+async fn send_bot_reply(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    result: eyre::Result<BotReply>,
+    private: bool,
+) {
+    let channel_id = reply_channel(ctx, msg_ctx, private).await;
+    // Only the first message of the answer is a reply
+    let mut reply_to = msg_ctx.reply_to(channel_id);
+
+    match result {
+        Ok(reply) => {
+            // Show how the model reasoned, hidden behind a spoiler
+            if let Some(summary) = &reply.reasoning_summary {
+                let spoiler = discord::spoiler(summary);
+                if let Err(why) =
+                    discord::say_reply(ctx, channel_id, reply_to.take(), spoiler).await
+                {
+                    tracing::error!("Error sending reasoning summary: {:?}", why);
+                }
+            }
+
+            // The reply was checked before it was stored
+            let text = reply.display_text();
+            let text = match reply.moderation {
+                Some(action @ ModerationAction::Block) => {
+                    moderation_notice(ContentDirection::Output, action).to_string()
+                }
+                Some(action @ ModerationAction::Warn) => format!(
+                    "{}\n{}",
+                    moderation_notice(ContentDirection::Output, action),
+                    discord::spoiler(&text)
+                ),
+                Some(ModerationAction::Log) | None => text,
+            };
+
             // Send the response back to Discord
-            if let Err(why) = discord::say(ctx, msg_ctx.channel_id, &response).await {
+            if let Err(why) = discord::say_reply(ctx, channel_id, reply_to, text).await {
                 tracing::error!("Error sending OpenAI response: {:?}", why);
             }
+
+            notify_budget_warnings(ctx, msg_ctx).await;
         }
         Err(err) => {
-            tracing::error!("Error getting OpenAI response: {:?}", err);
-            // Send an error message to the channel
-            let error_message =
-                format!("Sorry, I couldn't get a response from OpenAI at the moment. Error: {err}");
-            if let Err(why) = discord::say(ctx, msg_ctx.channel_id, error_message).await {
-                tracing::error!("Error sending error message: {:?}", why);
-            }
+            report_backend_error(ctx, msg_ctx, channel_id, FailedAction::Reply, &err).await;
+        }
+    }
+}
+
+/// Warn the developer about budgets that are close to their limit
+async fn notify_budget_warnings(ctx: &Context, msg_ctx: &MsgContextInfo) {
+    for warning in take_budget_warnings(msg_ctx).await {
+        let message = format!(
+            "Budget warning: {} {} budget is at {:.0}% ({})",
+            warning.budget.scope,
+            warning.budget.period,
+            warning.ratio * 100.0,
+            warning.budget.limit.format_used(&warning.used)
+        );
+        if let Err(err) = discord::send_dm_to_dev(ctx, &message).await {
+            tracing::error!("Failed to send budget warning: {:?}", err);
         }
     }
 }
@@ -138,11 +459,34 @@ impl EventHandler for MintyBotHandler {
             return;
         }
 
+        // Ignore regular chat without waiting on anything
+        let bot_id = ctx.cache.current_user().id;
+        if !may_mention_bot(&msg, bot_id) {
+            return;
+        }
+
+        // Take a place in the channel's queue right away, so mentions are handled in arrival order
+        let ticket = enqueue_channel_request(msg.channel_id);
+
         // Check if the bot is mentioned in the message
         let is_mentioned = check_mentioned(&ctx, &msg).await;
-        let content_without_mention = clean_message_content(&msg, ctx.cache.current_user().id);
+        let (content_without_mention, mut directives) = clean_message_content(&msg, bot_id);
 
         if is_mentioned {
+            // Throttle mention spam, admins are exempt
+            if !is_admin(author.id) {
+                match check_rate_limit(author.id, msg.channel_id).await {
+                    RateLimitDecision::Allowed => {}
+                    decision => {
+                        tracing::info!("Rate limited mention by {}: {:?}", author.name, decision);
+                        if let Err(why) = msg.react(&ctx.http, '⏳').await {
+                            tracing::error!("Error reacting to rate limited message: {:?}", why);
+                        }
+                        return;
+                    }
+                }
+            }
+
             // Create message context info
             let msg_ctx = MsgContextInfo::from_message(&ctx, &msg).await;
 
@@ -152,35 +496,146 @@ impl EventHandler for MintyBotHandler {
             // Log the received message
             tracing::debug!("Request: {:#?}", msg);
 
-            // Check if this is an admin command and process it if so
-            if process_admin_command(&ctx, &msg, &msg_ctx, &content_without_mention).await {
+            // Pick up a reply that was cut off instead of answering a new message
+            if content_without_mention == CONTINUE_COMMAND {
+                ticket.wait_turn().await;
+                continue_bot_reply(&ctx, &msg_ctx).await;
                 return;
             }
 
+            // Check if this is an admin command and process it if so
+            if is_admin_command(&content_without_mention) {
+                ticket.wait_turn().await;
+                if process_admin_command(&ctx, &msg, &msg_ctx, &content_without_mention).await {
+                    return;
+                }
+            }
+
+            restrict_directives(&ctx, &msg_ctx, &mut directives).await;
             let selected_name = get_best_name_of_author(&ctx, &msg_ctx).await;
 
-            // Extract image URL from attachments if present
-            let image_url = msg
-                .attachments
-                .iter()
-                .find(|attachment| {
-                    attachment
-                        .content_type
-                        .as_ref()
-                        .map(|ct| ct.starts_with("image/"))
-                        .unwrap_or(false)
-                })
-                .map(|attachment| attachment.url.clone());
-
-            // Process the mention and send a response
-            process_bot_mention(
-                &ctx,
-                &msg_ctx,
-                content_without_mention,
-                selected_name,
-                image_url,
-            )
-            .await;
+            // Check what the user wrote before it reaches the model or the image generator
+            if block_flagged_input(&ctx, &msg_ctx, &content_without_mention).await {
+                return;
+            }
+
+            // Draw an image instead of answering with text
+            if let Some(prompt) = parse_draw_command(&content_without_mention) {
+                let message = ChatMessage::user(content_without_mention.clone(), selected_name);
+                ticket.wait_turn().await;
+                draw_image(&ctx, &msg_ctx, message, prompt, directives.is_transient()).await;
+                return;
+            }
+
+            // Transcribe a voice message or audio file if present
+            let audio = msg.attachments.iter().find(|attachment| {
+                is_audio_attachment(attachment.content_type.as_deref(), &attachment.filename)
+            });
+            // Transcription is paid as well, so don't start it over the budget
+            if audio.is_some() && refuse_if_over_budget(&ctx, &msg_ctx).await {
+                return;
+            }
+            let transcript = match audio {
+                Some(attachment) => match transcribe_attachment(&ctx, &msg_ctx, attachment).await {
+                    Ok(transcript) => Some(transcript),
+                    // Nothing to answer without the transcript
+                    Err(err) if content_without_mention.trim().is_empty() => {
+                        report_backend_error(
+                            &ctx,
+                            &msg_ctx,
+                            msg.channel_id,
+                            FailedAction::Transcribe,
+                            &err,
+                        )
+                        .await;
+                        return;
+                    }
+                    Err(err) => {
+                        tracing::error!("Error transcribing audio: {:?}", err);
+                        None
+                    }
+                },
+                None => None,
+            };
+
+            // Show what was heard if enabled
+            if let Some(transcript) = &transcript
+                && is_transcript_reply_enabled().await
+                && let Err(why) = discord::say_reply(
+                    &ctx,
+                    msg.channel_id,
+                    Some(msg.id),
+                    discord::quote(transcript),
+                )
+                .await
+            {
+                tracing::error!("Error sending transcript: {:?}", why);
+            }
+
+            // Check what the user said as well
+            if let Some(transcript) = &transcript
+                && block_flagged_input(&ctx, &msg_ctx, transcript).await
+            {
+                return;
+            }
+
+            // Collect the images of the message, its embeds and the message it replies to
+            let max_images = get_max_images_per_message().await as usize;
+            let image_urls = collect_image_urls(&msg, max_images);
+            // Keep copies, as Discord's URLs expire, unless the message isn't kept at all
+            let mut pending_files = PendingFiles::default();
+            let image_urls = if directives.is_transient() {
+                image_urls
+            } else {
+                persist_images(image_urls, &mut pending_files).await
+            };
+
+            // Read text files and PDFs attached to the message
+            let file_attachments = load_file_attachments(&msg.attachments).await;
+            // PDF URLs expire like image URLs
+            let files = if directives.is_transient() {
+                file_attachments.files
+            } else {
+                persist_files(file_attachments.files, &mut pending_files).await
+            };
+
+            let mut message = if let Some(transcript) = transcript {
+                ChatMessage::user_with_voice(content_without_mention, selected_name, transcript)
+                    .with_images(image_urls)
+            } else {
+                ChatMessage::user_with_images(content_without_mention, selected_name, image_urls)
+            };
+            for text in file_attachments.texts {
+                message = message.with_text_attachment(text);
+            }
+            for (filename, url) in files {
+                message = message.with_file(filename, url);
+            }
+            let mention = PendingMention {
+                msg_ctx,
+                message,
+                pending_files,
+            };
+
+            // Wait until earlier mentions in this channel are handled
+            // A mention with directives is answered on its own
+            let mentions = if directives.is_empty() && is_batching_enabled(msg.channel_id).await {
+                // Mentions arriving in the meantime are answered together, up to another author's
+                push_pending_mention(mention);
+                ticket.wait_turn().await;
+                take_pending_mentions(msg.channel_id, author.id)
+            } else {
+                ticket.wait_turn().await;
+                vec![mention]
+            };
+
+            // Empty if already answered together with an earlier mention
+            if mentions.is_empty() {
+                return;
+            }
+
+            // Process the mentions and send a response
+            process_bot_mention(&ctx, mentions, directives).await;
         }
     }
 
@@ -194,9 +649,24 @@ impl EventHandler for MintyBotHandler {
         let bot_name = ready.user.name.clone();
         tracing::info!("{} is connected!", bot_name);
 
+        if let Err(err) = register_slash_commands(&ctx).await {
+            tracing::error!("Failed to register slash commands: {:?}", err);
+        }
+
         // Notify developer that the bot has started
         notify_bot_startup(&ctx, &bot_name).await;
     }
+
+    // Slash commands and their autocomplete requests
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        if let Interaction::Command(command) = &interaction
+            && let Some(user_command) = to_user_command(command)
+        {
+            run_user_command(&ctx, command, user_command).await;
+            return;
+        }
+        handle_interaction(&ctx, interaction).await;
+    }
 }
 
 /// Notify the developer that the bot has started
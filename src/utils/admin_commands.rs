@@ -1,22 +1,44 @@
+use serenity::all::CommandInteraction;
 use serenity::model::channel::Message;
-use serenity::model::id::UserId;
+use serenity::model::id::{ChannelId, MessageId, UserId};
 use serenity::prelude::*;
+use std::fmt::Display;
 use std::str::FromStr;
 use strum::IntoEnumIterator;
 
 use crate::discord;
 use crate::msg_context::MsgContextInfo;
 use crate::statics::DEV_USER_ID;
+use crate::utils::backend_error::Language;
+use crate::utils::budget::{Budget, BudgetLimit, BudgetPeriod, BudgetScope, parse_budget_scope};
+use crate::utils::circuit_breaker::get_circuit_state;
 use crate::utils::conversation::ChatMessage;
+use crate::utils::directives::Directive;
+use crate::utils::model_router::{ChannelRouting, DEFAULT_LIGHT_MODEL};
+use crate::utils::moderation::GuildModeration;
 use crate::utils::persistence::{
-    BotPersonality, add_message, change_model, get_channel_personality, get_conversation_history,
-    get_current_model, get_total_history_count, remove_conversation, set_channel_personality,
+    BotPersonality, add_message, change_model, get_admin_only_directives, get_auto_continue_rounds,
+    get_budget_usages, get_channel_personality, get_channel_routing, get_conversation_history,
+    get_current_model, get_disabled_tools, get_fallback_models, get_guild_language,
+    get_guild_moderation, get_last_answered_model, get_max_images_per_message,
+    get_moderation_words, get_prompt_cache_stats, get_rate_limit_config, get_total_history_count,
+    get_usage_ledger, invalidate_response_chain, is_batching_enabled, is_reasoning_summary_enabled,
+    is_reply_ping_enabled, is_response_chaining_enabled, is_transcript_reply_enabled,
+    remove_budgets, remove_conversation, reset_budget_spend, set_auto_continue_rounds,
+    set_batching_enabled, set_budget, set_channel_personality, set_channel_routing,
+    set_directive_admin_only, set_fallback_models, set_guild_language, set_guild_moderation,
+    set_max_images_per_message, set_moderation_words, set_rate_limit_config, set_reasoning_summary,
+    set_reply_ping, set_response_chaining, set_tool_enabled, set_transcript_replies,
 };
+use crate::utils::rate_limit::{RateLimit, reset_rate_limiter};
+use crate::utils::token_count::{estimate_tokens, input_token_limit};
+use crate::utils::tools::BuiltinTool;
+use crate::utils::usage::{UsageLedger, UsagePeriod, UsageTotals, today_kst};
 
 use super::persistence::get_channel_ids;
 
 /// Enum representing different admin command types
-#[derive(Debug)]
+#[derive(Debug, strum_macros::EnumIter)]
 pub enum AdminCommand {
     Forget,
     Model(String),
@@ -24,6 +46,45 @@ pub enum AdminCommand {
     DevMessage(String),
     GetPersonality,
     SetPersonality(String),
+    GetTools,
+    SetTool(String),
+    Chaining(String),
+    Fallback(String),
+    Usage(String),
+    Budget(String),
+    RateLimit(String),
+    Batching(String),
+    Reasoning(String),
+    AutoContinue(String),
+    Transcripts(String),
+    Images(String),
+    Moderation(String),
+    Routing(String),
+    Directives(String),
+    Language(String),
+    ReplyPing(String),
+}
+
+/// Where the output of an admin command goes
+pub enum CommandOutput<'a> {
+    /// Said in the channel of the text command, replying to it
+    Channel(ChannelId, Option<MessageId>),
+    /// Sent only to the user of the slash command
+    Interaction(&'a CommandInteraction),
+}
+
+impl CommandOutput<'_> {
+    /// Send command output, splitting long messages
+    pub async fn say(&self, ctx: &Context, msg: impl Display) -> eyre::Result<()> {
+        match self {
+            CommandOutput::Channel(channel_id, reply_to) => {
+                discord::say_reply(ctx, *channel_id, *reply_to, msg).await
+            }
+            CommandOutput::Interaction(interaction) => {
+                discord::say_ephemeral(ctx, interaction, msg).await
+            }
+        }
+    }
 }
 
 /// Process an admin command if present in the message
@@ -37,33 +98,60 @@ pub async fn process_admin_command(
         return false;
     };
 
+    let output = CommandOutput::Channel(msg_ctx.channel_id, msg_ctx.message_id);
+
     // check admin
     if !is_admin(msg_ctx.author_id) {
-        let _ = discord::say(
-            ctx,
-            msg_ctx.channel_id,
-            "You are not admin. Request denied.",
-        )
-        .await;
+        let _ = output.say(ctx, "You are not admin. Request denied.").await;
         return false;
     }
 
+    run_admin_command(ctx, msg_ctx, &output, command).await;
+    true
+}
+
+/// Run an admin command, the caller checks that the user is an admin
+pub async fn run_admin_command(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    output: &CommandOutput<'_>,
+    command: AdminCommand,
+) {
     match command {
-        AdminCommand::Forget => handle_forget_command(ctx, msg_ctx).await,
-        AdminCommand::Model(model_name) => handle_model_command(ctx, msg_ctx, &model_name).await,
-        AdminCommand::Status => handle_status_command(ctx, msg_ctx).await,
-        AdminCommand::DevMessage(message) => handle_dev_command(ctx, msg_ctx, &message).await,
-        AdminCommand::GetPersonality => handle_get_personality_command(ctx, msg_ctx).await,
+        AdminCommand::Forget => handle_forget_command(ctx, msg_ctx, output).await,
+        AdminCommand::Model(model_name) => handle_model_command(ctx, output, &model_name).await,
+        AdminCommand::Status => handle_status_command(ctx, msg_ctx, output).await,
+        AdminCommand::DevMessage(message) => {
+            handle_dev_command(ctx, msg_ctx, output, &message).await
+        }
+        AdminCommand::GetPersonality => handle_get_personality_command(ctx, msg_ctx, output).await,
         AdminCommand::SetPersonality(personality) => {
-            handle_set_personality_command(ctx, msg_ctx, &personality).await
+            handle_set_personality_command(ctx, msg_ctx, output, &personality).await
         }
+        AdminCommand::GetTools => handle_get_tools_command(ctx, msg_ctx, output).await,
+        AdminCommand::SetTool(args) => handle_set_tool_command(ctx, msg_ctx, output, &args).await,
+        AdminCommand::Chaining(args) => handle_chaining_command(ctx, output, &args).await,
+        AdminCommand::Fallback(args) => handle_fallback_command(ctx, output, &args).await,
+        AdminCommand::Usage(args) => handle_usage_command(ctx, output, &args).await,
+        AdminCommand::Budget(args) => handle_budget_command(ctx, msg_ctx, output, &args).await,
+        AdminCommand::RateLimit(args) => handle_rate_limit_command(ctx, output, &args).await,
+        AdminCommand::Batching(args) => handle_batching_command(ctx, msg_ctx, output, &args).await,
+        AdminCommand::Reasoning(args) => handle_reasoning_command(ctx, output, &args).await,
+        AdminCommand::AutoContinue(args) => handle_auto_continue_command(ctx, output, &args).await,
+        AdminCommand::Transcripts(args) => handle_transcripts_command(ctx, output, &args).await,
+        AdminCommand::Images(args) => handle_images_command(ctx, output, &args).await,
+        AdminCommand::Moderation(args) => {
+            handle_moderation_command(ctx, msg_ctx, output, &args).await
+        }
+        AdminCommand::Routing(args) => handle_routing_command(ctx, msg_ctx, output, &args).await,
+        AdminCommand::Directives(args) => handle_directives_command(ctx, output, &args).await,
+        AdminCommand::Language(args) => handle_language_command(ctx, msg_ctx, output, &args).await,
+        AdminCommand::ReplyPing(args) => handle_reply_ping_command(ctx, output, &args).await,
     }
-
-    true
 }
 
 /// Parse a message to check if it contains an admin command
-fn parse_admin_command(content: &str) -> Option<AdminCommand> {
+pub(crate) fn parse_admin_command(content: &str) -> Option<AdminCommand> {
     let content = content.trim();
 
     if content == "<forget>" {
@@ -90,33 +178,110 @@ fn parse_admin_command(content: &str) -> Option<AdminCommand> {
         return Some(AdminCommand::SetPersonality(personality.trim().to_string()));
     }
 
+    if content == "<tools>" {
+        return Some(AdminCommand::GetTools);
+    }
+
+    if let Some(args) = content.strip_prefix("<tools>") {
+        return Some(AdminCommand::SetTool(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<chaining>") {
+        return Some(AdminCommand::Chaining(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<fallback>") {
+        return Some(AdminCommand::Fallback(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<usage>") {
+        return Some(AdminCommand::Usage(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<budget>") {
+        return Some(AdminCommand::Budget(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<ratelimit>") {
+        return Some(AdminCommand::RateLimit(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<batching>") {
+        return Some(AdminCommand::Batching(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<reasoning>") {
+        return Some(AdminCommand::Reasoning(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<autocontinue>") {
+        return Some(AdminCommand::AutoContinue(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<transcripts>") {
+        return Some(AdminCommand::Transcripts(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<images>") {
+        return Some(AdminCommand::Images(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<moderation>") {
+        return Some(AdminCommand::Moderation(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<routing>") {
+        return Some(AdminCommand::Routing(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<directives>") {
+        return Some(AdminCommand::Directives(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<language>") {
+        return Some(AdminCommand::Language(args.trim().to_string()));
+    }
+
+    if let Some(args) = content.strip_prefix("<replyping>") {
+        return Some(AdminCommand::ReplyPing(args.trim().to_string()));
+    }
+
     None
 }
 
+/// Check if a message is an admin command
+pub fn is_admin_command(content: &str) -> bool {
+    parse_admin_command(content).is_some()
+}
+
 /// Check if the user is an admin (developer)
-fn is_admin(author_id: UserId) -> bool {
+pub fn is_admin(author_id: UserId) -> bool {
     author_id == **DEV_USER_ID
 }
 
 /// Handles the forget command from authorized users
-async fn handle_forget_command(ctx: &Context, msg_ctx: &MsgContextInfo) {
+async fn handle_forget_command(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    output: &CommandOutput<'_>,
+) {
     let channel_id = msg_ctx.channel_id;
 
     // Clear conversation history for this channel
     remove_conversation(channel_id).await;
 
     // Send confirmation message
-    let _ = discord::say(ctx, channel_id, "Conversation history has been cleared.").await;
+    let _ = output
+        .say(ctx, "Conversation history has been cleared.")
+        .await;
 }
 
 /// Handles the model change command from authorized users
-async fn handle_model_command(ctx: &Context, msg_ctx: &MsgContextInfo, model_name: &str) {
-    let channel_id = msg_ctx.channel_id;
-
+async fn handle_model_command(ctx: &Context, output: &CommandOutput<'_>, model_name: &str) {
     // Trim the model name and check if it's empty
     let model_name = model_name.trim();
     if model_name.is_empty() {
-        let _ = discord::say(ctx, channel_id, "Please specify a model name.").await;
+        let _ = output.say(ctx, "Please specify a model name.").await;
         return;
     }
 
@@ -124,44 +289,122 @@ async fn handle_model_command(ctx: &Context, msg_ctx: &MsgContextInfo, model_nam
     let response = change_model(model_name).await;
 
     // Send the response
-    let _ = discord::say(ctx, channel_id, response).await;
+    let _ = output.say(ctx, response).await;
 }
 
 /// Handles the status command to display bot state information
-async fn handle_status_command(ctx: &Context, msg_ctx: &MsgContextInfo) {
+async fn handle_status_command(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    output: &CommandOutput<'_>,
+) {
     let channel_id = msg_ctx.channel_id;
 
     let current_model = get_current_model().await;
+    let fallback_models = get_fallback_models().await;
+    let last_answered_model = get_last_answered_model().await;
     let personality = get_channel_personality(channel_id).await;
+    let routing = get_channel_routing(channel_id).await;
+    let language = get_guild_language(msg_ctx.guild_id).await;
 
     let channel_history = get_conversation_history(channel_id).await;
     let channel_history_count = channel_history.len().saturating_sub(1); // exclude system prompt
+    let channel_tokens = estimate_tokens(&channel_history);
+    let context_limit = input_token_limit(&current_model);
+    let (circuit_state, failures) = get_circuit_state().await;
+    let cache_stats = get_prompt_cache_stats(channel_id).await;
+    let prompt_cache = if cache_stats.requests == 0 {
+        "no requests yet".to_string()
+    } else {
+        format!(
+            "{:.1}% of {} input tokens cached over {} requests (last {:.1}%)",
+            cache_stats.hit_rate() * 100.0,
+            cache_stats.input_tokens,
+            cache_stats.requests,
+            cache_stats.last_hit_rate * 100.0
+        )
+    };
 
     let channel_ids = get_channel_ids().await;
     let channel_count = channel_ids.len();
 
     let total_history_count = get_total_history_count().await;
 
+    let chaining = if is_response_chaining_enabled().await {
+        "on"
+    } else {
+        "off"
+    };
+    let reasoning = if is_reasoning_summary_enabled().await {
+        "on"
+    } else {
+        "off"
+    };
+    let reply_ping = if is_reply_ping_enabled().await {
+        "on"
+    } else {
+        "off"
+    };
+    let transcripts = if is_transcript_reply_enabled().await {
+        "on"
+    } else {
+        "off"
+    };
+
+    let fallback_models = if fallback_models.is_empty() {
+        "none".to_string()
+    } else {
+        format!("`{}`", fallback_models.join(" → "))
+    };
+    let last_answered_model = match last_answered_model {
+        Some(model) if routing.as_ref().is_some_and(|r| r.light_model == model) => {
+            format!("`{model}` (routed)")
+        }
+        Some(model) if model != current_model => format!("`{model}` (fallback)"),
+        Some(model) => format!("`{model}`"),
+        None => "-".to_string(),
+    };
+    let routing = match routing {
+        Some(routing) => format!("on (light model `{}`)", routing.light_model),
+        None => "off".to_string(),
+    };
+
     let status_message = format!(
         "\
 **Bot Status**
 - Current model: `{current_model}`
+- Fallback models: {fallback_models}
+- This channel routing: {routing}
+- Last answered by: {last_answered_model}
+- OpenAI backend: {circuit_state} ({failures} failures in a row)
 - Current personality: `{personality}`
+- Error message language: `{language}`
 - This channel history: {channel_history_count} messages
-- Total history: {total_history_count} messages across {channel_count} channels",
+- This channel context: ~{channel_tokens} tokens (limit {context_limit})
+- This channel prompt cache: {prompt_cache}
+- Total history: {total_history_count} messages across {channel_count} channels
+- Response chaining: {chaining}
+- Reasoning summaries: {reasoning}
+- Voice transcript replies: {transcripts}
+- Reply pings: {reply_ping}",
     );
 
-    let _ = discord::say(ctx, channel_id, &status_message).await;
+    let _ = output.say(ctx, &status_message).await;
 }
 
 /// Handles the developer message command
-async fn handle_dev_command(ctx: &Context, msg_ctx: &MsgContextInfo, dev_message: &str) {
+async fn handle_dev_command(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    output: &CommandOutput<'_>,
+    dev_message: &str,
+) {
     let channel_id = msg_ctx.channel_id;
 
     // Trim the developer message and check if it's empty
     let dev_message = dev_message.trim();
     if dev_message.is_empty() {
-        let _ = discord::say(ctx, channel_id, "Please specify a developer message.").await;
+        let _ = output.say(ctx, "Please specify a developer message.").await;
         return;
     }
 
@@ -169,17 +412,21 @@ async fn handle_dev_command(ctx: &Context, msg_ctx: &MsgContextInfo, dev_message
     let dev_message = dev_message.to_string();
     add_message(channel_id, ChatMessage::developer(dev_message)).await;
 
+    // Resend the full history next time so the developer message is not lost
+    invalidate_response_chain(channel_id).await;
+
     // Send confirmation
-    let _ = discord::say(
-        ctx,
-        channel_id,
-        "Developer message added to conversation history.",
-    )
-    .await;
+    let _ = output
+        .say(ctx, "Developer message added to conversation history.")
+        .await;
 }
 
 /// Handles the get personality command
-async fn handle_get_personality_command(ctx: &Context, msg_ctx: &MsgContextInfo) {
+async fn handle_get_personality_command(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    output: &CommandOutput<'_>,
+) {
     let channel_id = msg_ctx.channel_id;
 
     // Get the current personality for this channel
@@ -194,13 +441,14 @@ async fn handle_get_personality_command(ctx: &Context, msg_ctx: &MsgContextInfo)
     );
 
     // Send the message
-    let _ = discord::say(ctx, channel_id, &message).await;
+    let _ = output.say(ctx, &message).await;
 }
 
 /// Handles the set personality command
 async fn handle_set_personality_command(
     ctx: &Context,
     msg_ctx: &MsgContextInfo,
+    output: &CommandOutput<'_>,
     personality_input: &str,
 ) {
     let channel_id = msg_ctx.channel_id;
@@ -208,63 +456,907 @@ async fn handle_set_personality_command(
     // Trim the personality input and check if it's empty
     let personality_input = personality_input.trim();
     if personality_input.is_empty() {
-        let _ = discord::say(ctx, channel_id, "Please specify a personality name.").await;
+        let _ = output.say(ctx, "Please specify a personality name.").await;
         return;
     }
 
     // Check for custom personality format: "custom <system prompt>"
-    let personality = if personality_input.to_lowercase().starts_with("custom ") {
-        // Extract the custom system prompt (everything after "custom ")
-        let custom_prompt = personality_input[7..].trim().to_string();
+    let personality =
+        if personality_input.to_lowercase().starts_with("custom ") {
+            // Extract the custom system prompt (everything after "custom ")
+            let custom_prompt = personality_input[7..].trim().to_string();
+
+            if custom_prompt.is_empty() {
+                let _ = output
+                    .say(ctx, "Please provide a system prompt after 'custom'.")
+                    .await;
+                return;
+            }
+
+            // Create a custom personality with the provided prompt
+            BotPersonality::custom(custom_prompt)
+        } else {
+            // Try to parse as a predefined personality
+            match BotPersonality::from_str(personality_input) {
+                Ok(p) => p,
+                Err(_) => {
+                    // List all available personalities using EnumIter
+                    let mut available_personalities: Vec<String> = BotPersonality::iter()
+                        .filter(|p| !matches!(p, BotPersonality::Custom(_))) // Filter out Custom
+                        .map(|p| p.to_string())
+                        .collect();
+
+                    // Add custom option
+                    available_personalities.push("Custom <system prompt>".to_string());
 
-        if custom_prompt.is_empty() {
-            let _ = discord::say(
+                    let _ = output.say(ctx, format!(
+                        "Unknown personality: {personality_input}\nAvailable personalities: {}",
+                        available_personalities.join(", ")
+                    ),
+                )
+                .await;
+                    return;
+                }
+            }
+        };
+
+    // Set the personality for this channel
+    set_channel_personality(channel_id, personality.clone()).await;
+
+    // Send confirmation
+    let _ = output
+        .say(
+            ctx,
+            format!("Personality set to {personality} for this channel."),
+        )
+        .await;
+}
+
+/// Handles the get tools command
+async fn handle_get_tools_command(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    output: &CommandOutput<'_>,
+) {
+    let channel_id = msg_ctx.channel_id;
+
+    let disabled = get_disabled_tools(channel_id).await;
+    let tool_lines: Vec<String> = BuiltinTool::iter()
+        .map(|tool| {
+            let state = if disabled.contains(&tool) {
+                "disabled"
+            } else {
+                "enabled"
+            };
+            format!("- `{tool}`: {state}")
+        })
+        .collect();
+
+    let message = format!(
+        "**Tools in this channel**\n{}\n\nUsage: `<tools> enable|disable <tool>`",
+        tool_lines.join("\n")
+    );
+
+    let _ = output.say(ctx, &message).await;
+}
+
+/// Handles the enable/disable tool command
+async fn handle_set_tool_command(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    output: &CommandOutput<'_>,
+    args: &str,
+) {
+    let channel_id = msg_ctx.channel_id;
+
+    let mut parts = args.split_whitespace();
+    let enabled = match parts.next().map(|s| s.to_lowercase()).as_deref() {
+        Some("enable") => true,
+        Some("disable") => false,
+        _ => {
+            let _ = output
+                .say(ctx, "Usage: `<tools> enable|disable <tool>`")
+                .await;
+            return;
+        }
+    };
+
+    let tool_name = parts.next().unwrap_or_default();
+    let Ok(tool) = BuiltinTool::from_str(tool_name) else {
+        let available_tools: Vec<String> = BuiltinTool::iter().map(|t| t.to_string()).collect();
+        let _ = output
+            .say(
                 ctx,
-                channel_id,
-                "Please provide a system prompt after 'custom'.",
+                format!(
+                    "Unknown tool: {tool_name}\nAvailable tools: {}",
+                    available_tools.join(", ")
+                ),
             )
             .await;
+        return;
+    };
+
+    // Update the tool setting for this channel
+    set_tool_enabled(channel_id, tool, enabled).await;
+
+    // Send confirmation
+    let state = if enabled { "enabled" } else { "disabled" };
+    let _ = output
+        .say(ctx, format!("Tool {tool} {state} for this channel."))
+        .await;
+}
+
+/// Handles the response chaining command
+async fn handle_chaining_command(ctx: &Context, output: &CommandOutput<'_>, args: &str) {
+    let enabled = match args.to_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        "" => {
+            let state = if is_response_chaining_enabled().await {
+                "on"
+            } else {
+                "off"
+            };
+            let _ = output
+                .say(
+                    ctx,
+                    format!("Response chaining is {state}.\nUsage: `<chaining> on|off`"),
+                )
+                .await;
+            return;
+        }
+        _ => {
+            let _ = output.say(ctx, "Usage: `<chaining> on|off`").await;
             return;
         }
+    };
+
+    // Change the setting for all channels
+    set_response_chaining(enabled).await;
 
-        // Create a custom personality with the provided prompt
-        BotPersonality::custom(custom_prompt)
+    // Send confirmation
+    let message = if enabled {
+        "Response chaining enabled. Only new messages will be sent with previous_response_id."
     } else {
-        // Try to parse as a predefined personality
-        match BotPersonality::from_str(personality_input) {
-            Ok(p) => p,
-            Err(_) => {
-                // List all available personalities using EnumIter
-                let mut available_personalities: Vec<String> = BotPersonality::iter()
-                    .filter(|p| !matches!(p, BotPersonality::Custom(_))) // Filter out Custom
-                    .map(|p| p.to_string())
-                    .collect();
+        "Response chaining disabled. The full history will be sent with every request."
+    };
+    let _ = output.say(ctx, message).await;
+}
+
+// Upper bound of automatic continuation rounds, each of them is a full request
+const MAX_AUTO_CONTINUE_ROUNDS: u32 = 10;
 
-                // Add custom option
-                available_personalities.push("Custom <system prompt>".to_string());
+/// Handles the automatic continuation command
+async fn handle_auto_continue_command(ctx: &Context, output: &CommandOutput<'_>, args: &str) {
+    if args.is_empty() {
+        let rounds = get_auto_continue_rounds().await;
+        let message = format!(
+            "Replies cut off by the output limit are continued up to {rounds} time(s).\nUsage: `<autocontinue> <rounds>` (0 to disable)"
+        );
+        let _ = output.say(ctx, &message).await;
+        return;
+    }
+
+    let rounds = match args.parse::<u32>() {
+        Ok(rounds) if rounds <= MAX_AUTO_CONTINUE_ROUNDS => rounds,
+        _ => {
+            let message = format!(
+                "Usage: `<autocontinue> <rounds>` (0 to {MAX_AUTO_CONTINUE_ROUNDS}, 0 to disable)"
+            );
+            let _ = output.say(ctx, &message).await;
+            return;
+        }
+    };
 
-                let _ = discord::say(
+    set_auto_continue_rounds(rounds).await;
+
+    let message = if rounds == 0 {
+        "Automatic continuation disabled. Use `<continue>` to continue a cut off reply.".to_string()
+    } else {
+        format!("Replies cut off by the output limit will be continued up to {rounds} time(s).")
+    };
+    let _ = output.say(ctx, &message).await;
+}
+
+/// Handles the reasoning summary command
+async fn handle_reasoning_command(ctx: &Context, output: &CommandOutput<'_>, args: &str) {
+    let enabled = match args.to_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        "" => {
+            let state = if is_reasoning_summary_enabled().await {
+                "on"
+            } else {
+                "off"
+            };
+            let _ = output
+                .say(
                     ctx,
-                    channel_id,
-                    format!(
-                        "Unknown personality: {personality_input}\nAvailable personalities: {}",
-                        available_personalities.join(", ")
-                    ),
+                    format!("Reasoning summaries are {state}.\nUsage: `<reasoning> on|off`"),
                 )
                 .await;
-                return;
+            return;
+        }
+        _ => {
+            let _ = output.say(ctx, "Usage: `<reasoning> on|off`").await;
+            return;
+        }
+    };
+
+    // Change the setting for all channels
+    set_reasoning_summary(enabled).await;
+
+    // Send confirmation
+    let message = if enabled {
+        "Reasoning summaries enabled. They will be posted as spoilers before the reply."
+    } else {
+        "Reasoning summaries disabled."
+    };
+    let _ = output.say(ctx, message).await;
+}
+
+const MODERATION_USAGE: &str = "Usage: `<moderation> openai|wordlist [threshold] [block|warn|log]`, `<moderation> off`, `<moderation> words [add|remove <words...>|clear]`";
+
+/// Handles the moderation command
+async fn handle_moderation_command(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    output: &CommandOutput<'_>,
+    args: &str,
+) {
+    let args: Vec<&str> = args.split_whitespace().collect();
+
+    if args
+        .first()
+        .is_some_and(|arg| arg.eq_ignore_ascii_case("words"))
+    {
+        handle_moderation_words_command(ctx, output, &args[1..]).await;
+        return;
+    }
+
+    let Some(guild_id) = msg_ctx.guild_id else {
+        let _ = output
+            .say(ctx, "Moderation is set per guild, use this in a guild.")
+            .await;
+        return;
+    };
+
+    let message = match args.as_slice() {
+        [] => match get_guild_moderation(guild_id).await {
+            Some(settings) => format!("Moderation in this guild: {settings}\n{MODERATION_USAGE}"),
+            None => format!("Moderation is off in this guild.\n{MODERATION_USAGE}"),
+        },
+        [off] if off.eq_ignore_ascii_case("off") => {
+            set_guild_moderation(guild_id, None).await;
+            "Moderation disabled in this guild.".to_string()
+        }
+        args => match GuildModeration::from_args(args) {
+            Ok(settings) => {
+                set_guild_moderation(guild_id, Some(settings)).await;
+                format!("Moderation in this guild set to {settings}.")
             }
+            Err(e) => format!("{e}\n{MODERATION_USAGE}"),
+        },
+    };
+    let _ = output.say(ctx, &message).await;
+}
+
+/// Handles the moderation word list subcommand
+async fn handle_moderation_words_command(ctx: &Context, output: &CommandOutput<'_>, args: &[&str]) {
+    let mut words = get_moderation_words().await;
+
+    let message = match args {
+        [] if words.is_empty() => "The moderation word list is empty.".to_string(),
+        [] => format!("Moderation words: ||{}||", words.join(", ")),
+        [clear] if clear.eq_ignore_ascii_case("clear") => {
+            set_moderation_words(Vec::new()).await;
+            "Moderation word list cleared.".to_string()
+        }
+        [add, new_words @ ..] if add.eq_ignore_ascii_case("add") && !new_words.is_empty() => {
+            for word in new_words {
+                let word = word.to_lowercase();
+                if !words.contains(&word) {
+                    words.push(word);
+                }
+            }
+            let count = words.len();
+            set_moderation_words(words).await;
+            format!("Moderation word list updated ({count} words).")
         }
+        [remove, old_words @ ..]
+            if remove.eq_ignore_ascii_case("remove") && !old_words.is_empty() =>
+        {
+            words.retain(|word| !old_words.iter().any(|old| old.eq_ignore_ascii_case(word)));
+            let count = words.len();
+            set_moderation_words(words).await;
+            format!("Moderation word list updated ({count} words).")
+        }
+        _ => MODERATION_USAGE.to_string(),
     };
+    let _ = output.say(ctx, &message).await;
+}
 
-    // Set the personality for this channel
-    set_channel_personality(channel_id, personality.clone()).await;
+// Upper bound of images per message, each of them adds input tokens
+const MAX_IMAGES_PER_MESSAGE: u32 = 10;
+
+/// Handles the images per message command
+async fn handle_images_command(ctx: &Context, output: &CommandOutput<'_>, args: &str) {
+    if args.is_empty() {
+        let max_images = get_max_images_per_message().await;
+        let message = format!(
+            "Up to {max_images} image(s) per message are sent to the model.\nUsage: `<images> <count>` (0 to ignore images)"
+        );
+        let _ = output.say(ctx, &message).await;
+        return;
+    }
+
+    let max_images = match args.parse::<u32>() {
+        Ok(max_images) if max_images <= MAX_IMAGES_PER_MESSAGE => max_images,
+        _ => {
+            let message = format!(
+                "Usage: `<images> <count>` (0 to {MAX_IMAGES_PER_MESSAGE}, 0 to ignore images)"
+            );
+            let _ = output.say(ctx, &message).await;
+            return;
+        }
+    };
+
+    set_max_images_per_message(max_images).await;
+
+    let message = if max_images == 0 {
+        "Images will be ignored.".to_string()
+    } else {
+        format!("Up to {max_images} image(s) per message will be sent to the model.")
+    };
+    let _ = output.say(ctx, &message).await;
+}
+
+/// Handles the voice transcript replies command
+async fn handle_transcripts_command(ctx: &Context, output: &CommandOutput<'_>, args: &str) {
+    let enabled = match args.to_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        "" => {
+            let state = if is_transcript_reply_enabled().await {
+                "on"
+            } else {
+                "off"
+            };
+            let _ = output
+                .say(
+                    ctx,
+                    format!("Voice transcript replies are {state}.\nUsage: `<transcripts> on|off`"),
+                )
+                .await;
+            return;
+        }
+        _ => {
+            let _ = output.say(ctx, "Usage: `<transcripts> on|off`").await;
+            return;
+        }
+    };
+
+    // Change the setting for all channels
+    set_transcript_replies(enabled).await;
 
     // Send confirmation
-    let _ = discord::say(
-        ctx,
-        channel_id,
-        format!("Personality set to {personality} for this channel."),
+    let message = if enabled {
+        "Voice transcript replies enabled. Transcripts will be posted as a reply to voice messages."
+    } else {
+        "Voice transcript replies disabled."
+    };
+    let _ = output.say(ctx, message).await;
+}
+
+/// Handles the reply ping command
+async fn handle_reply_ping_command(ctx: &Context, output: &CommandOutput<'_>, args: &str) {
+    let enabled = match args.to_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        "" => {
+            let state = if is_reply_ping_enabled().await {
+                "on"
+            } else {
+                "off"
+            };
+            let _ = output
+                .say(
+                    ctx,
+                    format!("Reply pings are {state}.\nUsage: `<replyping> on|off`"),
+                )
+                .await;
+            return;
+        }
+        _ => {
+            let _ = output.say(ctx, "Usage: `<replyping> on|off`").await;
+            return;
+        }
+    };
+
+    // Change the setting for all channels
+    set_reply_ping(enabled).await;
+
+    // Send confirmation
+    let message = if enabled {
+        "Reply pings enabled. Answers will mention the author of the message they reply to."
+    } else {
+        "Reply pings disabled. Answers will reply without a mention."
+    };
+    let _ = output.say(ctx, message).await;
+}
+
+/// Handles the batching command
+async fn handle_batching_command(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    output: &CommandOutput<'_>,
+    args: &str,
+) {
+    let channel_id = msg_ctx.channel_id;
+
+    let enabled = match args.to_lowercase().as_str() {
+        "on" => true,
+        "off" => false,
+        "" => {
+            let state = if is_batching_enabled(channel_id).await {
+                "on"
+            } else {
+                "off"
+            };
+            let _ = output
+                .say(
+                    ctx,
+                    format!("Batching in this channel is {state}.\nUsage: `<batching> on|off`"),
+                )
+                .await;
+            return;
+        }
+        _ => {
+            let _ = output.say(ctx, "Usage: `<batching> on|off`").await;
+            return;
+        }
+    };
+
+    // Change the setting for this channel
+    set_batching_enabled(channel_id, enabled).await;
+
+    // Send confirmation
+    let message = if enabled {
+        "Batching enabled. Mentions arriving during a request will be answered together."
+    } else {
+        "Batching disabled. Every mention will be answered on its own."
+    };
+    let _ = output.say(ctx, message).await;
+}
+
+/// Handles the model routing command
+async fn handle_routing_command(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    output: &CommandOutput<'_>,
+    args: &str,
+) {
+    let channel_id = msg_ctx.channel_id;
+    let usage = "Usage: `<routing> on [light model]|off`";
+
+    let mut parts = args.split_whitespace();
+    let routing = match (parts.next().map(str::to_lowercase).as_deref(), parts.next()) {
+        (None, _) => {
+            let message = match get_channel_routing(channel_id).await {
+                Some(routing) => format!(
+                    "Routing in this channel is on: simple messages go to `{}`.\n{usage}",
+                    routing.light_model
+                ),
+                None => format!("Routing in this channel is off.\n{usage}"),
+            };
+            let _ = output.say(ctx, &message).await;
+            return;
+        }
+        (Some("on"), light_model) => Some(ChannelRouting {
+            light_model: light_model.unwrap_or(DEFAULT_LIGHT_MODEL).to_string(),
+        }),
+        (Some("off"), None) => None,
+        _ => {
+            let _ = output.say(ctx, usage).await;
+            return;
+        }
+    };
+
+    let message = match &routing {
+        Some(routing) => format!(
+            "Routing enabled. Simple messages in this channel will be answered by `{}`, the rest by the current model.",
+            routing.light_model
+        ),
+        None => {
+            "Routing disabled. Every message will be answered by the current model.".to_string()
+        }
+    };
+    set_channel_routing(channel_id, routing).await;
+
+    let _ = output.say(ctx, &message).await;
+}
+
+/// Handles the command setting the language of the bot's own messages in a guild
+async fn handle_language_command(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    output: &CommandOutput<'_>,
+    args: &str,
+) {
+    let Some(guild_id) = msg_ctx.guild_id else {
+        let _ = output
+            .say(ctx, "The language is set per guild, use this in a guild.")
+            .await;
+        return;
+    };
+
+    if args.is_empty() {
+        let language = get_guild_language(Some(guild_id)).await;
+        let message = format!("Language in this guild: `{language}`\nUsage: `<language> ko|en`");
+        let _ = output.say(ctx, &message).await;
+        return;
+    }
+
+    let Ok(language) = Language::from_str(args) else {
+        let _ = output.say(ctx, "Usage: `<language> ko|en`").await;
+        return;
+    };
+
+    set_guild_language(guild_id, language).await;
+
+    let message = format!("Language in this guild set to `{language}`.");
+    let _ = output.say(ctx, &message).await;
+}
+
+/// Handles the command setting who may use message directives
+async fn handle_directives_command(ctx: &Context, output: &CommandOutput<'_>, args: &str) {
+    let usage = "Usage: `<directives> admin|everyone <model|ephemeral|nomemory>`";
+
+    let parts: Vec<&str> = args.split_whitespace().collect();
+    let (admin_only, directive) = match parts.as_slice() {
+        [] => {
+            let admin_only = get_admin_only_directives().await;
+            let lines: Vec<String> = Directive::iter()
+                .map(|directive| {
+                    let access = if admin_only.contains(&directive) {
+                        "admins only"
+                    } else {
+                        "everyone"
+                    };
+                    format!("- `!{directive}`: {access}")
+                })
+                .collect();
+            let message = format!("**Message directives**\n{}\n{usage}", lines.join("\n"));
+            let _ = output.say(ctx, &message).await;
+            return;
+        }
+        [access, name] => {
+            let admin_only = match access.to_lowercase().as_str() {
+                "admin" => true,
+                "everyone" => false,
+                _ => {
+                    let _ = output.say(ctx, usage).await;
+                    return;
+                }
+            };
+            let Ok(directive) = Directive::from_str(name.trim_start_matches('!')) else {
+                let message = format!("Unknown directive: {name}\n{usage}");
+                let _ = output.say(ctx, &message).await;
+                return;
+            };
+            (admin_only, directive)
+        }
+        _ => {
+            let _ = output.say(ctx, usage).await;
+            return;
+        }
+    };
+
+    set_directive_admin_only(directive, admin_only).await;
+
+    let access = if admin_only {
+        "only admins"
+    } else {
+        "everyone"
+    };
+    let message = format!("`!{directive}` can now be used by {access}.");
+    let _ = output.say(ctx, &message).await;
+}
+
+/// Handles the fallback models command
+async fn handle_fallback_command(ctx: &Context, output: &CommandOutput<'_>, args: &str) {
+    // Show the current fallback models
+    if args.is_empty() {
+        let fallback_models = get_fallback_models().await;
+        let models = if fallback_models.is_empty() {
+            "none".to_string()
+        } else {
+            fallback_models.join(" → ")
+        };
+        let _ = output.say(ctx, format!(
+                "Fallback models: {models}\nUsage: `<fallback> model1, model2, ...` or `<fallback> none`"
+            ),
+        )
+        .await;
+        return;
+    }
+
+    let models: Vec<String> = if args.eq_ignore_ascii_case("none") {
+        Vec::new()
+    } else {
+        args.split([',', ' '])
+            .map(str::trim)
+            .filter(|m| !m.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+
+    // Set the fallback models
+    set_fallback_models(models.clone()).await;
+
+    // Send confirmation
+    let message = if models.is_empty() {
+        "Fallback models cleared.".to_string()
+    } else {
+        format!("Fallback models set to {}", models.join(" → "))
+    };
+    let _ = output.say(ctx, message).await;
+}
+
+/// Format usage totals as a single line
+fn format_usage_totals(totals: &UsageTotals) -> String {
+    format!(
+        "${:.4} · {} requests · {} tokens (in {} / cached {} / out {} / reasoning {})",
+        totals.cost_usd,
+        totals.requests,
+        totals.total_tokens(),
+        totals.input_tokens,
+        totals.cached_input_tokens,
+        totals.output_tokens,
+        totals.reasoning_tokens
     )
-    .await;
+}
+
+/// Format the most expensive entries of a usage breakdown
+fn format_top_usage<K: Copy>(
+    title: &str,
+    entries: impl IntoIterator<Item = (K, UsageTotals)>,
+    label: impl Fn(K) -> String,
+) -> String {
+    const TOP_COUNT: usize = 10;
+
+    let mut entries: Vec<(K, UsageTotals)> = entries.into_iter().collect();
+    entries.sort_by(|a, b| b.1.cost_usd.total_cmp(&a.1.cost_usd));
+
+    let lines: Vec<String> = entries
+        .iter()
+        .take(TOP_COUNT)
+        .map(|(key, totals)| {
+            format!(
+                "- {}: ${:.4} · {} requests · {} tokens",
+                label(*key),
+                totals.cost_usd,
+                totals.requests,
+                totals.total_tokens()
+            )
+        })
+        .collect();
+
+    if lines.is_empty() {
+        format!("**{title}**\n- none")
+    } else {
+        format!("**{title}**\n{}", lines.join("\n"))
+    }
+}
+
+/// Build a detailed usage report for a period
+fn format_usage_breakdown(ledger: &UsageLedger, period: UsagePeriod) -> String {
+    let today = today_kst();
+    let from = period.start_date(today);
+    let summary = ledger.summarize(from, today);
+
+    [
+        format!(
+            "**Usage: {period}** ({from} ~ {today}, KST)\n{}",
+            format_usage_totals(&summary.total)
+        ),
+        format_top_usage("By user", summary.by_user, |id| ledger.user_label(id)),
+        format_top_usage("By channel", summary.by_channel, |id| {
+            ledger.channel_label(id)
+        }),
+        format_top_usage("By guild", summary.by_guild, |id| ledger.guild_label(id)),
+    ]
+    .join("\n\n")
+}
+
+/// Handles the usage command
+async fn handle_usage_command(ctx: &Context, output: &CommandOutput<'_>, args: &str) {
+    let ledger = get_usage_ledger().await;
+
+    let message = match args.to_lowercase().as_str() {
+        "" => {
+            let today = today_kst();
+            let lines: Vec<String> = [UsagePeriod::Today, UsagePeriod::Week, UsagePeriod::Month]
+                .into_iter()
+                .map(|period| {
+                    let summary = ledger.summarize(period.start_date(today), today);
+                    format!("- {period}: {}", format_usage_totals(&summary.total))
+                })
+                .collect();
+            format!(
+                "**Usage**\n{}\n\nDetails: `<usage> today|week|month`",
+                lines.join("\n")
+            )
+        }
+        "today" => format_usage_breakdown(&ledger, UsagePeriod::Today),
+        "week" => format_usage_breakdown(&ledger, UsagePeriod::Week),
+        "month" => format_usage_breakdown(&ledger, UsagePeriod::Month),
+        _ => "Usage: `<usage> [today|week|month]`".to_string(),
+    };
+
+    let _ = output.say(ctx, &message).await;
+}
+
+const BUDGET_COMMAND_USAGE: &str = "Usage: `<budget> set <scope> daily|monthly <limit>`, `<budget> reset <scope> [daily|monthly]` (spend of this period) or `<budget> remove <scope> [daily|monthly]`\n\
+    Scope: `global`, `guild [id]` or `user <id|mention>`. Limit: tokens (`50000`, `50k`) or USD (`$1.5`)";
+
+/// Readable name of a budget scope
+fn budget_scope_label(ledger: &UsageLedger, scope: BudgetScope) -> String {
+    match scope {
+        BudgetScope::Global => "Global".to_string(),
+        BudgetScope::Guild(guild_id) => format!("Guild {}", ledger.guild_label(guild_id)),
+        BudgetScope::User(user_id) => format!("User {}", ledger.user_label(user_id)),
+    }
+}
+
+/// Handles the budget command
+async fn handle_budget_command(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    output: &CommandOutput<'_>,
+    args: &str,
+) {
+    let args: Vec<&str> = args.split_whitespace().collect();
+
+    let message = match args.split_first() {
+        None => {
+            let ledger = get_usage_ledger().await;
+            let usages = get_budget_usages().await;
+            if usages.is_empty() {
+                format!("No budgets configured.\n{BUDGET_COMMAND_USAGE}")
+            } else {
+                let lines: Vec<String> = usages
+                    .iter()
+                    .map(|usage| {
+                        format!(
+                            "- {} ({}): {} ({:.0}%)",
+                            budget_scope_label(&ledger, usage.budget.scope),
+                            usage.budget.period,
+                            usage.budget.limit.format_used(&usage.used),
+                            usage.ratio * 100.0
+                        )
+                    })
+                    .collect();
+                format!("**Budgets**\n{}", lines.join("\n"))
+            }
+        }
+        Some((&"set", rest)) => match parse_budget_set_args(rest, msg_ctx) {
+            Ok(budget) => {
+                set_budget(budget).await;
+                format!(
+                    "Budget set: {} {} limit of {}",
+                    budget.scope, budget.period, budget.limit
+                )
+            }
+            Err(err) => format!("{err}\n{BUDGET_COMMAND_USAGE}"),
+        },
+        Some((&"reset", rest)) => match parse_budget_period_args(rest, msg_ctx) {
+            Ok((scope, period)) => {
+                let reset = reset_budget_spend(scope, period).await;
+                format!("Reset the spend of this period for {reset} budget(s) of {scope}.")
+            }
+            Err(err) => format!("{err}\n{BUDGET_COMMAND_USAGE}"),
+        },
+        Some((&"remove", rest)) => match parse_budget_period_args(rest, msg_ctx) {
+            Ok((scope, period)) => {
+                let removed = remove_budgets(scope, period).await;
+                format!("Removed {removed} budget(s) of {scope}.")
+            }
+            Err(err) => format!("{err}\n{BUDGET_COMMAND_USAGE}"),
+        },
+        Some(_) => BUDGET_COMMAND_USAGE.to_string(),
+    };
+
+    let _ = output.say(ctx, &message).await;
+}
+
+/// Parse `<scope> daily|monthly <limit>`
+fn parse_budget_set_args(args: &[&str], msg_ctx: &MsgContextInfo) -> eyre::Result<Budget> {
+    let (scope, rest) = parse_budget_scope(args, msg_ctx)?;
+    let [period, limit] = rest else {
+        return Err(eyre::eyre!("Expected a period and a limit"));
+    };
+
+    Ok(Budget {
+        scope,
+        period: BudgetPeriod::from_str(period)
+            .map_err(|_| eyre::eyre!("Unknown period: {period}"))?,
+        limit: BudgetLimit::from_str(limit)?,
+    })
+}
+
+/// Parse `<scope> [daily|monthly]`
+fn parse_budget_period_args(
+    args: &[&str],
+    msg_ctx: &MsgContextInfo,
+) -> eyre::Result<(BudgetScope, Option<BudgetPeriod>)> {
+    let (scope, rest) = parse_budget_scope(args, msg_ctx)?;
+    let period = match rest {
+        [] => None,
+        [period] => Some(
+            BudgetPeriod::from_str(period).map_err(|_| eyre::eyre!("Unknown period: {period}"))?,
+        ),
+        _ => return Err(eyre::eyre!("Too many arguments")),
+    };
+
+    Ok((scope, period))
+}
+
+const RATE_LIMIT_COMMAND_USAGE: &str =
+    "Usage: `<ratelimit> user|channel <count>/<seconds>` or `<ratelimit> user|channel off`";
+
+/// Handles the rate limit command
+async fn handle_rate_limit_command(ctx: &Context, output: &CommandOutput<'_>, args: &str) {
+    let mut config = get_rate_limit_config().await;
+    let format_limit = |limit: Option<RateLimit>| {
+        limit
+            .map(|limit| limit.to_string())
+            .unwrap_or_else(|| "off".to_string())
+    };
+
+    let args: Vec<&str> = args.split_whitespace().collect();
+    let (target, value) = match args.as_slice() {
+        [] => {
+            let message = format!(
+                "**Rate limits**\n- Per user: {}\n- Per channel: {}\n\n{RATE_LIMIT_COMMAND_USAGE}",
+                format_limit(config.per_user),
+                format_limit(config.per_channel)
+            );
+            let _ = output.say(ctx, &message).await;
+            return;
+        }
+        [target, value] => (target.to_lowercase(), *value),
+        _ => {
+            let _ = output.say(ctx, RATE_LIMIT_COMMAND_USAGE).await;
+            return;
+        }
+    };
+
+    let limit = if value.eq_ignore_ascii_case("off") {
+        None
+    } else {
+        match RateLimit::from_str(value) {
+            Ok(limit) => Some(limit),
+            Err(err) => {
+                let message = format!("{err}\n{RATE_LIMIT_COMMAND_USAGE}");
+                let _ = output.say(ctx, &message).await;
+                return;
+            }
+        }
+    };
+
+    match target.as_str() {
+        "user" => config.per_user = limit,
+        "channel" => config.per_channel = limit,
+        _ => {
+            let _ = output.say(ctx, RATE_LIMIT_COMMAND_USAGE).await;
+            return;
+        }
+    }
+
+    set_rate_limit_config(config).await;
+    reset_rate_limiter().await;
+
+    let message = format!("Rate limit per {target} set to {}.", format_limit(limit));
+    let _ = output.say(ctx, &message).await;
 }
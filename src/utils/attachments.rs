@@ -0,0 +1,260 @@
+use serenity::all::{Attachment, Message};
+
+// Extensions of files that are inlined as text
+const TEXT_EXTENSIONS: &[&str] = &["txt", "md", "rs", "py", "json", "log", "csv"];
+
+// Limits to keep the context small
+const MAX_TEXT_FILE_SIZE: u32 = 1024 * 1024;
+const MAX_TEXT_CHARS_PER_FILE: usize = 20_000;
+const MAX_PDF_SIZE: u32 = 32 * 1024 * 1024;
+const MAX_FILES_PER_MESSAGE: usize = 5;
+
+/// How an attachment is passed to the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttachmentKind {
+    /// Inlined into the message as text
+    Text,
+    /// Sent as an `input_file` for the model to read
+    Pdf,
+}
+
+/// Content of the file attachments of a message
+#[derive(Debug, Default)]
+pub struct FileAttachments {
+    /// Formatted text of each text file
+    pub texts: Vec<String>,
+    /// Filename and URL of each PDF
+    pub files: Vec<(String, String)>,
+}
+
+/// Find out how an attachment can be passed to the model, if at all
+pub fn classify_attachment(content_type: Option<&str>, filename: &str) -> Option<AttachmentKind> {
+    let extension = filename
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_lowercase())
+        .unwrap_or_default();
+    let content_type = content_type.unwrap_or_default();
+
+    if extension == "pdf" || content_type.starts_with("application/pdf") {
+        Some(AttachmentKind::Pdf)
+    } else if TEXT_EXTENSIONS.contains(&extension.as_str())
+        || content_type.starts_with("text/")
+        || content_type.starts_with("application/json")
+    {
+        Some(AttachmentKind::Text)
+    } else {
+        None
+    }
+}
+
+/// Format the content of a text file for the model, truncating it if it is too long
+pub fn format_text_attachment(filename: &str, bytes: &[u8], max_chars: usize) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let text = text.trim_end();
+    let total_chars = text.chars().count();
+
+    if total_chars <= max_chars {
+        return format!("[File: {filename}]\n```\n{text}\n```");
+    }
+
+    let end = text
+        .char_indices()
+        .nth(max_chars)
+        .map_or(text.len(), |(index, _)| index);
+    format!(
+        "[File: {filename}]\n```\n{}\n```\n[Truncated: showing the first {max_chars} of {total_chars} characters]",
+        &text[..end]
+    )
+}
+
+/// Download the text files and collect the PDFs attached to a message
+/// Files that can't be read are noted in the text so the model knows about them
+pub async fn load_file_attachments(attachments: &[Attachment]) -> FileAttachments {
+    let mut loaded = FileAttachments::default();
+
+    let files = attachments.iter().filter_map(|attachment| {
+        classify_attachment(attachment.content_type.as_deref(), &attachment.filename)
+            .map(|kind| (attachment, kind))
+    });
+    for (index, (attachment, kind)) in files.enumerate() {
+        let filename = &attachment.filename;
+        if index >= MAX_FILES_PER_MESSAGE {
+            loaded.texts.push(format!(
+                "[File: {filename} (skipped, at most {MAX_FILES_PER_MESSAGE} files are read per message)]"
+            ));
+            continue;
+        }
+
+        match kind {
+            AttachmentKind::Text if attachment.size > MAX_TEXT_FILE_SIZE => {
+                loaded.texts.push(format!(
+                    "[File: {filename} (skipped, larger than {} KB)]",
+                    MAX_TEXT_FILE_SIZE / 1024
+                ));
+            }
+            AttachmentKind::Text => match attachment.download().await {
+                Ok(bytes) => loaded.texts.push(format_text_attachment(
+                    filename,
+                    &bytes,
+                    MAX_TEXT_CHARS_PER_FILE,
+                )),
+                Err(e) => {
+                    tracing::error!("Failed to download attachment {filename}: {e}");
+                    loaded
+                        .texts
+                        .push(format!("[File: {filename} (could not be downloaded)]"));
+                }
+            },
+            AttachmentKind::Pdf if attachment.size > MAX_PDF_SIZE => {
+                loaded.texts.push(format!(
+                    "[File: {filename} (skipped, larger than {} MB)]",
+                    MAX_PDF_SIZE / 1024 / 1024
+                ));
+            }
+            AttachmentKind::Pdf => {
+                loaded
+                    .files
+                    .push((filename.clone(), attachment.url.clone()));
+            }
+        }
+    }
+
+    loaded
+}
+
+/// Image URLs of a single message, attachments first and then embeds
+fn message_image_urls(msg: &Message) -> impl Iterator<Item = String> + '_ {
+    let attachments = msg
+        .attachments
+        .iter()
+        .filter(|attachment| {
+            attachment
+                .content_type
+                .as_deref()
+                .is_some_and(|ct| ct.starts_with("image/"))
+        })
+        .map(|attachment| attachment.url.clone());
+
+    // Prefer the Discord proxy, as the source of a link preview may not be reachable
+    let embeds = msg.embeds.iter().flat_map(|embed| {
+        let image = embed
+            .image
+            .as_ref()
+            .map(|image| image.proxy_url.clone().unwrap_or_else(|| image.url.clone()));
+        let thumbnail = embed.thumbnail.as_ref().map(|thumbnail| {
+            thumbnail
+                .proxy_url
+                .clone()
+                .unwrap_or_else(|| thumbnail.url.clone())
+        });
+        image.into_iter().chain(thumbnail)
+    });
+
+    attachments.chain(embeds)
+}
+
+/// Collect the images of a message and of the message it replies to, without duplicates
+pub fn collect_image_urls(msg: &Message, max_images: usize) -> Vec<String> {
+    let referenced = msg
+        .referenced_message
+        .iter()
+        .flat_map(|referenced| message_image_urls(referenced));
+
+    let mut urls: Vec<String> = Vec::new();
+    for url in message_image_urls(msg).chain(referenced) {
+        if urls.len() >= max_images {
+            break;
+        }
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_attachment() {
+        assert_eq!(
+            classify_attachment(Some("text/plain; charset=utf-8"), "error.log"),
+            Some(AttachmentKind::Text)
+        );
+        assert_eq!(
+            classify_attachment(None, "main.RS"),
+            Some(AttachmentKind::Text)
+        );
+        assert_eq!(
+            classify_attachment(Some("application/pdf"), "paper.pdf"),
+            Some(AttachmentKind::Pdf)
+        );
+        assert_eq!(classify_attachment(Some("image/png"), "cat.png"), None);
+        assert_eq!(classify_attachment(Some("audio/ogg"), "voice.ogg"), None);
+    }
+
+    #[test]
+    fn test_format_text_attachment() {
+        assert_eq!(
+            format_text_attachment("a.txt", b"hello\n", 100),
+            "[File: a.txt]\n```\nhello\n```"
+        );
+
+        let truncated = format_text_attachment("b.log", "가나다라마".as_bytes(), 3);
+        assert_eq!(
+            truncated,
+            "[File: b.log]\n```\n가나다\n```\n[Truncated: showing the first 3 of 5 characters]"
+        );
+    }
+
+    fn image_attachment(url: &str, content_type: &str) -> Attachment {
+        serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "filename": "image",
+            "size": 100,
+            "url": url,
+            "proxy_url": url,
+            "content_type": content_type,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_collect_image_urls() {
+        let mut referenced = Message::default();
+        referenced.attachments = vec![
+            image_attachment("https://cdn/old.png", "image/png"),
+            image_attachment("https://cdn/a.png", "image/png"),
+        ];
+
+        let mut msg = Message::default();
+        msg.attachments = vec![
+            image_attachment("https://cdn/a.png", "image/png"),
+            image_attachment("https://cdn/notes.txt", "text/plain"),
+        ];
+        msg.embeds = vec![
+            serde_json::from_value(serde_json::json!({
+                "image": { "url": "https://example.com/b.png", "proxy_url": "https://proxy/b.png" },
+                "thumbnail": { "url": "https://example.com/c.png" },
+            }))
+            .unwrap(),
+        ];
+        msg.referenced_message = Some(Box::new(referenced));
+
+        assert_eq!(
+            collect_image_urls(&msg, 10),
+            vec![
+                "https://cdn/a.png",
+                "https://proxy/b.png",
+                "https://example.com/c.png",
+                "https://cdn/old.png",
+            ]
+        );
+        assert_eq!(
+            collect_image_urls(&msg, 2),
+            vec!["https://cdn/a.png", "https://proxy/b.png"]
+        );
+        assert!(collect_image_urls(&msg, 0).is_empty());
+    }
+}
@@ -0,0 +1,241 @@
+use serde::{Deserialize, Serialize};
+use serenity::model::id::ChannelId;
+use serenity::prelude::Context;
+use strum_macros::EnumString;
+
+use crate::utils::circuit_breaker::{CircuitOpenError, OFFLINE_MESSAGE};
+use crate::utils::discord;
+use crate::utils::msg_context::MsgContextInfo;
+use crate::utils::openai_schema::OpenAiApiError;
+use crate::utils::persistence::get_guild_language;
+
+// Length of the error details sent to the developer, a DM holds at most 2000 characters
+const MAX_REPORTED_DETAILS_CHARS: usize = 1500;
+
+/// Language of the messages the bot writes itself in a guild
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    EnumString,
+    strum_macros::Display,
+)]
+#[strum(ascii_case_insensitive)]
+pub enum Language {
+    #[default]
+    #[strum(serialize = "ko")]
+    Korean,
+    #[strum(serialize = "en")]
+    English,
+}
+
+/// What the bot failed to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum FailedAction {
+    Reply,
+    Draw,
+    Transcribe,
+}
+
+/// Kind of a failed backend request, shown to users without the details
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::Display)]
+pub enum BackendError {
+    /// Requests are paused by the circuit breaker
+    Unavailable,
+    /// Too many requests or the quota is used up
+    RateLimited,
+    /// OpenAI couldn't be reached or didn't answer in time
+    Network,
+    /// OpenAI failed to handle the request
+    Server,
+    /// The request was refused, e.g. because it was too long
+    Rejected,
+    /// Anything else, e.g. an unreadable response
+    Other,
+}
+
+impl BackendError {
+    /// Find out what kind of failure an error is
+    pub fn classify(error: &eyre::Report) -> Self {
+        if error.downcast_ref::<CircuitOpenError>().is_some() {
+            return Self::Unavailable;
+        }
+        if let Some(api_error) = error.downcast_ref::<OpenAiApiError>() {
+            return match api_error.status {
+                429 => Self::RateLimited,
+                500.. => Self::Server,
+                400..=499 => Self::Rejected,
+                _ => Self::Other,
+            };
+        }
+        if error.downcast_ref::<reqwest::Error>().is_some() {
+            return Self::Network;
+        }
+        Self::Other
+    }
+
+    /// Short message for the channel, with the correlation id to find the details
+    pub fn user_message(&self, action: FailedAction, language: Language, id: &str) -> String {
+        match language {
+            Language::Korean => {
+                let reason = match self {
+                    Self::Unavailable => "지금 OpenAI가 응답하지 않아서",
+                    Self::RateLimited => "요청이 너무 많아서",
+                    Self::Network => "OpenAI랑 연결이 잘 안 돼서",
+                    Self::Server => "OpenAI 쪽에 문제가 생겨서",
+                    Self::Rejected => "요청이 거절돼서",
+                    Self::Other => "알 수 없는 문제가 생겨서",
+                };
+                let action = match action {
+                    FailedAction::Reply => "답장을 못 했어",
+                    FailedAction::Draw => "그림을 못 그렸어",
+                    FailedAction::Transcribe => "음성 메시지를 못 알아들었어",
+                };
+                format!("미안, {reason} {action}. 잠시 후에 다시 해줘! (오류 ID: `{id}`)")
+            }
+            Language::English => {
+                let reason = match self {
+                    Self::Unavailable => "OpenAI isn't responding right now",
+                    Self::RateLimited => "there are too many requests right now",
+                    Self::Network => "I couldn't reach OpenAI",
+                    Self::Server => "OpenAI ran into a problem",
+                    Self::Rejected => "the request was rejected",
+                    Self::Other => "something unexpected happened",
+                };
+                let action = match action {
+                    FailedAction::Reply => "reply",
+                    FailedAction::Draw => "draw that",
+                    FailedAction::Transcribe => "understand the voice message",
+                };
+                format!(
+                    "Sorry, I couldn't {action} because {reason}. Please try again later. (Error ID: `{id}`)"
+                )
+            }
+        }
+    }
+}
+
+/// Create a short id shown to users and written to the logs, to match them up
+pub fn new_correlation_id() -> String {
+    format!("{:08x}", rand::random::<u32>())
+}
+
+/// Report a failed backend request
+/// The channel gets a friendly message in the guild's language, while the details go to
+/// the log and to the developer, all tagged with the same correlation id
+pub async fn report_backend_error(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    channel_id: ChannelId,
+    action: FailedAction,
+    error: &eyre::Report,
+) {
+    // The backend is known to be down and the developer was told when the circuit opened,
+    // so answer in character without the details
+    if error.downcast_ref::<CircuitOpenError>().is_some() {
+        tracing::info!("Not trying to {action} while the backend is down: {error}");
+        if let Err(why) = discord::say_reply(
+            ctx,
+            channel_id,
+            msg_ctx.reply_to(channel_id),
+            OFFLINE_MESSAGE,
+        )
+        .await
+        {
+            tracing::error!("Error sending offline message: {:?}", why);
+        }
+        return;
+    }
+
+    let kind = BackendError::classify(error);
+    let id = new_correlation_id();
+    tracing::error!("[{id}] Failed to {action} ({kind}): {error:?}");
+
+    let language = get_guild_language(msg_ctx.guild_id).await;
+    let message = kind.user_message(action, language, &id);
+    if let Err(why) =
+        discord::say_reply(ctx, channel_id, msg_ctx.reply_to(channel_id), message).await
+    {
+        tracing::error!("[{id}] Error sending error message: {:?}", why);
+    }
+
+    let details: String = format!("{error:?}")
+        .chars()
+        .take(MAX_REPORTED_DETAILS_CHARS)
+        .collect();
+    let report = format!(
+        "Error `{id}`: failed to {action} ({kind})\nGuild: {}\nChannel: {}\nAuthor: {}\n```\n{details}\n```",
+        msg_ctx.guild_name.as_deref().unwrap_or("-"),
+        msg_ctx.channel_name.as_deref().unwrap_or("-"),
+        msg_ctx.author.name,
+    );
+    if let Err(e) = discord::send_dm_to_dev(ctx, &report).await {
+        tracing::error!("[{id}] Failed to send error report: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    fn api_error(status: u16) -> eyre::Report {
+        let body = r#"{"error": {"message": "req_123 org-secret failed", "type": "server_error"}}"#;
+        OpenAiApiError::from_response_body(status, body).into()
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(
+            BackendError::classify(&api_error(429)),
+            BackendError::RateLimited
+        );
+        assert_eq!(
+            BackendError::classify(&api_error(502)),
+            BackendError::Server
+        );
+        assert_eq!(
+            BackendError::classify(&api_error(400)),
+            BackendError::Rejected
+        );
+        let open = eyre::Report::new(CircuitOpenError {
+            retry_in: Duration::from_secs(10),
+        });
+        assert_eq!(BackendError::classify(&open), BackendError::Unavailable);
+        assert_eq!(
+            BackendError::classify(&eyre::eyre!("no text in response")),
+            BackendError::Other
+        );
+    }
+
+    #[test]
+    fn test_user_message_hides_details() {
+        let korean =
+            BackendError::Server.user_message(FailedAction::Reply, Language::Korean, "0badc0de");
+        assert_eq!(
+            korean,
+            "미안, OpenAI 쪽에 문제가 생겨서 답장을 못 했어. 잠시 후에 다시 해줘! (오류 ID: `0badc0de`)"
+        );
+
+        let english =
+            BackendError::RateLimited.user_message(FailedAction::Draw, Language::English, "42");
+        assert!(english.starts_with("Sorry, I couldn't draw that because there are too many"));
+        assert!(english.ends_with("(Error ID: `42`)"));
+        assert!(!english.contains("org-secret"));
+    }
+
+    #[test]
+    fn test_parse_language() {
+        assert_eq!(Language::from_str("EN").unwrap(), Language::English);
+        assert_eq!(Language::Korean.to_string(), "ko");
+        assert!(Language::from_str("jp").is_err());
+        assert_eq!(new_correlation_id().len(), 8);
+    }
+}
@@ -0,0 +1,580 @@
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{GuildId, UserId};
+use serenity::utils::parse_user_mention;
+use std::fmt::Display;
+use std::str::FromStr;
+use strum_macros::EnumString;
+
+use crate::utils::msg_context::MsgContextInfo;
+use crate::utils::usage::{UsageLedger, UsageTotals};
+
+// Share of a budget at which the developer gets warned
+const WARNING_RATIO: f64 = 0.8;
+
+/// Who a budget applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BudgetScope {
+    Global,
+    Guild(GuildId),
+    User(UserId),
+}
+
+impl Display for BudgetScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BudgetScope::Global => write!(f, "global"),
+            BudgetScope::Guild(guild_id) => write!(f, "guild {guild_id}"),
+            BudgetScope::User(user_id) => write!(f, "user {user_id}"),
+        }
+    }
+}
+
+/// Period after which a budget starts over (in KST)
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    EnumString,
+    strum_macros::Display,
+)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum BudgetPeriod {
+    Daily,
+    Monthly,
+}
+
+impl BudgetPeriod {
+    /// First day of the period that contains `today`
+    pub fn start_date(&self, today: NaiveDate) -> NaiveDate {
+        match self {
+            BudgetPeriod::Daily => today,
+            BudgetPeriod::Monthly => today.with_day(1).unwrap_or(today),
+        }
+    }
+}
+
+/// Maximum spend within a period
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BudgetLimit {
+    Tokens(u64),
+    Usd(f64),
+}
+
+impl BudgetLimit {
+    /// Share of the limit used by the given totals (1.0 means fully used)
+    pub fn usage_ratio(&self, used: &UsageTotals) -> f64 {
+        match self {
+            BudgetLimit::Tokens(limit) => used.total_tokens() as f64 / (*limit).max(1) as f64,
+            BudgetLimit::Usd(limit) => used.cost_usd / limit.max(f64::EPSILON),
+        }
+    }
+
+    /// Format how much of the limit is used
+    pub fn format_used(&self, used: &UsageTotals) -> String {
+        match self {
+            BudgetLimit::Tokens(limit) => format!("{} / {limit} tokens", used.total_tokens()),
+            BudgetLimit::Usd(limit) => format!("${:.4} / ${limit:.2}", used.cost_usd),
+        }
+    }
+}
+
+impl Display for BudgetLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BudgetLimit::Tokens(limit) => write!(f, "{limit} tokens"),
+            BudgetLimit::Usd(limit) => write!(f, "${limit:.2}"),
+        }
+    }
+}
+
+impl FromStr for BudgetLimit {
+    type Err = eyre::Report;
+
+    /// Parse "$1.5" or "1.5usd" as USD and "50000", "50k" or "2m" as tokens
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let invalid = || eyre::eyre!("Invalid budget limit: {s}");
+
+        if let Some(usd) = s.strip_prefix('$').or_else(|| s.strip_suffix("usd")) {
+            let usd: f64 = usd.trim().parse().map_err(|_| invalid())?;
+            if !usd.is_finite() || usd <= 0.0 {
+                return Err(invalid());
+            }
+            return Ok(BudgetLimit::Usd(usd));
+        }
+
+        let (number, multiplier) = if let Some(n) = s.strip_suffix('k') {
+            (n, 1_000)
+        } else if let Some(n) = s.strip_suffix('m') {
+            (n, 1_000_000)
+        } else {
+            (s.as_str(), 1)
+        };
+        let tokens: u64 = number.trim().parse().map_err(|_| invalid())?;
+        if tokens == 0 {
+            return Err(invalid());
+        }
+
+        let tokens = tokens.checked_mul(multiplier).ok_or_else(invalid)?;
+        Ok(BudgetLimit::Tokens(tokens))
+    }
+}
+
+/// A configured budget
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Budget {
+    pub scope: BudgetScope,
+    pub period: BudgetPeriod,
+    pub limit: BudgetLimit,
+}
+
+/// Current spend against a budget
+#[derive(Debug, Clone, Copy)]
+pub struct BudgetUsage {
+    pub budget: Budget,
+    pub used: UsageTotals,
+    pub ratio: f64,
+}
+
+impl BudgetUsage {
+    pub fn is_exceeded(&self) -> bool {
+        self.ratio >= 1.0
+    }
+
+    /// In-character reply sent instead of calling the model
+    pub fn refusal_message(&self) -> &'static str {
+        match (self.budget.scope, self.budget.period) {
+            (BudgetScope::User(_), BudgetPeriod::Daily) => {
+                "오늘 나랑 너무 많이 떠들었어 ㅋㅋ 나도 좀 쉬어야지. 내일 다시 불러줘!"
+            }
+            (BudgetScope::User(_), BudgetPeriod::Monthly) => {
+                "이번 달은 나랑 충분히 얘기한 것 같아 ㅎㅎ 다음 달에 다시 놀자!"
+            }
+            (_, BudgetPeriod::Daily) => {
+                "오늘은 말을 너무 많이 해서 목이 다 쉬었어 ㅠㅠ 내일 다시 불러줘!"
+            }
+            (_, BudgetPeriod::Monthly) => {
+                "이번 달은 너무 많이 떠들어서 더 이상 말할 힘이 없어 ㅠㅠ 다음 달에 보자!"
+            }
+        }
+    }
+}
+
+/// A warning already sent for a budget in a period
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct SentWarning {
+    scope: BudgetScope,
+    period: BudgetPeriod,
+    period_start: NaiveDate,
+}
+
+/// Spend of a budget that no longer counts, as it was reset in the period
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct SpendReset {
+    scope: BudgetScope,
+    period: BudgetPeriod,
+    period_start: NaiveDate,
+    spent: UsageTotals,
+}
+
+/// Persistent budget configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BudgetConfig {
+    pub budgets: Vec<Budget>,
+    #[serde(default)]
+    warnings_sent: Vec<SentWarning>,
+    #[serde(default)]
+    resets: Vec<SpendReset>,
+}
+
+impl BudgetConfig {
+    /// Add a budget, replacing any budget with the same scope and period
+    pub fn set_budget(&mut self, budget: Budget) {
+        self.budgets
+            .retain(|b| !(b.scope == budget.scope && b.period == budget.period));
+        self.budgets.push(budget);
+    }
+
+    /// Remove the budgets of a scope (optionally only for one period)
+    /// Returns the number of removed budgets
+    pub fn remove_budgets(&mut self, scope: BudgetScope, period: Option<BudgetPeriod>) -> usize {
+        let before = self.budgets.len();
+        self.budgets
+            .retain(|b| !(b.scope == scope && period.is_none_or(|p| b.period == p)));
+        self.warnings_sent
+            .retain(|w| !(w.scope == scope && period.is_none_or(|p| w.period == p)));
+        self.resets
+            .retain(|r| !(r.scope == scope && period.is_none_or(|p| r.period == p)));
+        before - self.budgets.len()
+    }
+
+    /// Start the spend of the budgets of a scope (optionally only for one period) over,
+    /// keeping their limits and the usage statistics
+    /// Returns the number of reset budgets
+    pub fn reset_spend(
+        &mut self,
+        ledger: &UsageLedger,
+        scope: BudgetScope,
+        period: Option<BudgetPeriod>,
+        today: NaiveDate,
+    ) -> usize {
+        // Forget resets of past periods
+        self.resets
+            .retain(|r| r.period_start == r.period.start_date(today));
+
+        let budgets: Vec<Budget> = self
+            .budgets
+            .iter()
+            .filter(|b| b.scope == scope && period.is_none_or(|p| b.period == p))
+            .copied()
+            .collect();
+        for budget in &budgets {
+            let reset = SpendReset {
+                scope: budget.scope,
+                period: budget.period,
+                period_start: budget.period.start_date(today),
+                spent: self.spent(budget, ledger, today),
+            };
+            self.resets
+                .retain(|r| !(r.scope == reset.scope && r.period == reset.period));
+            self.resets.push(reset);
+            self.warnings_sent
+                .retain(|w| !(w.scope == budget.scope && w.period == budget.period));
+        }
+
+        budgets.len()
+    }
+
+    /// Current spend of every budget that applies to the given message
+    pub fn evaluate(
+        &self,
+        ledger: &UsageLedger,
+        msg_ctx: &MsgContextInfo,
+        today: NaiveDate,
+    ) -> Vec<BudgetUsage> {
+        self.budgets
+            .iter()
+            .filter(|budget| match budget.scope {
+                BudgetScope::Global => true,
+                BudgetScope::Guild(guild_id) => msg_ctx.guild_id == Some(guild_id),
+                BudgetScope::User(user_id) => msg_ctx.author_id == user_id,
+            })
+            .map(|budget| self.usage_of(budget, ledger, today))
+            .collect()
+    }
+
+    /// Current spend of every configured budget
+    pub fn evaluate_all(&self, ledger: &UsageLedger, today: NaiveDate) -> Vec<BudgetUsage> {
+        self.budgets
+            .iter()
+            .map(|budget| self.usage_of(budget, ledger, today))
+            .collect()
+    }
+
+    fn usage_of(&self, budget: &Budget, ledger: &UsageLedger, today: NaiveDate) -> BudgetUsage {
+        let period_start = budget.period.start_date(today);
+        let reset = self.resets.iter().find(|r| {
+            r.scope == budget.scope && r.period == budget.period && r.period_start == period_start
+        });
+        let spent = self.spent(budget, ledger, today);
+        let used = match reset {
+            Some(reset) => spent.saturating_sub(&reset.spent),
+            None => spent,
+        };
+
+        BudgetUsage {
+            budget: *budget,
+            used,
+            ratio: budget.limit.usage_ratio(&used),
+        }
+    }
+
+    /// Everything spent against a budget in its current period, including before a reset
+    fn spent(&self, budget: &Budget, ledger: &UsageLedger, today: NaiveDate) -> UsageTotals {
+        let summary = ledger.summarize(budget.period.start_date(today), today);
+        match budget.scope {
+            BudgetScope::Global => summary.total,
+            BudgetScope::Guild(guild_id) => {
+                summary.by_guild.get(&guild_id).copied().unwrap_or_default()
+            }
+            BudgetScope::User(user_id) => {
+                summary.by_user.get(&user_id).copied().unwrap_or_default()
+            }
+        }
+    }
+
+    /// Pick the budgets that crossed the warning threshold and haven't been warned about
+    /// in their current period yet, and remember them as warned
+    pub fn take_new_warnings(
+        &mut self,
+        usages: &[BudgetUsage],
+        today: NaiveDate,
+    ) -> Vec<BudgetUsage> {
+        // Forget warnings of past periods
+        self.warnings_sent
+            .retain(|w| w.period_start == w.period.start_date(today));
+
+        let mut new_warnings = Vec::new();
+        for usage in usages.iter().filter(|u| u.ratio >= WARNING_RATIO) {
+            let warning = SentWarning {
+                scope: usage.budget.scope,
+                period: usage.budget.period,
+                period_start: usage.budget.period.start_date(today),
+            };
+            if !self.warnings_sent.contains(&warning) {
+                self.warnings_sent.push(warning);
+                new_warnings.push(*usage);
+            }
+        }
+
+        new_warnings
+    }
+}
+
+/// Parse a budget scope from command arguments: "global", "guild [id]" or "user <id|mention>"
+/// A guild scope without id refers to the guild of the current message
+/// Returns the scope and the remaining arguments
+pub fn parse_budget_scope<'a>(
+    args: &'a [&'a str],
+    msg_ctx: &MsgContextInfo,
+) -> eyre::Result<(BudgetScope, &'a [&'a str])> {
+    let parse_id = |s: &str| s.parse::<u64>().ok().filter(|id| *id != 0);
+
+    match args.first().map(|s| s.to_lowercase()).as_deref() {
+        Some("global") => Ok((BudgetScope::Global, &args[1..])),
+        Some("guild") => match args.get(1).and_then(|s| parse_id(s)) {
+            Some(id) => Ok((BudgetScope::Guild(GuildId::new(id)), &args[2..])),
+            None => msg_ctx
+                .guild_id
+                .map(|guild_id| (BudgetScope::Guild(guild_id), &args[1..]))
+                .ok_or_else(|| eyre::eyre!("Specify a guild id when not in a guild")),
+        },
+        Some("user") => {
+            let user_id = args
+                .get(1)
+                .and_then(|s| parse_user_mention(s).or_else(|| parse_id(s).map(UserId::new)))
+                .ok_or_else(|| eyre::eyre!("Specify a user id or mention"))?;
+            Ok((BudgetScope::User(user_id), &args[2..]))
+        }
+        _ => Err(eyre::eyre!(
+            "Scope must be one of: global, guild [id], user <id>"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spend(ledger: &mut UsageLedger, date: NaiveDate, ctx: &MsgContextInfo, usd: f64) {
+        let totals = UsageTotals {
+            requests: 1,
+            input_tokens: 1000,
+            output_tokens: 1000,
+            cost_usd: usd,
+            ..Default::default()
+        };
+        ledger.record(date, ctx, totals);
+    }
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_parse_budget_limit() {
+        assert_eq!(
+            "$1.5".parse::<BudgetLimit>().unwrap(),
+            BudgetLimit::Usd(1.5)
+        );
+        assert_eq!(
+            "2usd".parse::<BudgetLimit>().unwrap(),
+            BudgetLimit::Usd(2.0)
+        );
+        assert_eq!(
+            "50000".parse::<BudgetLimit>().unwrap(),
+            BudgetLimit::Tokens(50_000)
+        );
+        assert_eq!(
+            "50k".parse::<BudgetLimit>().unwrap(),
+            BudgetLimit::Tokens(50_000)
+        );
+        assert_eq!(
+            "2M".parse::<BudgetLimit>().unwrap(),
+            BudgetLimit::Tokens(2_000_000)
+        );
+        assert!("$0".parse::<BudgetLimit>().is_err());
+        assert!("0".parse::<BudgetLimit>().is_err());
+        assert!("lots".parse::<BudgetLimit>().is_err());
+        assert!("18446744073709551615k".parse::<BudgetLimit>().is_err());
+    }
+
+    #[test]
+    fn test_parse_budget_scope() {
+        let ctx = MsgContextInfo::for_test(1, 1, Some(100));
+
+        let args = ["global", "daily"];
+        let (scope, rest) = parse_budget_scope(&args, &ctx).unwrap();
+        assert_eq!(scope, BudgetScope::Global);
+        assert_eq!(rest, ["daily"]);
+
+        let args = ["guild", "daily"];
+        let (scope, _) = parse_budget_scope(&args, &ctx).unwrap();
+        assert_eq!(scope, BudgetScope::Guild(GuildId::new(100)));
+
+        let args = ["guild", "200", "daily"];
+        let (scope, rest) = parse_budget_scope(&args, &ctx).unwrap();
+        assert_eq!(scope, BudgetScope::Guild(GuildId::new(200)));
+        assert_eq!(rest, ["daily"]);
+
+        let args = ["user", "<@42>", "monthly"];
+        let (scope, rest) = parse_budget_scope(&args, &ctx).unwrap();
+        assert_eq!(scope, BudgetScope::User(UserId::new(42)));
+        assert_eq!(rest, ["monthly"]);
+
+        assert!(parse_budget_scope(&["user"], &ctx).is_err());
+        assert!(parse_budget_scope(&["guild"], &MsgContextInfo::for_test(1, 1, None)).is_err());
+        assert!(parse_budget_scope(&["channel"], &ctx).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_applicable_budgets() {
+        let alice = MsgContextInfo::for_test(1, 1, Some(100));
+        let bob = MsgContextInfo::for_test(2, 1, Some(100));
+        let mut ledger = UsageLedger::default();
+        spend(&mut ledger, date(3, 10), &alice, 0.5);
+        spend(&mut ledger, date(3, 10), &bob, 0.25);
+        spend(&mut ledger, date(3, 1), &alice, 1.0);
+
+        let mut config = BudgetConfig::default();
+        config.set_budget(Budget {
+            scope: BudgetScope::User(UserId::new(1)),
+            period: BudgetPeriod::Daily,
+            limit: BudgetLimit::Usd(0.5),
+        });
+        config.set_budget(Budget {
+            scope: BudgetScope::Guild(GuildId::new(100)),
+            period: BudgetPeriod::Monthly,
+            limit: BudgetLimit::Usd(10.0),
+        });
+        config.set_budget(Budget {
+            scope: BudgetScope::Global,
+            period: BudgetPeriod::Daily,
+            limit: BudgetLimit::Tokens(10_000),
+        });
+
+        // Alice has used up her daily budget
+        let usages = config.evaluate(&ledger, &alice, date(3, 10));
+        assert_eq!(usages.len(), 3);
+        assert!(usages.iter().any(|u| u.is_exceeded()));
+        let guild = usages
+            .iter()
+            .find(|u| matches!(u.budget.scope, BudgetScope::Guild(_)))
+            .unwrap();
+        assert!((guild.used.cost_usd - 1.75).abs() < 1e-9);
+        let global = usages
+            .iter()
+            .find(|u| u.budget.scope == BudgetScope::Global)
+            .unwrap();
+        assert_eq!(global.used.total_tokens(), 4000);
+
+        // Bob's user budget doesn't exist, so only guild and global apply
+        let usages = config.evaluate(&ledger, &bob, date(3, 10));
+        assert_eq!(usages.len(), 2);
+        assert!(!usages.iter().any(|u| u.is_exceeded()));
+
+        // A new day resets the daily budget
+        let usages = config.evaluate(&ledger, &alice, date(3, 11));
+        assert!(!usages.iter().any(|u| u.is_exceeded()));
+    }
+
+    #[test]
+    fn test_set_and_remove_budgets() {
+        let mut config = BudgetConfig::default();
+        let budget = Budget {
+            scope: BudgetScope::Global,
+            period: BudgetPeriod::Daily,
+            limit: BudgetLimit::Usd(1.0),
+        };
+        config.set_budget(budget);
+        config.set_budget(Budget {
+            limit: BudgetLimit::Usd(2.0),
+            ..budget
+        });
+        config.set_budget(Budget {
+            period: BudgetPeriod::Monthly,
+            ..budget
+        });
+        assert_eq!(config.budgets.len(), 2);
+        assert_eq!(config.budgets[0].limit, BudgetLimit::Usd(2.0));
+
+        assert_eq!(
+            config.remove_budgets(BudgetScope::Global, Some(BudgetPeriod::Daily)),
+            1
+        );
+        assert_eq!(config.remove_budgets(BudgetScope::Global, None), 1);
+        assert!(config.budgets.is_empty());
+    }
+
+    #[test]
+    fn test_reset_spend_keeps_the_limit() {
+        let alice = MsgContextInfo::for_test(1, 1, None);
+        let mut ledger = UsageLedger::default();
+        spend(&mut ledger, date(3, 10), &alice, 1.0);
+
+        let mut config = BudgetConfig::default();
+        config.set_budget(Budget {
+            scope: BudgetScope::Global,
+            period: BudgetPeriod::Monthly,
+            limit: BudgetLimit::Usd(1.0),
+        });
+        assert!(config.evaluate(&ledger, &alice, date(3, 10))[0].is_exceeded());
+
+        assert_eq!(
+            config.reset_spend(&ledger, BudgetScope::Global, None, date(3, 10)),
+            1
+        );
+        assert_eq!(config.budgets.len(), 1);
+        let usage = config.evaluate(&ledger, &alice, date(3, 10))[0];
+        assert_eq!(usage.used.cost_usd, 0.0);
+
+        // Spending after the reset counts again
+        spend(&mut ledger, date(3, 12), &alice, 0.5);
+        let usage = config.evaluate(&ledger, &alice, date(3, 12))[0];
+        assert!((usage.used.cost_usd - 0.5).abs() < 1e-9);
+
+        // The next period starts from zero without the reset
+        spend(&mut ledger, date(4, 1), &alice, 0.25);
+        let usage = config.evaluate(&ledger, &alice, date(4, 1))[0];
+        assert!((usage.used.cost_usd - 0.25).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_warnings_are_sent_once_per_period() {
+        let alice = MsgContextInfo::for_test(1, 1, None);
+        let mut ledger = UsageLedger::default();
+        spend(&mut ledger, date(3, 10), &alice, 0.85);
+
+        let mut config = BudgetConfig::default();
+        config.set_budget(Budget {
+            scope: BudgetScope::Global,
+            period: BudgetPeriod::Daily,
+            limit: BudgetLimit::Usd(1.0),
+        });
+
+        let usages = config.evaluate(&ledger, &alice, date(3, 10));
+        assert_eq!(config.take_new_warnings(&usages, date(3, 10)).len(), 1);
+        assert!(config.take_new_warnings(&usages, date(3, 10)).is_empty());
+
+        // The next day starts a new period
+        spend(&mut ledger, date(3, 11), &alice, 0.9);
+        let usages = config.evaluate(&ledger, &alice, date(3, 11));
+        assert_eq!(config.take_new_warnings(&usages, date(3, 11)).len(), 1);
+    }
+}
@@ -0,0 +1,304 @@
+use lazy_static::lazy_static;
+use serenity::prelude::Context;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::utils::discord;
+use crate::utils::openai_schema::OpenAiApiError;
+use crate::utils::statics::{BREAKER_COOLDOWN_SECS, BREAKER_FAILURE_THRESHOLD};
+
+lazy_static! {
+    static ref BREAKER: Arc<Mutex<CircuitBreaker>> = Arc::new(Mutex::new(CircuitBreaker::new(
+        *BREAKER_FAILURE_THRESHOLD,
+        Duration::from_secs(*BREAKER_COOLDOWN_SECS),
+    )));
+}
+
+/// In-character reply while the backend is considered down
+pub const OFFLINE_MESSAGE: &str = "으앗, 지금 머리가 잘 안 돌아가… 🫠 조금 이따가 다시 불러줘!";
+
+/// State of the circuit around the OpenAI backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum CircuitState {
+    /// Requests are sent as usual
+    Closed,
+    /// Requests are refused without calling the API
+    Open,
+    /// A single probe request is let through to see if the backend is back
+    #[strum(serialize = "half-open")]
+    HalfOpen,
+}
+
+/// Error returned instead of calling the API while the circuit is open
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitOpenError {
+    pub retry_in: Duration,
+}
+
+impl std::fmt::Display for CircuitOpenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "OpenAI backend is unavailable, retrying in {}s",
+            self.retry_in.as_secs()
+        )
+    }
+}
+
+impl std::error::Error for CircuitOpenError {}
+
+/// Change of the circuit state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub from: CircuitState,
+    pub to: CircuitState,
+}
+
+/// Opens after `failure_threshold` consecutive failures and probes again after `cooldown`
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    /// When the probe request of the half-open state was let through
+    probe_started_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+            probe_started_at: None,
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.state
+    }
+
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Check whether a request may be sent now
+    /// Once the cooldown is over, a single probe is let through (again after another
+    /// cooldown if its outcome is never recorded)
+    pub fn try_acquire(&mut self, now: Instant) -> Result<Option<Transition>, CircuitOpenError> {
+        match self.state {
+            CircuitState::Closed => Ok(None),
+            CircuitState::Open => {
+                let opened_at = self.opened_at.unwrap_or(now);
+                let elapsed = now.saturating_duration_since(opened_at);
+                if elapsed < self.cooldown {
+                    return Err(CircuitOpenError {
+                        retry_in: self.cooldown - elapsed,
+                    });
+                }
+                self.probe_started_at = Some(now);
+                Ok(Some(self.transition(CircuitState::HalfOpen)))
+            }
+            CircuitState::HalfOpen => {
+                let probe_started_at = self.probe_started_at.unwrap_or(now);
+                let elapsed = now.saturating_duration_since(probe_started_at);
+                if self.probe_started_at.is_some() && elapsed < self.cooldown {
+                    return Err(CircuitOpenError {
+                        retry_in: self.cooldown - elapsed,
+                    });
+                }
+                self.probe_started_at = Some(now);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Record a successful request, closing the circuit
+    pub fn record_success(&mut self) -> Option<Transition> {
+        self.consecutive_failures = 0;
+        self.opened_at = None;
+        self.probe_started_at = None;
+        (self.state != CircuitState::Closed).then(|| self.transition(CircuitState::Closed))
+    }
+
+    /// Record a failed request, opening the circuit after too many in a row
+    pub fn record_failure(&mut self, now: Instant) -> Option<Transition> {
+        self.consecutive_failures += 1;
+        let should_open = match self.state {
+            CircuitState::Closed => self.consecutive_failures >= self.failure_threshold,
+            // The probe failed, the backend is still down
+            CircuitState::HalfOpen => true,
+            CircuitState::Open => false,
+        };
+        if !should_open {
+            return None;
+        }
+
+        self.opened_at = Some(now);
+        self.probe_started_at = None;
+        Some(self.transition(CircuitState::Open))
+    }
+
+    fn transition(&mut self, to: CircuitState) -> Transition {
+        let from = self.state;
+        self.state = to;
+        Transition { from, to }
+    }
+}
+
+/// Whether an error means the backend is down, rather than a problem with the request
+pub fn is_backend_failure(error: &eyre::Report) -> bool {
+    match error.downcast_ref::<OpenAiApiError>() {
+        Some(api_error) => api_error.status >= 500 || api_error.status == 429,
+        // Network errors and unreadable responses
+        None => error.downcast_ref::<CircuitOpenError>().is_none(),
+    }
+}
+
+/// Check whether a request to the backend may be sent now
+pub async fn acquire(ctx: &Context) -> Result<(), CircuitOpenError> {
+    let result = BREAKER.lock().await.try_acquire(Instant::now());
+    if let Ok(Some(transition)) = result {
+        report_transition(ctx, transition, None).await;
+    }
+    result.map(|_| ())
+}
+
+/// Record the outcome of a request to the backend
+pub async fn record_outcome<T>(ctx: &Context, result: &eyre::Result<T>) {
+    let transition = {
+        let mut breaker = BREAKER.lock().await;
+        match result {
+            Ok(_) => breaker.record_success(),
+            Err(e) if is_backend_failure(e) => breaker.record_failure(Instant::now()),
+            // The backend answered, the request itself was wrong
+            Err(_) => breaker.record_success(),
+        }
+    };
+
+    if let Some(transition) = transition {
+        let error = result.as_ref().err().map(|e| e.to_string());
+        report_transition(ctx, transition, error.as_deref()).await;
+    }
+}
+
+/// Get the current state of the circuit and the number of failures in a row
+pub async fn get_circuit_state() -> (CircuitState, u32) {
+    let breaker = BREAKER.lock().await;
+    (breaker.state(), breaker.consecutive_failures())
+}
+
+/// Log a state change, telling the developer when the backend goes down or comes back
+/// Probes are only logged, so a long outage doesn't send a DM every cooldown
+async fn report_transition(ctx: &Context, transition: Transition, error: Option<&str>) {
+    tracing::warn!("Circuit breaker: {} → {}", transition.from, transition.to);
+
+    let message = match transition {
+        Transition {
+            from: CircuitState::Closed,
+            to: CircuitState::Open,
+        } => format!(
+            "Circuit breaker opened after {} failures in a row, requests are paused for {}s.\nLast error: {}",
+            *BREAKER_FAILURE_THRESHOLD,
+            *BREAKER_COOLDOWN_SECS,
+            error.unwrap_or("-")
+        ),
+        Transition {
+            to: CircuitState::Closed,
+            ..
+        } => "Circuit breaker closed, the OpenAI backend is answering again.".to_string(),
+        _ => return,
+    };
+    if let Err(e) = discord::send_dm_to_dev(ctx, &message).await {
+        tracing::error!("Failed to send circuit breaker alert: {:?}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let mut breaker = CircuitBreaker::new(3, COOLDOWN);
+        let now = Instant::now();
+
+        assert_eq!(breaker.record_failure(now), None);
+        assert_eq!(breaker.record_failure(now), None);
+        // A success in between starts the count over
+        assert_eq!(breaker.record_success(), None);
+        assert_eq!(breaker.record_failure(now), None);
+        assert_eq!(breaker.record_failure(now), None);
+        assert_eq!(
+            breaker.record_failure(now),
+            Some(Transition {
+                from: CircuitState::Closed,
+                to: CircuitState::Open,
+            })
+        );
+
+        let refused = breaker
+            .try_acquire(now + Duration::from_secs(20))
+            .unwrap_err();
+        assert_eq!(refused.retry_in, Duration::from_secs(40));
+    }
+
+    #[test]
+    fn test_half_open_probe() {
+        let mut breaker = CircuitBreaker::new(1, COOLDOWN);
+        let start = Instant::now();
+        breaker.record_failure(start);
+
+        // After the cooldown a single probe is let through
+        let probe_time = start + COOLDOWN;
+        assert_eq!(
+            breaker.try_acquire(probe_time).unwrap(),
+            Some(Transition {
+                from: CircuitState::Open,
+                to: CircuitState::HalfOpen,
+            })
+        );
+        assert!(breaker.try_acquire(probe_time).is_err());
+
+        // A failed probe opens the circuit again
+        assert_eq!(
+            breaker.record_failure(probe_time),
+            Some(Transition {
+                from: CircuitState::HalfOpen,
+                to: CircuitState::Open,
+            })
+        );
+        assert!(breaker.try_acquire(probe_time + COOLDOWN / 2).is_err());
+
+        // A successful probe closes it
+        assert!(breaker.try_acquire(probe_time + COOLDOWN).is_ok());
+        assert_eq!(
+            breaker.record_success(),
+            Some(Transition {
+                from: CircuitState::HalfOpen,
+                to: CircuitState::Closed,
+            })
+        );
+        assert_eq!(breaker.try_acquire(probe_time + COOLDOWN), Ok(None));
+    }
+
+    #[test]
+    fn test_is_backend_failure() {
+        let api_error = |status| eyre::Report::new(OpenAiApiError::from_response_body(status, ""));
+        assert!(is_backend_failure(&api_error(500)));
+        assert!(is_backend_failure(&api_error(429)));
+        assert!(!is_backend_failure(&api_error(400)));
+        assert!(is_backend_failure(&eyre::eyre!("connection reset")));
+        assert!(!is_backend_failure(&eyre::Report::new(CircuitOpenError {
+            retry_in: COOLDOWN
+        })));
+    }
+}
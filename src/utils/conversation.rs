@@ -17,7 +17,9 @@ impl Display for ChatMessage {
             .map(|item| match item {
                 ContentItem::InputText { text } => text.clone(),
                 ContentItem::InputImage { image_url } => format!("[Image: {image_url}]"),
+                ContentItem::InputFile { filename, .. } => format!("[File: {filename}]"),
                 ContentItem::OutputText { text } => text.clone(),
+                ContentItem::Refusal { refusal } => format!("[Refusal: {refusal}]"),
                 ContentItem::Other => "[Unknown content]".to_string(),
             })
             .collect::<Vec<_>>()
@@ -41,21 +43,54 @@ impl ChatMessage {
         }
     }
 
-    /// Create a new user message with both text and image content
-    pub fn user_with_image(text_content: String, name: String, image_url: String) -> Self {
-        let formatted_content = format!("({name}) {text_content}");
+    /// Create a new user message from a voice message, with the text sent along with it
+    pub fn user_with_voice(text_content: String, name: String, transcript: String) -> Self {
+        let text_content = text_content.trim();
+        let formatted_content = if text_content.is_empty() {
+            format!("({name}) [voice] {transcript}")
+        } else {
+            format!("({name}) {text_content}\n[voice] {transcript}")
+        };
 
         Self {
             role: "user".to_string(),
-            content: vec![
-                ContentItem::InputText {
-                    text: formatted_content,
-                },
-                ContentItem::InputImage { image_url },
-            ],
+            content: vec![ContentItem::InputText {
+                text: formatted_content,
+            }],
         }
     }
 
+    /// Create a new user message with text and any number of images
+    pub fn user_with_images(text_content: String, name: String, image_urls: Vec<String>) -> Self {
+        Self::user(text_content, name).with_images(image_urls)
+    }
+
+    /// Add images sent along with the message, each as a separate content item
+    pub fn with_images(mut self, image_urls: Vec<String>) -> Self {
+        self.content.extend(
+            image_urls
+                .into_iter()
+                .map(|image_url| ContentItem::InputImage { image_url }),
+        );
+        self
+    }
+
+    /// Add the content of a text file sent along with the message
+    pub fn with_text_attachment(mut self, text: String) -> Self {
+        self.content.push(ContentItem::InputText { text });
+        self
+    }
+
+    /// Add a file (e.g. a PDF) sent along with the message, read by the model itself
+    pub fn with_file(mut self, filename: String, file_url: String) -> Self {
+        self.content.push(ContentItem::InputFile {
+            filename,
+            file_url: Some(file_url),
+            file_data: None,
+        });
+        self
+    }
+
     /// Create a new assistant message
     pub fn assistant(content: String) -> Self {
         Self {
@@ -64,6 +99,21 @@ impl ChatMessage {
         }
     }
 
+    /// Append text to the last text content, e.g. when continuing a reply that was cut off
+    pub fn append_text(&mut self, text: &str) {
+        let last_text = self.content.iter_mut().rev().find_map(|item| match item {
+            ContentItem::OutputText { text } | ContentItem::InputText { text } => Some(text),
+            _ => None,
+        });
+
+        match last_text {
+            Some(existing) => existing.push_str(text),
+            None => self.content.push(ContentItem::OutputText {
+                text: text.to_string(),
+            }),
+        }
+    }
+
     /// Create a new developer message
     pub fn developer(content: String) -> Self {
         Self {
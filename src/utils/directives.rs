@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use strum_macros::{EnumIter, EnumString};
+
+/// Directive that can be written in front of a mention, e.g. `!nomemory`
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    Serialize,
+    Deserialize,
+    EnumString,
+    EnumIter,
+    strum_macros::Display,
+)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum Directive {
+    /// `!model=<name>`: answer with another model, without fallback
+    Model,
+    /// `!ephemeral`: send the answer by DM instead of in the channel, implies `!nomemory`
+    Ephemeral,
+    /// `!nomemory`: keep the message and the answer out of the history
+    NoMemory,
+}
+
+/// Directives used by default only by admins
+pub fn default_admin_only_directives() -> HashSet<Directive> {
+    HashSet::from([Directive::Model])
+}
+
+/// Directives given in a message
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MessageDirectives {
+    pub model: Option<String>,
+    pub ephemeral: bool,
+    pub no_memory: bool,
+}
+
+impl MessageDirectives {
+    /// Directives that were given
+    pub fn used(&self) -> Vec<Directive> {
+        let mut used = Vec::new();
+        if self.model.is_some() {
+            used.push(Directive::Model);
+        }
+        if self.ephemeral {
+            used.push(Directive::Ephemeral);
+        }
+        if self.no_memory {
+            used.push(Directive::NoMemory);
+        }
+        used
+    }
+
+    /// Whether no directive was given
+    pub fn is_empty(&self) -> bool {
+        self.used().is_empty()
+    }
+
+    /// Whether the message and its answer are kept out of the history
+    /// A private answer is never kept, or anyone in the channel could ask the bot for it
+    pub fn is_transient(&self) -> bool {
+        self.no_memory || self.ephemeral
+    }
+
+    /// Ignore a directive
+    pub fn remove(&mut self, directive: Directive) {
+        match directive {
+            Directive::Model => self.model = None,
+            Directive::Ephemeral => self.ephemeral = false,
+            Directive::NoMemory => self.no_memory = false,
+        }
+    }
+}
+
+/// Parse the directives at the start of a message
+/// Returns them along with the rest of the message, parsing stops at the first other word
+pub fn parse_directives(content: &str) -> (MessageDirectives, &str) {
+    let mut directives = MessageDirectives::default();
+    let mut rest = content.trim_start();
+
+    loop {
+        let (word, remainder) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let Some(directive) = word.strip_prefix('!') else {
+            break;
+        };
+
+        match directive.split_once('=') {
+            Some((name, model)) if name.eq_ignore_ascii_case("model") && !model.is_empty() => {
+                directives.model = Some(model.to_string());
+            }
+            None if directive.eq_ignore_ascii_case("ephemeral") => directives.ephemeral = true,
+            None if directive.eq_ignore_ascii_case("nomemory") => directives.no_memory = true,
+            _ => break,
+        }
+        rest = remainder.trim_start();
+    }
+
+    (directives, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_directives() {
+        let (directives, rest) = parse_directives("!model=o3 !ephemeral !NoMemory 질문 !ephemeral");
+        assert_eq!(
+            directives,
+            MessageDirectives {
+                model: Some("o3".to_string()),
+                ephemeral: true,
+                no_memory: true,
+            }
+        );
+        assert_eq!(rest, "질문 !ephemeral");
+
+        let (directives, rest) = parse_directives("안녕 !nomemory");
+        assert!(directives.is_empty());
+        assert_eq!(rest, "안녕 !nomemory");
+
+        // Unknown directives are left in the message
+        let (directives, rest) = parse_directives("!nomemory !wow hi");
+        assert_eq!(directives.used(), vec![Directive::NoMemory]);
+        assert_eq!(rest, "!wow hi");
+
+        let (directives, rest) = parse_directives("!model= hi");
+        assert!(directives.is_empty());
+        assert_eq!(rest, "!model= hi");
+
+        let (directives, rest) = parse_directives("!ephemeral");
+        assert!(directives.ephemeral);
+        assert_eq!(rest, "");
+        // A private answer stays out of the channel history
+        assert!(directives.is_transient());
+        assert!(!parse_directives("!model=o3 hi").0.is_transient());
+    }
+
+    #[test]
+    fn test_remove_directive() {
+        let (mut directives, _) = parse_directives("!model=o3 !nomemory hi");
+        directives.remove(Directive::Model);
+        assert_eq!(directives.used(), vec![Directive::NoMemory]);
+        directives.remove(Directive::NoMemory);
+        assert!(directives.is_empty());
+    }
+}
@@ -1,11 +1,30 @@
 use std::fmt::Display;
 
-use serenity::{all::CreateMessage, model::prelude::ChannelId, prelude::Context};
-
+use serenity::{
+    all::{
+        CommandInteraction, CreateAllowedMentions, CreateAttachment,
+        CreateInteractionResponseFollowup, CreateMessage, MessageReference,
+    },
+    model::prelude::{ChannelId, MessageId},
+    prelude::Context,
+};
+
+use super::persistence::is_reply_ping_enabled;
 use super::statics::DEV_USER_ID;
 
 /// Send a message to a Discord channel, automatically handling message chunking for long messages
 pub async fn say(ctx: &Context, channel: ChannelId, msg: impl Display) -> eyre::Result<()> {
+    say_reply(ctx, channel, None, msg).await
+}
+
+/// Send a message as a reply to another one, like `say`
+/// Only the first chunk of a long message is a reply, the others follow it in the channel
+pub async fn say_reply(
+    ctx: &Context,
+    channel: ChannelId,
+    reply_to: Option<MessageId>,
+    msg: impl Display,
+) -> eyre::Result<()> {
     // Convert the message to a string
     let content = msg.to_string();
 
@@ -14,7 +33,8 @@ pub async fn say(ctx: &Context, channel: ChannelId, msg: impl Display) -> eyre::
 
     if content.len() <= DISCORD_MESSAGE_LIMIT {
         // Send as a single message if it's short enough
-        match channel.say(&ctx.http, content).await {
+        let message = create_message(channel, reply_to).await.content(content);
+        match channel.send_message(&ctx.http, message).await {
             Ok(_) => {}
             Err(e) => {
                 tracing::error!("Failed to send message: {}", e);
@@ -22,16 +42,38 @@ pub async fn say(ctx: &Context, channel: ChannelId, msg: impl Display) -> eyre::
             }
         }
     } else {
-        send_chunked_message(ctx, channel, content).await?;
+        send_chunked_message(ctx, channel, reply_to, content).await?;
     }
 
     Ok(())
 }
 
+/// Start a message, replying to another one if given
+async fn create_message(channel: ChannelId, reply_to: Option<MessageId>) -> CreateMessage {
+    let message = CreateMessage::new();
+    let Some(message_id) = reply_to else {
+        return message;
+    };
+
+    let mut reference = MessageReference::from((channel, message_id));
+    // Send a normal message if the question was deleted in the meantime
+    reference.fail_if_not_exists = Some(false);
+    // Only the reply ping is configurable, other mentions work as in `channel.say`
+    let mentions = CreateAllowedMentions::new()
+        .all_users(true)
+        .all_roles(true)
+        .everyone(true)
+        .replied_user(is_reply_ping_enabled().await);
+    message
+        .reference_message(reference)
+        .allowed_mentions(mentions)
+}
+
 /// Split a long message into chunks and send them sequentially
 async fn send_chunked_message(
     ctx: &Context,
     channel: ChannelId,
+    reply_to: Option<MessageId>,
     content: String,
 ) -> eyre::Result<()> {
     // Discord has a 2000 character limit per message
@@ -39,6 +81,7 @@ async fn send_chunked_message(
 
     // Split the message into chunks
     let mut remaining = content.as_str();
+    let mut reply_to = reply_to;
 
     while !remaining.is_empty() {
         let chunk_size = std::cmp::min(DISCORD_MESSAGE_LIMIT, remaining.len());
@@ -46,9 +89,12 @@ async fn send_chunked_message(
         // Try to find a good breaking point (newline or space)
         let actual_size = find_chunk_break_point(remaining, chunk_size);
 
-        // Send this chunk
+        // Send this chunk, only the first one is a reply
         let chunk = &remaining[..actual_size];
-        match channel.say(&ctx.http, chunk).await {
+        let message = create_message(channel, reply_to.take())
+            .await
+            .content(chunk);
+        match channel.send_message(&ctx.http, message).await {
             Ok(_) => {}
             Err(e) => {
                 tracing::error!("Failed to send message chunk: {}", e);
@@ -63,6 +109,36 @@ async fn send_chunked_message(
     Ok(())
 }
 
+/// Send a message only the user of a deferred slash command can see, chunked like `say`
+pub async fn say_ephemeral(
+    ctx: &Context,
+    interaction: &CommandInteraction,
+    msg: impl Display,
+) -> eyre::Result<()> {
+    // Discord has a 2000 character limit per message
+    const DISCORD_MESSAGE_LIMIT: usize = 2000;
+
+    let content = msg.to_string();
+    let mut remaining = content.as_str();
+
+    while !remaining.is_empty() {
+        let chunk_size = std::cmp::min(DISCORD_MESSAGE_LIMIT, remaining.len());
+        let actual_size = find_chunk_break_point(remaining, chunk_size);
+
+        let followup = CreateInteractionResponseFollowup::new()
+            .content(&remaining[..actual_size])
+            .ephemeral(true);
+        if let Err(e) = interaction.create_followup(&ctx.http, followup).await {
+            tracing::error!("Failed to send ephemeral message: {}", e);
+            return Err(eyre::eyre!("{}", e));
+        }
+
+        remaining = &remaining[actual_size..];
+    }
+
+    Ok(())
+}
+
 /// Find an appropriate break point for message chunking
 fn find_chunk_break_point(text: &str, max_size: usize) -> usize {
     if max_size >= text.len() {
@@ -94,6 +170,61 @@ fn find_safe_boundary(text: &str, pos: usize) -> usize {
     safe_pos
 }
 
+/// Wrap text in a spoiler that fits in a single message, shortening it if needed
+pub fn spoiler(text: &str) -> String {
+    // Discord has a 2000 character limit per message
+    const DISCORD_MESSAGE_LIMIT: usize = 2000;
+    // Leave room for the spoiler markers and the ellipsis
+    const MAX_TEXT_SIZE: usize = DISCORD_MESSAGE_LIMIT - 8;
+
+    // Markers inside the text would end the spoiler early
+    let text = text.trim().replace("||", "| |");
+    if text.len() <= MAX_TEXT_SIZE {
+        return format!("||{text}||");
+    }
+
+    let end = find_safe_boundary(&text, MAX_TEXT_SIZE);
+    format!("||{}…||", &text[..end])
+}
+
+/// Format text as a block quote that fits in a single message, shortening it if needed
+pub fn quote(text: &str) -> String {
+    // Discord has a 2000 character limit per message
+    const DISCORD_MESSAGE_LIMIT: usize = 2000;
+
+    let quoted = text
+        .trim()
+        .lines()
+        .map(|line| format!("> {line}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    if quoted.len() <= DISCORD_MESSAGE_LIMIT {
+        return quoted;
+    }
+
+    // Leave room for the ellipsis
+    let end = find_safe_boundary(&quoted, DISCORD_MESSAGE_LIMIT - 3);
+    format!("{}…", &quoted[..end])
+}
+
+/// Upload an image to a Discord channel, as a reply if given
+pub async fn send_image(
+    ctx: &Context,
+    channel: ChannelId,
+    reply_to: Option<MessageId>,
+    bytes: Vec<u8>,
+    filename: &str,
+) -> eyre::Result<()> {
+    let attachment = CreateAttachment::bytes(bytes, filename);
+    let message = create_message(channel, reply_to).await.add_file(attachment);
+    if let Err(e) = channel.send_message(&ctx.http, message).await {
+        tracing::error!("Failed to send image: {}", e);
+        return Err(eyre::eyre!("{}", e));
+    }
+
+    Ok(())
+}
+
 /// Send a direct message to the developer
 pub async fn send_dm_to_dev(ctx: &Context, msg: &str) -> eyre::Result<()> {
     if let Ok(user) = DEV_USER_ID.to_user(&ctx.http).await {
@@ -175,4 +306,26 @@ mod tests {
         let max_size = text.len() + 10;
         assert_eq!(find_chunk_break_point(text, max_size), text.len());
     }
+
+    #[test]
+    fn test_spoiler() {
+        assert_eq!(spoiler("thinking || hard\n"), "||thinking | | hard||");
+
+        let long = "가".repeat(1000);
+        let wrapped = spoiler(&long);
+        assert!(wrapped.len() <= 2000);
+        assert!(wrapped.starts_with("||가"));
+        assert!(wrapped.ends_with("…||"));
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("hello\nworld\n"), "> hello\n> world");
+
+        let long = "가".repeat(1000);
+        let quoted = quote(&long);
+        assert!(quoted.len() <= 2000);
+        assert!(quoted.starts_with("> 가"));
+        assert!(quoted.ends_with('…'));
+    }
 }
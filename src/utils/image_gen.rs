@@ -0,0 +1,304 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serenity::prelude::Context;
+
+use crate::utils::circuit_breaker;
+use crate::utils::conversation::ChatMessage;
+use crate::utils::discord;
+use crate::utils::moderation::{ContentDirection, ModerationAction, moderate};
+use crate::utils::msg_context::MsgContextInfo;
+use crate::utils::openai_schema::OpenAiApiError;
+use crate::utils::persistence::{add_message, record_usage};
+use crate::utils::pricing::get_model_pricing;
+use crate::utils::statics::{OPENAI_BASE_URL, OPENAI_TOKEN};
+use crate::utils::usage::UsageTotals;
+
+const IMAGE_MODEL: &str = "gpt-image-1";
+const IMAGE_SIZE: &str = "1024x1024";
+const IMAGE_QUALITY: &str = "medium";
+pub const MAX_PROMPT_LENGTH: usize = 4000;
+
+/// Request body of the Images API
+#[derive(Debug, Serialize)]
+struct ImageGenerationRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    size: &'a str,
+    quality: &'a str,
+    n: u32,
+}
+
+/// Response body of the Images API
+#[derive(Debug, Deserialize)]
+struct ImageGenerationResponse {
+    data: Vec<ImageData>,
+    #[serde(default)]
+    usage: ImageUsage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ImageData {
+    b64_json: Option<String>,
+    revised_prompt: Option<String>,
+}
+
+/// Token usage of an image generation
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct ImageUsage {
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+}
+
+/// An image made by the Images API
+#[derive(Debug, Clone)]
+pub struct GeneratedImage {
+    pub bytes: Vec<u8>,
+    pub revised_prompt: Option<String>,
+    pub usage: ImageUsage,
+    pub model: String,
+}
+
+impl GeneratedImage {
+    /// Cost of the generation in USD, or None if the model has no known price
+    pub fn cost(&self) -> Option<f64> {
+        let pricing = get_model_pricing(&self.model)?;
+        let cost = f64::from(self.usage.input_tokens) * pricing.input
+            + f64::from(self.usage.output_tokens) * pricing.output;
+        Some(cost / 1_000_000.0)
+    }
+}
+
+/// Generate an image with the Images API at the given base URL
+pub async fn generate_image(
+    client: &Client,
+    base_url: &str,
+    token: &str,
+    prompt: &str,
+) -> eyre::Result<GeneratedImage> {
+    let request = ImageGenerationRequest {
+        model: IMAGE_MODEL,
+        prompt,
+        size: IMAGE_SIZE,
+        quality: IMAGE_QUALITY,
+        n: 1,
+    };
+
+    let response = client
+        .post(format!("{base_url}/images/generations"))
+        .header("Authorization", format!("Bearer {token}"))
+        .json(&request)
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await?;
+        return Err(OpenAiApiError::from_response_body(status.as_u16(), &error_text).into());
+    }
+
+    let response: ImageGenerationResponse = response.json().await?;
+    let image = response
+        .data
+        .into_iter()
+        .next()
+        .ok_or_else(|| eyre::eyre!("No image in the response"))?;
+    let encoded = image
+        .b64_json
+        .ok_or_else(|| eyre::eyre!("Image response has no data"))?;
+
+    Ok(GeneratedImage {
+        bytes: BASE64.decode(encoded)?,
+        revised_prompt: image.revised_prompt,
+        usage: response.usage,
+        model: IMAGE_MODEL.to_string(),
+    })
+}
+
+/// Who wrote the prompt of an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptSource {
+    /// Taken from the user's message, which was already moderated
+    User,
+    /// Written by the model in a tool call
+    Model,
+}
+
+/// A prompt that isn't sent to the Images API
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidPrompt {
+    Empty,
+    TooLong,
+    Blocked,
+}
+
+impl InvalidPrompt {
+    /// In-character reply to a `<draw>` command with this prompt
+    pub fn user_message(&self) -> &'static str {
+        match self {
+            InvalidPrompt::Empty => "뭘 그려줄까? `<draw> 고양이`처럼 말해줘!",
+            InvalidPrompt::TooLong => "설명이 너무 길어서 머리에 다 안 들어가 ㅠㅠ 조금만 줄여줘!",
+            InvalidPrompt::Blocked => "으음… 그건 그려주기 어려워. 다른 걸 그려볼까?",
+        }
+    }
+}
+
+impl std::fmt::Display for InvalidPrompt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidPrompt::Empty => write!(f, "The prompt is empty"),
+            InvalidPrompt::TooLong => write!(
+                f,
+                "The prompt is too long (max {MAX_PROMPT_LENGTH} characters)"
+            ),
+            InvalidPrompt::Blocked => write!(f, "The prompt was blocked by moderation"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidPrompt {}
+
+/// Check a prompt before it is sent, moderating it if the model wrote it
+async fn check_prompt(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    prompt: &str,
+    source: PromptSource,
+) -> Result<(), InvalidPrompt> {
+    if prompt.trim().is_empty() {
+        return Err(InvalidPrompt::Empty);
+    }
+    if prompt.chars().count() > MAX_PROMPT_LENGTH {
+        return Err(InvalidPrompt::TooLong);
+    }
+    if source == PromptSource::Model
+        && moderate(ctx, msg_ctx, ContentDirection::Input, prompt).await
+            == Some(ModerationAction::Block)
+    {
+        return Err(InvalidPrompt::Blocked);
+    }
+    Ok(())
+}
+
+/// Generate an image, post it to the channel of the message and record it in the history
+/// unless the request is `transient`
+/// Returns the generated image, or an `InvalidPrompt` error without calling the API
+pub async fn draw_and_post(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    prompt: &str,
+    source: PromptSource,
+    transient: bool,
+) -> eyre::Result<GeneratedImage> {
+    check_prompt(ctx, msg_ctx, prompt, source).await?;
+
+    // Refused without calling the API while the backend is down
+    circuit_breaker::acquire(ctx).await?;
+    let result = generate_image(&Client::new(), &OPENAI_BASE_URL, &OPENAI_TOKEN, prompt).await;
+    circuit_breaker::record_outcome(ctx, &result).await;
+    let image = result?;
+
+    // Track the cost like any other response
+    let cost_usd = image.cost();
+    if cost_usd.is_none() {
+        tracing::warn!(
+            "No pricing known for model {}, cost not tracked",
+            image.model
+        );
+    }
+    let totals = UsageTotals {
+        requests: 1,
+        input_tokens: u64::from(image.usage.input_tokens),
+        output_tokens: u64::from(image.usage.output_tokens),
+        cost_usd: cost_usd.unwrap_or_default(),
+        ..Default::default()
+    };
+    record_usage(msg_ctx, totals).await;
+    tracing::info!(
+        "Generated image for {:?} with {} (cost: ${:.4})",
+        prompt,
+        image.model,
+        totals.cost_usd
+    );
+
+    let reply_to = msg_ctx.reply_to(msg_ctx.channel_id);
+    discord::send_image(
+        ctx,
+        msg_ctx.channel_id,
+        reply_to,
+        image.bytes.clone(),
+        "image.png",
+    )
+    .await?;
+
+    // Let the model know what it has drawn
+    if !transient {
+        let description = image.revised_prompt.as_deref().unwrap_or(prompt);
+        let message = ChatMessage::assistant(format!("[Generated image: {description}]"));
+        add_message(msg_ctx.channel_id, message).await;
+    }
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_server::StandInServer;
+
+    #[tokio::test]
+    async fn test_generate_image_with_stand_in_server() {
+        let png = b"\x89PNG\r\n\x1a\nfake image".to_vec();
+        let body = serde_json::json!({
+            "created": 1_700_000_000,
+            "data": [{ "b64_json": BASE64.encode(&png), "revised_prompt": "a cute cat" }],
+            "usage": {
+                "input_tokens": 50,
+                "input_tokens_details": { "text_tokens": 50, "image_tokens": 0 },
+                "output_tokens": 1056,
+                "total_tokens": 1106
+            }
+        });
+        let server = StandInServer::start(200, "application/json", body.to_string()).await;
+
+        let image = generate_image(&Client::new(), &server.base_url, "test-token", "cat")
+            .await
+            .unwrap();
+        assert_eq!(image.bytes, png);
+        assert_eq!(image.revised_prompt.as_deref(), Some("a cute cat"));
+        assert_eq!(image.usage.output_tokens, 1056);
+        let expected_cost = (50.0 * 5.0 + 1056.0 * 40.0) / 1_000_000.0;
+        assert!((image.cost().unwrap() - expected_cost).abs() < 1e-12);
+
+        let request = server.request().await;
+        assert_eq!(request.request_line, "POST /images/generations HTTP/1.1");
+        assert!(
+            request
+                .headers
+                .to_lowercase()
+                .contains("authorization: bearer test-token")
+        );
+        let sent: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(sent["prompt"], "cat");
+        assert_eq!(sent["model"], IMAGE_MODEL);
+    }
+
+    #[tokio::test]
+    async fn test_generate_image_reports_api_errors() {
+        let body = r#"{"error": {"message": "Your request was rejected by the safety system.", "type": "image_generation_user_error", "param": null, "code": "moderation_blocked"}}"#;
+        let server = StandInServer::start(400, "application/json", body).await;
+
+        let err = generate_image(
+            &Client::new(),
+            &server.base_url,
+            "test-token",
+            "something bad",
+        )
+        .await
+        .unwrap_err();
+        let api_error = err.downcast_ref::<OpenAiApiError>().unwrap();
+        assert_eq!(api_error.status, 400);
+        assert_eq!(api_error.code.as_deref(), Some("moderation_blocked"));
+    }
+}
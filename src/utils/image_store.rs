@@ -0,0 +1,443 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use lazy_static::lazy_static;
+use reqwest::Client;
+use ring::digest::{SHA256, digest};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::utils::conversation::ChatMessage;
+use crate::utils::openai_schema::ContentItem;
+use crate::utils::statics::{IMAGE_MAX_SIDE, get_state_dir_name};
+
+/// Prefix of image URLs in the history that refer to a file in the data directory
+pub const LOCAL_IMAGE_PREFIX: &str = "local-image:";
+/// Prefix of file URLs (PDFs) in the history that refer to a file in the data directory
+pub const LOCAL_FILE_PREFIX: &str = "local-file:";
+
+// Images larger than this are not downloaded
+const MAX_DOWNLOAD_SIZE: usize = 20 * 1024 * 1024;
+// PDFs larger than this are not downloaded
+const MAX_FILE_DOWNLOAD_SIZE: usize = 32 * 1024 * 1024;
+const JPEG_QUALITY: u8 = 85;
+
+lazy_static! {
+    /// How many messages in the histories or waiting to be added refer to each stored file
+    /// A file is only deleted while this lock is held and its count is zero
+    static ref FILE_REFERENCES: Mutex<HashMap<String, usize>> = Mutex::new(HashMap::new());
+}
+
+/// Stored files of a message that is not in the history yet
+/// Dropping this releases them, and deletes those nothing else refers to
+#[derive(Debug, Default)]
+pub struct PendingFiles {
+    names: Vec<String>,
+}
+
+impl PendingFiles {
+    fn hold(&mut self, name: &str) {
+        add_references([name]);
+        self.names.push(name.to_string());
+    }
+}
+
+impl Clone for PendingFiles {
+    fn clone(&self) -> Self {
+        add_references(self.names.iter().map(String::as_str));
+        Self {
+            names: self.names.clone(),
+        }
+    }
+}
+
+impl Drop for PendingFiles {
+    fn drop(&mut self) {
+        let unreferenced = remove_references(self.names.drain(..));
+        if unreferenced.is_empty() {
+            return;
+        }
+        // Not added after all, e.g. refused because of the budget
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            runtime.spawn(delete_unreferenced_files(unreferenced));
+        }
+    }
+}
+
+fn add_references<'a>(names: impl IntoIterator<Item = &'a str>) {
+    let mut references = FILE_REFERENCES.lock().unwrap();
+    for name in names {
+        *references.entry(name.to_string()).or_default() += 1;
+    }
+}
+
+/// Returns the names nothing refers to anymore
+fn remove_references(names: impl IntoIterator<Item = impl AsRef<str>>) -> Vec<String> {
+    let mut references = FILE_REFERENCES.lock().unwrap();
+    let mut unreferenced = Vec::new();
+    for name in names {
+        let name = name.as_ref();
+        let Some(count) = references.get_mut(name) else {
+            continue;
+        };
+        *count -= 1;
+        if *count == 0 {
+            references.remove(name);
+            unreferenced.push(name.to_string());
+        }
+    }
+    unreferenced
+}
+
+// PDFs are kept next to the images, their names are content hashes as well
+fn images_dir() -> PathBuf {
+    PathBuf::from(get_state_dir_name()).join("images")
+}
+
+/// Get the file name of a local image URL, or None for other URLs
+pub fn local_image_name(url: &str) -> Option<&str> {
+    url.strip_prefix(LOCAL_IMAGE_PREFIX)
+}
+
+/// Get the file name of a local file URL, or None for other URLs
+pub fn local_file_name(url: &str) -> Option<&str> {
+    url.strip_prefix(LOCAL_FILE_PREFIX)
+}
+
+/// File names of the stored images and PDFs in a message
+pub fn stored_file_names(message: &ChatMessage) -> impl Iterator<Item = &str> {
+    message.content.iter().filter_map(|item| match item {
+        ContentItem::InputImage { image_url } => local_image_name(image_url),
+        ContentItem::InputFile {
+            file_url: Some(file_url),
+            ..
+        } => local_file_name(file_url),
+        _ => None,
+    })
+}
+
+/// Shrink an image so its longest side is at most `max_side` and recompress it as JPEG
+pub fn downscale_image(bytes: &[u8], max_side: u32) -> eyre::Result<Vec<u8>> {
+    let mut image = image::load_from_memory(bytes)?;
+    if image.width() > max_side || image.height() > max_side {
+        // Keeps the aspect ratio
+        image = image.resize(max_side, max_side, FilterType::Triangle);
+    }
+
+    let mut jpeg = Vec::new();
+    image
+        .to_rgb8()
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY))?;
+    Ok(jpeg)
+}
+
+/// File name of a stored file, from a hash of its content that stays the same across builds
+fn stored_file_name(bytes: &[u8], extension: &str) -> String {
+    let hash: String = digest(&SHA256, bytes)
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    format!("{hash}.{extension}")
+}
+
+/// Write a file to the data directory, keeping it until its message is added
+async fn write_stored_file(
+    name: &str,
+    bytes: &[u8],
+    pending: &mut PendingFiles,
+) -> eyre::Result<()> {
+    // Referenced before writing, so a deletion of the same file can't follow the write
+    pending.hold(name);
+
+    let dir = images_dir();
+    tokio::fs::create_dir_all(&dir).await?;
+    tokio::fs::write(dir.join(name), bytes).await?;
+    Ok(())
+}
+
+/// Download a file, failing if it is larger than `max_size`
+async fn download(client: &Client, url: &str, max_size: usize) -> eyre::Result<Vec<u8>> {
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    if response
+        .content_length()
+        .is_some_and(|length| length as usize > max_size)
+    {
+        return Err(eyre::eyre!("File is too large to download"));
+    }
+
+    // The length may be missing or wrong, so stop reading once the body gets too large
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if bytes.len() + chunk.len() > max_size {
+            return Err(eyre::eyre!("File is too large to download"));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// Count the stored files of messages added to the history
+/// Call this with the state lock held
+pub fn reference_stored_files<'a>(messages: impl IntoIterator<Item = &'a ChatMessage>) {
+    add_references(messages.into_iter().flat_map(stored_file_names));
+}
+
+/// Uncount the stored files of messages removed from the history
+/// Call this with the state lock held, and pass the result to `delete_unreferenced_files`
+/// once it is released
+pub fn unreference_stored_files(messages: &[ChatMessage]) -> Vec<String> {
+    remove_references(messages.iter().flat_map(stored_file_names))
+}
+
+/// Download an image, shrink it and keep it in the data directory
+/// Returns the local image URL to store in the history
+async fn store_image(
+    client: &Client,
+    url: &str,
+    pending: &mut PendingFiles,
+) -> eyre::Result<String> {
+    let bytes = download(client, url, MAX_DOWNLOAD_SIZE).await?;
+
+    let max_side = *IMAGE_MAX_SIDE;
+    let jpeg = tokio::task::spawn_blocking(move || downscale_image(&bytes, max_side)).await??;
+
+    // Name the file after its content so the same image is only stored once
+    let name = stored_file_name(&jpeg, "jpg");
+    write_stored_file(&name, &jpeg, pending).await?;
+
+    Ok(format!("{LOCAL_IMAGE_PREFIX}{name}"))
+}
+
+/// Download a PDF and keep it in the data directory
+/// Returns the local file URL to store in the history
+async fn store_pdf(client: &Client, url: &str, pending: &mut PendingFiles) -> eyre::Result<String> {
+    let bytes = download(client, url, MAX_FILE_DOWNLOAD_SIZE).await?;
+
+    let name = stored_file_name(&bytes, "pdf");
+    write_stored_file(&name, &bytes, pending).await?;
+
+    Ok(format!("{LOCAL_FILE_PREFIX}{name}"))
+}
+
+/// Replace expiring image URLs with copies in the data directory
+/// URLs that can't be stored are kept as they are
+/// The stored files are kept in `pending` until the message is added
+pub async fn persist_images(urls: Vec<String>, pending: &mut PendingFiles) -> Vec<String> {
+    let client = Client::new();
+    let mut persisted = Vec::with_capacity(urls.len());
+
+    for url in urls {
+        match store_image(&client, &url, pending).await {
+            Ok(local_url) => persisted.push(local_url),
+            Err(e) => {
+                tracing::warn!("Failed to store image {url}, keeping the URL: {e}");
+                persisted.push(url);
+            }
+        }
+    }
+
+    persisted
+}
+
+/// Replace expiring PDF URLs of `(filename, url)` pairs with copies in the data directory
+/// URLs that can't be stored are kept as they are
+/// The stored files are kept in `pending` until the message is added
+pub async fn persist_files(
+    files: Vec<(String, String)>,
+    pending: &mut PendingFiles,
+) -> Vec<(String, String)> {
+    let client = Client::new();
+    let mut persisted = Vec::with_capacity(files.len());
+
+    for (filename, url) in files {
+        match store_pdf(&client, &url, pending).await {
+            Ok(local_url) => persisted.push((filename, local_url)),
+            Err(e) => {
+                tracing::warn!("Failed to store file {filename}, keeping the URL: {e}");
+                persisted.push((filename, url));
+            }
+        }
+    }
+
+    persisted
+}
+
+/// Inline the stored images and PDFs of messages so they can be sent to the API
+/// Files that are gone are replaced by a note
+pub async fn resolve_local_images(mut messages: Vec<ChatMessage>) -> Vec<ChatMessage> {
+    for message in &mut messages {
+        for item in &mut message.content {
+            if let ContentItem::InputFile {
+                filename,
+                file_url: Some(file_url),
+                ..
+            } = item
+            {
+                let Some(name) = local_file_name(file_url) else {
+                    continue;
+                };
+                *item = match tokio::fs::read(images_dir().join(name)).await {
+                    Ok(bytes) => ContentItem::InputFile {
+                        filename: filename.clone(),
+                        file_url: None,
+                        file_data: Some(format!(
+                            "data:application/pdf;base64,{}",
+                            BASE64.encode(bytes)
+                        )),
+                    },
+                    Err(e) => {
+                        tracing::warn!("Stored file {name} is not readable: {e}");
+                        ContentItem::InputText {
+                            text: format!("[File: {filename} (no longer available)]"),
+                        }
+                    }
+                };
+                continue;
+            }
+
+            let ContentItem::InputImage { image_url } = item else {
+                continue;
+            };
+            let Some(name) = local_image_name(image_url) else {
+                continue;
+            };
+
+            *item = match tokio::fs::read(images_dir().join(name)).await {
+                Ok(bytes) => ContentItem::InputImage {
+                    image_url: format!("data:image/jpeg;base64,{}", BASE64.encode(bytes)),
+                },
+                Err(e) => {
+                    tracing::warn!("Stored image {name} is not readable: {e}");
+                    ContentItem::InputText {
+                        text: "[Image no longer available]".to_string(),
+                    }
+                }
+            };
+        }
+    }
+
+    messages
+}
+
+/// Delete stored images and PDFs from the data directory, unless referenced again meanwhile
+/// Call this without the state lock held
+pub async fn delete_unreferenced_files(names: Vec<String>) {
+    if names.is_empty() {
+        return;
+    }
+    if let Err(e) = tokio::task::spawn_blocking(move || remove_unreferenced_files(&names)).await {
+        tracing::warn!("Failed to delete stored files: {e}");
+    }
+}
+
+fn remove_unreferenced_files(names: &[String]) {
+    // Held while deleting, so a file being written for a new message is either kept or
+    // written again after the deletion
+    let references = FILE_REFERENCES.lock().unwrap();
+    for name in names {
+        // Never leave the images directory
+        if name.contains(['/', '\\']) || name.starts_with('.') {
+            continue;
+        }
+        if references.contains_key(name) {
+            continue;
+        }
+        match std::fs::remove_file(images_dir().join(name)) {
+            Ok(()) => {}
+            // Failed to be written in the first place
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("Failed to delete stored image {name}: {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, RgbImage};
+    use std::io::Cursor;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        RgbImage::new(width, height)
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_downscale_image() {
+        let jpeg = downscale_image(&png(2000, 1000), 1024).unwrap();
+        assert_eq!(image::guess_format(&jpeg).unwrap(), ImageFormat::Jpeg);
+        let image = image::load_from_memory(&jpeg).unwrap();
+        assert_eq!((image.width(), image.height()), (1024, 512));
+
+        // Small images keep their size
+        let image =
+            image::load_from_memory(&downscale_image(&png(300, 200), 1024).unwrap()).unwrap();
+        assert_eq!((image.width(), image.height()), (300, 200));
+
+        assert!(downscale_image(b"not an image", 1024).is_err());
+    }
+
+    #[test]
+    fn test_stored_file_names() {
+        let message = ChatMessage::user_with_images(
+            "look".to_string(),
+            "a".to_string(),
+            vec![
+                "local-image:0123.jpg".to_string(),
+                "https://cdn.discordapp.com/a.png".to_string(),
+            ],
+        )
+        .with_file("a.pdf".to_string(), "local-file:4567.pdf".to_string())
+        .with_file(
+            "b.pdf".to_string(),
+            "https://cdn.discordapp.com/b.pdf".to_string(),
+        );
+        assert_eq!(
+            stored_file_names(&message).collect::<Vec<_>>(),
+            vec!["0123.jpg", "4567.pdf"]
+        );
+    }
+
+    #[test]
+    fn test_stored_file_name_is_sha256() {
+        assert_eq!(
+            stored_file_name(b"", "jpg"),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855.jpg"
+        );
+    }
+
+    #[test]
+    fn test_file_references() {
+        let name = "references-test.jpg";
+        let message = ChatMessage::user_with_images(
+            "look".to_string(),
+            "a".to_string(),
+            vec![format!("{LOCAL_IMAGE_PREFIX}{name}")],
+        );
+
+        // Stored for a new message and added to the history
+        let mut pending = PendingFiles::default();
+        pending.hold(name);
+        reference_stored_files([&message]);
+        drop(pending);
+        assert_eq!(FILE_REFERENCES.lock().unwrap().get(name), Some(&1));
+
+        // Waiting again for another message while the first one is trimmed
+        let mut pending = PendingFiles::default();
+        pending.hold(name);
+        assert!(unreference_stored_files(std::slice::from_ref(&message)).is_empty());
+
+        // Never added, so nothing refers to it anymore
+        assert_eq!(
+            remove_references(std::mem::take(&mut pending.names)),
+            vec![name]
+        );
+        assert!(!FILE_REFERENCES.lock().unwrap().contains_key(name));
+    }
+}
@@ -7,6 +7,7 @@ use std::time::Duration;
 use tokio::sync::Mutex;
 
 use crate::utils::conversation::ChatMessage;
+use crate::utils::model_router::RoutingDecision;
 use crate::utils::openai_schema::ResponsesUsage;
 
 use super::msg_context::MsgContextInfo;
@@ -28,6 +29,20 @@ pub struct Logger {
     log_dir: String,
 }
 
+/// Everything recorded about a single OpenAI request
+pub struct ConversationLogEntry<'a> {
+    pub msg_ctx: &'a MsgContextInfo,
+    /// Model that actually answered
+    pub model: &'a str,
+    pub messages: &'a [ChatMessage],
+    pub response: &'a str,
+    pub duration: Duration,
+    pub token_usage: ResponsesUsage,
+    pub cost_usd: Option<f64>,
+    /// Model tier picked by the router, if routing is on in the channel
+    pub routing: Option<&'a RoutingDecision>,
+}
+
 impl Logger {
     pub fn new(log_dir: &str) -> Self {
         // Ensure logs directory exists
@@ -41,14 +56,18 @@ impl Logger {
     }
 
     // Log OpenAI request and response
-    pub fn log_openai_conversation(
-        &self,
-        msg_ctx: &MsgContextInfo,
-        messages: &[ChatMessage],
-        response: &str,
-        duration: Duration,
-        token_usage: ResponsesUsage,
-    ) -> std::io::Result<()> {
+    pub fn log_openai_conversation(&self, entry: &ConversationLogEntry) -> std::io::Result<()> {
+        let ConversationLogEntry {
+            msg_ctx,
+            model,
+            messages,
+            response,
+            duration,
+            token_usage,
+            cost_usd,
+            routing,
+        } = entry;
+
         // Create KST timezone (UTC+9)
         let kst = FixedOffset::east_opt(9 * 3600).unwrap();
 
@@ -80,6 +99,10 @@ impl Logger {
             writeln!(file, "Guild Name: {guild_name}")?;
         }
         writeln!(file, "Timestamp: {timestamp}")?;
+        writeln!(file, "Model: {model}")?;
+        if let Some(routing) = routing {
+            writeln!(file, "Routing: {routing}")?;
+        }
         writeln!(file, "API Call Duration: {duration:.2?}")?;
 
         writeln!(
@@ -87,6 +110,10 @@ impl Logger {
             "Token Usage: Input: {}, Output: {}, Total: {}",
             token_usage.input_tokens, token_usage.output_tokens, token_usage.total_tokens
         )?;
+        match cost_usd {
+            Some(cost) => writeln!(file, "Cost: ${cost:.6}")?,
+            None => writeln!(file, "Cost: unknown (no pricing for {model})")?,
+        }
 
         writeln!(
             file,
@@ -95,7 +122,7 @@ impl Logger {
 
         // Write request messages
         writeln!(file, "\n[REQUEST]")?;
-        for message in messages {
+        for message in messages.iter() {
             writeln!(file, "{message}")?;
         }
 
@@ -110,13 +137,7 @@ impl Logger {
 // Helper functions to interact with the global logger
 
 /// Log an OpenAI conversation (request and response)
-pub async fn log_openai_conversation(
-    msg_ctx: &MsgContextInfo,
-    messages: &[ChatMessage],
-    response: &str,
-    duration: Duration,
-    token_usage: ResponsesUsage,
-) -> std::io::Result<()> {
+pub async fn log_openai_conversation(entry: &ConversationLogEntry<'_>) -> std::io::Result<()> {
     let logger = LOGGER.lock().await;
-    logger.log_openai_conversation(msg_ctx, messages, response, duration, token_usage)
+    logger.log_openai_conversation(entry)
 }
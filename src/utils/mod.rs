@@ -1,9 +1,28 @@
 pub mod admin_commands;
+pub mod attachments;
+pub mod backend_error;
+pub mod budget;
+pub mod circuit_breaker;
 pub mod conversation;
+pub mod directives;
 pub mod discord;
+pub mod image_gen;
+pub mod image_store;
 pub mod logger;
+pub mod model_router;
+pub mod moderation;
 pub mod msg_context;
 pub mod openai;
 pub mod openai_schema;
 pub mod persistence;
+pub mod pricing;
+pub mod rate_limit;
+pub mod request_queue;
+pub mod slash_commands;
 pub mod statics;
+#[cfg(test)]
+mod test_server;
+pub mod token_count;
+pub mod tools;
+pub mod transcription;
+pub mod usage;
@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use crate::utils::conversation::ChatMessage;
+use crate::utils::openai_schema::ContentItem;
+
+/// Light model used when routing is turned on without naming one
+pub const DEFAULT_LIGHT_MODEL: &str = "gpt-5-nano";
+
+// Longer messages always go to the default model
+const MAX_LIGHT_CHARS: usize = 120;
+const MAX_LIGHT_LINES: usize = 3;
+
+// Words hinting at a question that needs some thought
+const COMPLEX_KEYWORDS: &[&str] = &[
+    "왜",
+    "어떻게",
+    "설명",
+    "코드",
+    "에러",
+    "오류",
+    "버그",
+    "차이",
+    "계산",
+    "how",
+    "why",
+    "explain",
+    "error",
+    "code",
+    "debug",
+];
+
+/// Routing settings of a channel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChannelRouting {
+    /// Model answering simple messages, everything else goes to the current model
+    pub light_model: String,
+}
+
+/// Model tier picked for a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum ModelTier {
+    /// Cheap model for small talk
+    Light,
+    /// Current model with its fallbacks
+    Default,
+}
+
+/// Tier picked for a request and why, recorded in the conversation log
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutingDecision {
+    pub tier: ModelTier,
+    pub reason: &'static str,
+}
+
+impl Display for RoutingDecision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.tier, self.reason)
+    }
+}
+
+impl RoutingDecision {
+    fn new(tier: ModelTier, reason: &'static str) -> Self {
+        Self { tier, reason }
+    }
+}
+
+/// Messages of the history the next reply answers, i.e. the ones after the last reply
+pub fn unanswered_messages(history: &[ChatMessage]) -> &[ChatMessage] {
+    let start = history
+        .iter()
+        .rposition(|message| message.role == "assistant")
+        .map_or(0, |index| index + 1);
+    &history[start..]
+}
+
+/// Text of a user message without the "(name) " prefix, so names don't affect routing
+fn strip_author(text: &str) -> &str {
+    text.strip_prefix('(')
+        .and_then(|rest| rest.split_once(") "))
+        .map_or(text, |(_, content)| content)
+}
+
+/// Pick the tier of a request from the user messages it answers
+pub fn classify_messages(messages: &[ChatMessage]) -> RoutingDecision {
+    let user_messages = messages.iter().filter(|message| message.role == "user");
+    let mut text = String::new();
+
+    for item in user_messages.flat_map(|message| &message.content) {
+        match item {
+            ContentItem::InputImage { .. } | ContentItem::InputFile { .. } => {
+                return RoutingDecision::new(ModelTier::Default, "attachment");
+            }
+            // Text files are inlined as separate text items
+            ContentItem::InputText { text: item } if item.starts_with("[File: ") => {
+                return RoutingDecision::new(ModelTier::Default, "attachment");
+            }
+            ContentItem::InputText { text: item } => {
+                text.push_str(strip_author(item));
+                text.push('\n');
+            }
+            _ => {}
+        }
+    }
+
+    let text = text.trim();
+    let lowercase = text.to_lowercase();
+    if text.is_empty() {
+        RoutingDecision::new(ModelTier::Default, "no text")
+    } else if text.contains("```") {
+        RoutingDecision::new(ModelTier::Default, "code block")
+    } else if text.chars().count() > MAX_LIGHT_CHARS || text.lines().count() > MAX_LIGHT_LINES {
+        RoutingDecision::new(ModelTier::Default, "long message")
+    } else if COMPLEX_KEYWORDS
+        .iter()
+        .any(|keyword| lowercase.contains(keyword))
+    {
+        RoutingDecision::new(ModelTier::Default, "complex question")
+    } else {
+        RoutingDecision::new(ModelTier::Light, "short message")
+    }
+}
+
+/// Put the light model in front of the candidates if the decision calls for it
+pub fn route_candidates(
+    decision: &RoutingDecision,
+    routing: &ChannelRouting,
+    mut candidates: Vec<String>,
+) -> Vec<String> {
+    if decision.tier == ModelTier::Light {
+        candidates.retain(|model| *model != routing.light_model);
+        candidates.insert(0, routing.light_model.clone());
+    }
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(text: &str) -> ChatMessage {
+        ChatMessage::user(text.to_string(), "a".to_string())
+    }
+
+    #[test]
+    fn test_classify_messages() {
+        assert_eq!(classify_messages(&[user("ㅋㅋㅋ")]).tier, ModelTier::Light);
+        let from_howard = ChatMessage::user("ㅎㅇ".to_string(), "howard".to_string());
+        assert_eq!(classify_messages(&[from_howard]).tier, ModelTier::Light);
+        assert_eq!(
+            classify_messages(&[user("안녕"), user("뭐해")]).tier,
+            ModelTier::Light
+        );
+
+        let reason = |messages: &[ChatMessage]| classify_messages(messages).reason;
+        assert_eq!(reason(&[user("```rust\nfn main() {}\n```")]), "code block");
+        assert_eq!(reason(&[user(&"긴 메시지 ".repeat(30))]), "long message");
+        assert_eq!(reason(&[user("이거 왜 안 돼?")]), "complex question");
+        assert_eq!(reason(&[user("How does this work")]), "complex question");
+        assert_eq!(
+            reason(&[user("봐봐").with_images(vec!["https://a.png".to_string()])]),
+            "attachment"
+        );
+        assert_eq!(
+            reason(&[user("봐봐").with_text_attachment("[File: a.txt]\nhi".to_string())]),
+            "attachment"
+        );
+    }
+
+    #[test]
+    fn test_unanswered_messages() {
+        let history = vec![
+            ChatMessage::developer("system".to_string()),
+            user("first"),
+            ChatMessage::assistant("reply".to_string()),
+            user("second"),
+            user("third"),
+        ];
+        let unanswered = unanswered_messages(&history);
+        assert_eq!(unanswered.len(), 2);
+        assert_eq!(unanswered[0].to_string(), history[3].to_string());
+
+        assert_eq!(unanswered_messages(&history[..3]).len(), 0);
+    }
+
+    #[test]
+    fn test_route_candidates() {
+        let routing = ChannelRouting {
+            light_model: "gpt-5-mini".to_string(),
+        };
+        let candidates = vec!["gpt-5".to_string(), "gpt-5-mini".to_string()];
+
+        let light = RoutingDecision::new(ModelTier::Light, "short message");
+        assert_eq!(
+            route_candidates(&light, &routing, candidates.clone()),
+            vec!["gpt-5-mini", "gpt-5"]
+        );
+
+        let default = RoutingDecision::new(ModelTier::Default, "code block");
+        assert_eq!(
+            route_candidates(&default, &routing, candidates.clone()),
+            candidates
+        );
+    }
+}
@@ -0,0 +1,364 @@
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serenity::model::id::GuildId;
+use serenity::prelude::Context;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use strum_macros::EnumString;
+
+use crate::utils::circuit_breaker;
+use crate::utils::discord;
+use crate::utils::msg_context::MsgContextInfo;
+use crate::utils::openai_schema::OpenAiApiError;
+use crate::utils::persistence::{get_guild_moderation, get_moderation_words};
+use crate::utils::statics::{OPENAI_BASE_URL, OPENAI_TOKEN};
+
+const MODERATION_MODEL: &str = "omni-moderation-latest";
+const DEFAULT_THRESHOLD: f64 = 0.5;
+// Length of the flagged text quoted in the report to the developer
+const MAX_REPORTED_CHARS: usize = 1000;
+
+/// What decides whether content is flagged
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumString, strum_macros::Display,
+)]
+#[strum(ascii_case_insensitive)]
+pub enum ModerationBackend {
+    /// OpenAI moderation endpoint, scored per category
+    #[strum(serialize = "openai")]
+    OpenAi,
+    /// Configured list of words, any match scores 1.0
+    #[strum(serialize = "wordlist")]
+    WordList,
+}
+
+/// What happens to flagged content
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, EnumString, strum_macros::Display,
+)]
+#[strum(serialize_all = "lowercase", ascii_case_insensitive)]
+pub enum ModerationAction {
+    /// The message is not answered, or the reply is not sent
+    Block,
+    /// A warning is posted, but the message is still handled
+    Warn,
+    /// Only the developer is told
+    Log,
+}
+
+impl ModerationAction {
+    /// The stricter of two outcomes, e.g. for a reply checked in several parts
+    pub fn strictest(a: Option<Self>, b: Option<Self>) -> Option<Self> {
+        [Self::Block, Self::Warn, Self::Log]
+            .into_iter()
+            .find(|action| a == Some(*action) || b == Some(*action))
+    }
+}
+
+/// Moderation settings of a guild
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GuildModeration {
+    pub backend: ModerationBackend,
+    /// Score from which content is flagged (0.0 to 1.0)
+    pub threshold: f64,
+    pub action: ModerationAction,
+}
+
+impl Display for GuildModeration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (threshold {:.2}, action {})",
+            self.backend, self.threshold, self.action
+        )
+    }
+}
+
+impl GuildModeration {
+    /// Parse "<backend> [threshold] [block|warn|log]"
+    pub fn from_args(args: &[&str]) -> eyre::Result<Self> {
+        let (backend, rest) = args
+            .split_first()
+            .ok_or_else(|| eyre::eyre!("Specify a backend: openai or wordlist"))?;
+        let backend = ModerationBackend::from_str(backend)
+            .map_err(|_| eyre::eyre!("Unknown backend: {backend} (openai or wordlist)"))?;
+
+        let mut settings = Self {
+            backend,
+            threshold: DEFAULT_THRESHOLD,
+            action: ModerationAction::Block,
+        };
+        for arg in rest {
+            if let Ok(action) = ModerationAction::from_str(arg) {
+                settings.action = action;
+            } else if let Ok(threshold) = arg.parse::<f64>() {
+                if !(threshold > 0.0 && threshold <= 1.0) {
+                    return Err(eyre::eyre!("Threshold must be between 0 and 1"));
+                }
+                settings.threshold = threshold;
+            } else {
+                return Err(eyre::eyre!("Unknown option: {arg}"));
+            }
+        }
+
+        Ok(settings)
+    }
+}
+
+/// Persistent moderation configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModerationConfig {
+    /// Guilds with moderation enabled, it is off everywhere else
+    #[serde(default)]
+    pub guilds: HashMap<GuildId, GuildModeration>,
+    /// Words flagged by the word list backend
+    #[serde(default)]
+    pub words: Vec<String>,
+}
+
+/// Whether content is written by a user or by the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::Display)]
+pub enum ContentDirection {
+    #[strum(serialize = "user message")]
+    Input,
+    #[strum(serialize = "bot reply")]
+    Output,
+}
+
+/// Highest scoring category of checked content
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModerationScore {
+    pub category: String,
+    pub score: f64,
+}
+
+/// Response body of the moderation endpoint
+#[derive(Debug, Deserialize)]
+struct ModerationResponse {
+    results: Vec<ModerationResultBody>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModerationResultBody {
+    category_scores: HashMap<String, f64>,
+}
+
+/// Check text against a word list, ignoring case
+pub fn check_word_list(text: &str, words: &[String]) -> Option<ModerationScore> {
+    let text = text.to_lowercase();
+    words
+        .iter()
+        .find(|word| !word.is_empty() && text.contains(&word.to_lowercase()))
+        .map(|word| ModerationScore {
+            category: format!("word list ({word})"),
+            score: 1.0,
+        })
+}
+
+/// Check text with the OpenAI moderation endpoint at the given base URL
+pub async fn check_openai(
+    client: &Client,
+    base_url: &str,
+    token: &str,
+    text: &str,
+) -> eyre::Result<Option<ModerationScore>> {
+    let response = client
+        .post(format!("{base_url}/moderations"))
+        .header("Authorization", format!("Bearer {token}"))
+        .json(&serde_json::json!({
+            "model": MODERATION_MODEL,
+            "input": text,
+        }))
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await?;
+        return Err(OpenAiApiError::from_response_body(status.as_u16(), &error_text).into());
+    }
+
+    let response: ModerationResponse = response.json().await?;
+    let highest = response
+        .results
+        .into_iter()
+        .flat_map(|result| result.category_scores)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(category, score)| ModerationScore { category, score });
+
+    Ok(highest)
+}
+
+/// Check content written in a guild with moderation enabled
+/// Returns the action to take if it is flagged, the developer is told about every flag
+pub async fn moderate(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    direction: ContentDirection,
+    text: &str,
+) -> Option<ModerationAction> {
+    let guild_id = msg_ctx.guild_id?;
+    let settings = get_guild_moderation(guild_id).await?;
+    if text.trim().is_empty() {
+        return None;
+    }
+
+    let score = match settings.backend {
+        ModerationBackend::WordList => check_word_list(text, &get_moderation_words().await),
+        ModerationBackend::OpenAi => {
+            let result = match circuit_breaker::acquire(ctx).await {
+                Ok(()) => {
+                    let result =
+                        check_openai(&Client::new(), &OPENAI_BASE_URL, &OPENAI_TOKEN, text).await;
+                    circuit_breaker::record_outcome(ctx, &result).await;
+                    result
+                }
+                Err(e) => Err(e.into()),
+            };
+            match result {
+                Ok(score) => score,
+                Err(e) => {
+                    // Don't stop the bot when the moderation endpoint is down
+                    tracing::warn!("Moderation check failed, letting the {direction} through: {e}");
+                    None
+                }
+            }
+        }
+    }?;
+    if score.score < settings.threshold {
+        return None;
+    }
+
+    tracing::warn!(
+        "Flagged {direction} by {} in channel {}: {} ({:.2}), action {}",
+        msg_ctx.author.name,
+        msg_ctx.channel_id,
+        score.category,
+        score.score,
+        settings.action
+    );
+    report_to_developer(ctx, msg_ctx, direction, text, &score, settings.action).await;
+
+    Some(settings.action)
+}
+
+/// Tell the developer about flagged content
+async fn report_to_developer(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    direction: ContentDirection,
+    text: &str,
+    score: &ModerationScore,
+    action: ModerationAction,
+) {
+    let excerpt: String = text.chars().take(MAX_REPORTED_CHARS).collect();
+    let report = format!(
+        "Moderation: flagged {direction} ({}, score {:.2}, action {action})\nGuild: {}\nChannel: {}\nAuthor: {}\n{}",
+        score.category,
+        score.score,
+        msg_ctx.guild_name.as_deref().unwrap_or("-"),
+        msg_ctx.channel_name.as_deref().unwrap_or("-"),
+        msg_ctx.author.name,
+        discord::quote(&excerpt)
+    );
+    if let Err(e) = discord::send_dm_to_dev(ctx, &report).await {
+        tracing::error!("Failed to send moderation report: {:?}", e);
+    }
+}
+
+/// In-character notice posted when content is blocked or warned about
+pub fn moderation_notice(direction: ContentDirection, action: ModerationAction) -> &'static str {
+    match (direction, action) {
+        (ContentDirection::Input, ModerationAction::Block) => {
+            "으음… 그 얘기는 대답하기 어려워. 다른 얘기 하자!"
+        }
+        (ContentDirection::Input, _) => "⚠️ 방금 메시지는 조금 조심해줘!",
+        (ContentDirection::Output, ModerationAction::Block) => {
+            "앗, 하려던 대답이 좀 부적절한 것 같아서 말 안 할래. 미안!"
+        }
+        (ContentDirection::Output, _) => "⚠️ 아래 대답은 부적절할 수도 있어서 가려뒀어.",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_server::StandInServer;
+
+    #[test]
+    fn test_strictest_action() {
+        use ModerationAction::*;
+        assert_eq!(
+            ModerationAction::strictest(Some(Log), Some(Block)),
+            Some(Block)
+        );
+        assert_eq!(ModerationAction::strictest(Some(Warn), None), Some(Warn));
+        assert_eq!(ModerationAction::strictest(None, None), None);
+    }
+
+    #[test]
+    fn test_parse_guild_moderation() {
+        assert_eq!(
+            GuildModeration::from_args(&["openai"]).unwrap(),
+            GuildModeration {
+                backend: ModerationBackend::OpenAi,
+                threshold: DEFAULT_THRESHOLD,
+                action: ModerationAction::Block,
+            }
+        );
+        assert_eq!(
+            GuildModeration::from_args(&["WordList", "warn", "0.9"]).unwrap(),
+            GuildModeration {
+                backend: ModerationBackend::WordList,
+                threshold: 0.9,
+                action: ModerationAction::Warn,
+            }
+        );
+        assert!(GuildModeration::from_args(&[]).is_err());
+        assert!(GuildModeration::from_args(&["regex"]).is_err());
+        assert!(GuildModeration::from_args(&["openai", "1.5"]).is_err());
+        assert!(GuildModeration::from_args(&["openai", "ban"]).is_err());
+    }
+
+    #[test]
+    fn test_check_word_list() {
+        let words = vec!["Spam".to_string(), String::new()];
+        assert_eq!(
+            check_word_list("buy SPAM now", &words),
+            Some(ModerationScore {
+                category: "word list (Spam)".to_string(),
+                score: 1.0,
+            })
+        );
+        assert_eq!(check_word_list("hello", &words), None);
+    }
+
+    #[tokio::test]
+    async fn test_check_openai_with_stand_in_server() {
+        let body = r#"{"id": "modr-1", "model": "omni-moderation-latest", "results": [{"flagged": true, "categories": {"harassment": true, "violence": false}, "category_scores": {"harassment": 0.92, "violence": 0.01}}]}"#;
+        let server = StandInServer::start(200, "application/json", body).await;
+
+        let score = check_openai(
+            &Client::new(),
+            &server.base_url,
+            "test-token",
+            "you are dumb",
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            score,
+            Some(ModerationScore {
+                category: "harassment".to_string(),
+                score: 0.92,
+            })
+        );
+
+        let request = server.request().await;
+        assert_eq!(request.request_line, "POST /moderations HTTP/1.1");
+        let sent: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(sent["input"], "you are dumb");
+        assert_eq!(sent["model"], MODERATION_MODEL);
+    }
+}
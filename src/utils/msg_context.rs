@@ -1,5 +1,6 @@
+use serenity::all::CommandInteraction;
 use serenity::model::channel::Message;
-use serenity::model::id::{ChannelId, GuildId, UserId};
+use serenity::model::id::{ChannelId, GuildId, MessageId, UserId};
 use serenity::model::user::User;
 use serenity::prelude::Context;
 
@@ -12,13 +13,24 @@ pub struct MsgContextInfo {
     pub guild_name: Option<String>,
     pub author_id: UserId,
     pub author: User,
+    /// The message to reply to, none for slash commands
+    pub message_id: Option<MessageId>,
 }
 
 impl MsgContextInfo {
     /// Create a new MsgContextInfo from a Message
     pub async fn from_message(ctx: &Context, msg: &Message) -> Self {
-        let channel_id = msg.channel_id;
-        let author = msg.author.clone();
+        let mut msg_ctx = Self::from_parts(ctx, msg.channel_id, msg.author.clone()).await;
+        msg_ctx.message_id = Some(msg.id);
+        msg_ctx
+    }
+
+    /// Create a new MsgContextInfo from a slash command
+    pub async fn from_interaction(ctx: &Context, interaction: &CommandInteraction) -> Self {
+        Self::from_parts(ctx, interaction.channel_id, interaction.user.clone()).await
+    }
+
+    async fn from_parts(ctx: &Context, channel_id: ChannelId, author: User) -> Self {
         let author_id = author.id;
 
         // Try to get channel and guild information
@@ -47,6 +59,49 @@ impl MsgContextInfo {
             guild_name,
             author_id,
             author,
+            message_id: None,
         }
     }
+
+    /// Context of a message by `user` in `channel`, for tests
+    #[cfg(test)]
+    pub(crate) fn for_test(user: u64, channel: u64, guild: Option<u64>) -> Self {
+        let mut author = User::default();
+        author.id = UserId::new(user);
+        author.name = format!("user{user}");
+
+        Self {
+            channel_id: ChannelId::new(channel),
+            channel_name: Some(format!("channel{channel}")),
+            guild_id: guild.map(GuildId::new),
+            guild_name: guild.map(|g| format!("guild{g}")),
+            author_id: author.id,
+            author,
+            message_id: None,
+        }
+    }
+
+    /// The message to reply to when answering in a channel
+    /// Only a message of the same channel can be replied to, e.g. not from the author's DMs
+    pub fn reply_to(&self, channel_id: ChannelId) -> Option<MessageId> {
+        self.message_id.filter(|_| channel_id == self.channel_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_to_same_channel_only() {
+        let mut msg_ctx = MsgContextInfo::for_test(1, 1, None);
+        msg_ctx.message_id = Some(MessageId::new(10));
+
+        assert_eq!(
+            msg_ctx.reply_to(ChannelId::new(1)),
+            Some(MessageId::new(10))
+        );
+        // e.g. a private answer in the author's DMs
+        assert_eq!(msg_ctx.reply_to(ChannelId::new(2)), None);
+    }
 }
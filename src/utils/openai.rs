@@ -1,49 +1,612 @@
 use reqwest::{Client, Response};
+use serenity::model::id::ChannelId;
+use serenity::prelude::Context;
 use std::time::Instant;
+use tokio::sync::Semaphore;
 
+use crate::utils::circuit_breaker;
 use crate::utils::conversation::ChatMessage;
-use crate::utils::logger::log_openai_conversation;
+use crate::utils::image_store::resolve_local_images;
+use crate::utils::logger::{ConversationLogEntry, log_openai_conversation};
+use crate::utils::model_router::{classify_messages, route_candidates, unanswered_messages};
+use crate::utils::moderation::{ContentDirection, ModerationAction, moderate};
 use crate::utils::msg_context::MsgContextInfo;
 use crate::utils::openai_schema::*;
-use crate::utils::persistence::{add_message, get_conversation_history};
-use crate::utils::statics::OPENAI_TOKEN;
+use crate::utils::persistence::{
+    add_message, append_to_last_assistant_message, get_auto_continue_rounds, get_chained_input,
+    get_channel_routing, get_conversation_history, get_disabled_tools, get_model_candidates,
+    invalidate_response_chain, is_reasoning_summary_enabled, is_response_chaining_enabled,
+    record_prompt_cache, record_usage, set_last_answered_model, set_response_chain,
+};
+use crate::utils::pricing::calculate_cost;
+use crate::utils::statics::{OPENAI_BASE_URL, OPENAI_TOKEN};
+use crate::utils::token_count::{fit_to_token_limit, input_token_limit};
+use crate::utils::tools::{BuiltinTool, ToolContext, enabled_tool_definitions};
+use crate::utils::usage::UsageTotals;
+
+// Maximum number of tool call rounds before giving up on a request
+const MAX_TOOL_ROUNDS: usize = 5;
+
+// Maximum number of OpenAI requests in flight across all channels
+const MAX_CONCURRENT_REQUESTS: usize = 4;
+
+static REQUEST_PERMITS: Semaphore = Semaphore::const_new(MAX_CONCURRENT_REQUESTS);
+
+// Messages left out at once when the history doesn't fit in the context, so the
+// prompt prefix stays the same for a while instead of changing every turn
+const CONTEXT_DROP_CHUNK: usize = 20;
+
+// Reason of incomplete responses that can be continued
+const CONTINUABLE_REASON: &str = "max_output_tokens";
+
+// Sent after the history to continue a reply that was cut off
+const CONTINUE_PROMPT: &str = "방금 네 마지막 메시지가 중간에 끊겼어. 앞부분을 반복하거나 다른 말을 덧붙이지 말고, 끊긴 지점부터 바로 이어서 써줘.";
+
+/// Reply of the bot to a conversation
+#[derive(Debug, Clone)]
+pub struct BotReply {
+    pub content: String,
+    /// Whether the model refused to answer, in which case the content is its refusal
+    pub refusal: bool,
+    /// Reason the reply was cut off, if it is incomplete
+    pub incomplete_reason: Option<String>,
+    /// Summary of the model's reasoning, if requested
+    pub reasoning_summary: Option<String>,
+    /// Outcome of the output moderation, a blocked reply is not in the history
+    pub moderation: Option<ModerationAction>,
+}
+
+impl BotReply {
+    /// Text to post on Discord, marking replies that were cut off
+    pub fn display_text(&self) -> String {
+        match &self.incomplete_reason {
+            Some(reason) => format!("{}…\n\n*[truncated: {reason}]*", self.content.trim_end()),
+            None => self.content.clone(),
+        }
+    }
+
+    /// Whether the reply was cut off by the output limit and can be continued
+    pub fn can_continue(&self) -> bool {
+        self.incomplete_reason.as_deref() == Some(CONTINUABLE_REASON)
+            && self.moderation != Some(ModerationAction::Block)
+    }
+
+    /// Merge the continuation of this reply into it
+    fn extend(&mut self, continuation: BotReply) {
+        self.content.push_str(&continuation.content);
+        self.refusal |= continuation.refusal;
+        self.incomplete_reason = continuation.incomplete_reason;
+        self.reasoning_summary = match (
+            self.reasoning_summary.take(),
+            continuation.reasoning_summary,
+        ) {
+            (Some(first), Some(next)) => Some(format!("{first}\n\n{next}")),
+            (first, next) => first.or(next),
+        };
+        self.moderation = ModerationAction::strictest(self.moderation, continuation.moderation);
+    }
+}
+
+/// Options shared by every request made for a single reply
+#[derive(Clone)]
+struct RequestOptions {
+    tools: Vec<FunctionTool>,
+    reasoning_summary: bool,
+    /// Continue the last assistant message instead of answering the conversation
+    continue_last: bool,
+    /// Messages sent after the history without being stored in it
+    transient_messages: Vec<ChatMessage>,
+}
+
+/// A tool call that already ran, e.g. posting an image
+/// A retry with another model or the full history gets its result instead of running it again
+#[derive(Debug, Clone)]
+struct CompletedToolCall {
+    call: FunctionCall,
+    output: String,
+}
+
+impl CompletedToolCall {
+    /// The call and its result as input items of a new request
+    fn input_items(&self) -> [InputItem; 2] {
+        [
+            InputItem::Raw(serde_json::json!({
+                "type": "function_call",
+                "call_id": self.call.call_id,
+                "name": self.call.name,
+                "arguments": self.call.arguments,
+            })),
+            InputItem::FunctionCallOutput(FunctionCallOutput::new(
+                self.call.call_id.clone(),
+                self.output.clone(),
+            )),
+        ]
+    }
+}
+
+/// Changes to a single reply asked for in the message, e.g. with directives
+#[derive(Debug, Clone, Default)]
+pub struct ReplyOptions {
+    /// Model answering instead of the current model and its fallbacks
+    pub model: Option<String>,
+    /// Messages to answer without storing them in the history, the reply isn't stored either
+    pub transient_messages: Option<Vec<ChatMessage>>,
+}
+
+/// Text answer of the model along with the metadata of the final response
+struct OpenAiReply {
+    text: ResponseText,
+    reasoning_summary: Vec<String>,
+    usage: ResponsesUsage,
+    response_id: String,
+    model: String,
+}
+
+/// Text extracted from the messages of a response
+#[derive(Debug, Default, PartialEq)]
+struct ResponseText {
+    content: String,
+    refusal: bool,
+    incomplete_reason: Option<String>,
+}
 
 /// Get a response from OpenAI for the conversation in the specified channel
-pub async fn get_openai_response(msg_ctx: &MsgContextInfo) -> eyre::Result<String> {
-    // Get conversation history for this channel
+pub async fn get_openai_response(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    reply_options: ReplyOptions,
+) -> eyre::Result<BotReply> {
+    let reply = request_reply(ctx, msg_ctx, false, &reply_options).await?;
+
+    // A reply that isn't in the history can't be continued
+    if reply_options.transient_messages.is_some() {
+        return Ok(reply);
+    }
+    continue_while_cut_off(ctx, msg_ctx, reply, reply_options.model).await
+}
+
+/// Continue the last reply in the channel where it was cut off
+/// The continuation is merged into the last assistant message of the history
+pub async fn continue_openai_response(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+) -> eyre::Result<BotReply> {
     let history = get_conversation_history(msg_ctx.channel_id).await;
+    if history
+        .last()
+        .is_none_or(|message| message.role != "assistant")
+    {
+        return Err(eyre::eyre!("There is no reply to continue"));
+    }
+
+    let reply = request_reply(ctx, msg_ctx, true, &ReplyOptions::default()).await?;
+    continue_while_cut_off(ctx, msg_ctx, reply, None).await
+}
+
+/// Continue a reply cut off by the output limit, up to the configured number of rounds
+/// The continuation is written by the `model` chosen for the reply, if any
+async fn continue_while_cut_off(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    mut reply: BotReply,
+    model: Option<String>,
+) -> eyre::Result<BotReply> {
+    let max_rounds = get_auto_continue_rounds().await;
+    let reply_options = ReplyOptions {
+        model,
+        transient_messages: None,
+    };
+
+    for round in 1..=max_rounds {
+        if !reply.can_continue() {
+            break;
+        }
+
+        tracing::info!("Reply was cut off, continuing it ({round}/{max_rounds})");
+        match request_reply(ctx, msg_ctx, true, &reply_options).await {
+            Ok(continuation) => reply.extend(continuation),
+            Err(e) => {
+                // Keep what we have, it can still be continued later
+                tracing::warn!("Failed to continue reply: {e:?}");
+                break;
+            }
+        }
+    }
+
+    Ok(reply)
+}
+
+/// Request a single reply and record it in the history, usage and logs
+async fn request_reply(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    continue_last: bool,
+    reply_options: &ReplyOptions,
+) -> eyre::Result<BotReply> {
+    let channel_id = msg_ctx.channel_id;
+    let transient = reply_options.transient_messages.is_some();
+
+    // Get the tools enabled for this channel
+    let options = RequestOptions {
+        tools: enabled_tool_definitions(&get_disabled_tools(channel_id).await),
+        reasoning_summary: is_reasoning_summary_enabled().await,
+        continue_last,
+        transient_messages: reply_options.transient_messages.clone().unwrap_or_default(),
+    };
+    let tool_ctx = ToolContext {
+        ctx,
+        msg_ctx,
+        transient,
+    };
+
+    // Pick the model tier if routing is on, a continuation stays with the usual models
+    let mut candidates = match &reply_options.model {
+        Some(model) => vec![model.clone()],
+        None => get_model_candidates().await,
+    };
+    let mut routing_decision = None;
+    let routing = if continue_last || reply_options.model.is_some() {
+        None
+    } else {
+        get_channel_routing(channel_id).await
+    };
+    if let Some(routing) = routing {
+        let decision = match &reply_options.transient_messages {
+            Some(messages) => classify_messages(messages),
+            None => classify_messages(unanswered_messages(
+                &get_conversation_history(channel_id).await,
+            )),
+        };
+        tracing::info!("Routing request in channel {channel_id}: {decision}");
+        candidates = route_candidates(&decision, &routing, candidates);
+        routing_decision = Some(decision);
+    }
 
     // Create and send the request to OpenAI, measuring the time it takes
     let start_time = Instant::now();
-    let (response_content, token_usage) = send_responses_api_request(history.clone()).await?;
+    let (reply, sent_messages) =
+        request_with_fallback(candidates, channel_id, options, &tool_ctx).await?;
     let duration = start_time.elapsed();
 
+    if reply.text.refusal {
+        tracing::warn!("Model {} refused to answer", reply.model);
+    }
+    if let Some(reason) = &reply.text.incomplete_reason {
+        tracing::warn!("Response of {} is incomplete: {reason}", reply.model);
+    }
+
+    // Calculate the cost of the response
+    let cost_usd = calculate_cost(&reply.model, &reply.usage);
+    if cost_usd.is_none() {
+        tracing::warn!(
+            "No pricing known for model {}, cost not tracked",
+            reply.model
+        );
+    }
+    record_usage(
+        msg_ctx,
+        UsageTotals::from_response(&reply.usage, cost_usd.unwrap_or_default()),
+    )
+    .await;
+    record_prompt_cache(channel_id, &reply.usage).await;
+
     // Log the conversation (request and response)
-    if let Err(e) =
-        log_openai_conversation(msg_ctx, &history, &response_content, duration, token_usage).await
-    {
+    let log_entry = ConversationLogEntry {
+        msg_ctx,
+        model: &reply.model,
+        messages: &sent_messages,
+        response: &reply.text.content,
+        duration,
+        token_usage: reply.usage,
+        cost_usd,
+        routing: routing_decision.as_ref(),
+    };
+    if let Err(e) = log_openai_conversation(&log_entry).await {
         tracing::error!("Failed to log OpenAI conversation: {e}");
     }
 
+    // Check the reply before it is stored, a blocked reply must not be built upon
+    let moderation = moderate(ctx, msg_ctx, ContentDirection::Output, &reply.text.content).await;
+    let blocked = moderation == Some(ModerationAction::Block);
+
     // Store the assistant's response in the conversation history
-    let message = ChatMessage::assistant(response_content.clone());
-    add_message(msg_ctx.channel_id, message).await;
+    if transient {
+        tracing::info!("Reply in channel {channel_id} is kept out of the history");
+    } else if blocked {
+        tracing::info!("Blocked reply in channel {channel_id} is kept out of the history");
+    } else if continue_last {
+        if !append_to_last_assistant_message(channel_id, &reply.text.content).await {
+            add_message(
+                channel_id,
+                ChatMessage::assistant(reply.text.content.clone()),
+            )
+            .await;
+        }
+    } else {
+        add_message(
+            channel_id,
+            ChatMessage::assistant(reply.text.content.clone()),
+        )
+        .await;
+    }
+    set_last_answered_model(reply.model.clone()).await;
 
-    Ok(response_content)
+    // Remember the response so the next request can continue from it
+    // A continuation isn't stored as it is, so it can't be continued from
+    if is_response_chaining_enabled().await && !continue_last && !transient && !blocked {
+        set_response_chain(channel_id, reply.response_id, reply.model).await;
+    }
+
+    let reasoning_summary =
+        (!reply.reasoning_summary.is_empty()).then(|| reply.reasoning_summary.join("\n\n"));
+
+    Ok(BotReply {
+        content: reply.text.content,
+        refusal: reply.text.refusal,
+        incomplete_reason: reply.text.incomplete_reason,
+        reasoning_summary,
+        moderation,
+    })
 }
 
-/// Send a request to the OpenAI Responses API
-async fn send_responses_api_request(
+/// Try the candidate models in order until one of them answers
+/// Returns the reply along with the messages that were sent
+async fn request_with_fallback(
+    candidates: Vec<String>,
+    channel_id: ChannelId,
+    options: RequestOptions,
+    tool_ctx: &ToolContext<'_>,
+) -> eyre::Result<(OpenAiReply, Vec<ChatMessage>)> {
+    let mut last_error = None;
+    let mut completed_calls = Vec::new();
+
+    for model in candidates {
+        match request_for_model(
+            &model,
+            channel_id,
+            options.clone(),
+            tool_ctx,
+            &mut completed_calls,
+        )
+        .await
+        {
+            Ok(result) => return Ok(result),
+            Err(e) if api_error(&e).is_some_and(OpenAiApiError::is_model_error) => {
+                tracing::warn!("Model {model} failed, trying the next fallback model: {e}");
+                last_error = Some(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| eyre::eyre!("No model configured")))
+}
+
+/// Request a response from a single model, continuing from the last response if possible
+async fn request_for_model(
+    model: &str,
+    channel_id: ChannelId,
+    options: RequestOptions,
+    tool_ctx: &ToolContext<'_>,
+    completed_calls: &mut Vec<CompletedToolCall>,
+) -> eyre::Result<(OpenAiReply, Vec<ChatMessage>)> {
+    // Ask to continue the last message, sending the whole history
+    if options.continue_last {
+        let mut history = get_conversation_history(channel_id).await;
+        history.push(ChatMessage::developer(CONTINUE_PROMPT.to_string()));
+        let history = fit_to_context(model, history);
+        let reply = request_with_tools(
+            model,
+            history.clone(),
+            None,
+            options,
+            tool_ctx,
+            completed_calls,
+        )
+        .await?;
+        return Ok((reply, history));
+    }
+
+    // Continue from the last response, sending only the new messages
+    // Messages kept out of the history are sent with the full history instead
+    let chained_input = if options.transient_messages.is_empty() {
+        get_chained_input(channel_id, model).await
+    } else {
+        None
+    };
+    if let Some((previous_response_id, new_messages)) = chained_input {
+        match request_with_tools(
+            model,
+            new_messages.clone(),
+            Some(previous_response_id),
+            options.clone(),
+            tool_ctx,
+            completed_calls,
+        )
+        .await
+        {
+            Ok(reply) => return Ok((reply, new_messages)),
+            Err(e)
+                if api_error(&e).is_some_and(|error| {
+                    error.is_previous_response_error() || error.is_context_length_error()
+                }) =>
+            {
+                tracing::warn!("Previous response is no longer usable, sending full history: {e}");
+                invalidate_response_chain(channel_id).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut history = get_conversation_history(channel_id).await;
+    history.extend(options.transient_messages.iter().cloned());
+    let history = fit_to_context(model, history);
+    let reply = request_with_tools(
+        model,
+        history.clone(),
+        None,
+        options,
+        tool_ctx,
+        completed_calls,
+    )
+    .await?;
+    Ok((reply, history))
+}
+
+/// Leave out the oldest messages of the history if it wouldn't fit in the model's context
+fn fit_to_context(model: &str, history: Vec<ChatMessage>) -> Vec<ChatMessage> {
+    let (history, dropped) =
+        fit_to_token_limit(history, input_token_limit(model), CONTEXT_DROP_CHUNK);
+    if dropped > 0 {
+        tracing::warn!(
+            "History is too large for {model}, leaving out the {dropped} oldest messages"
+        );
+    }
+    history
+}
+
+/// Prompt cache key of the requests made for a channel
+fn prompt_cache_key(channel_id: ChannelId) -> String {
+    format!("mintybot-channel-{channel_id}")
+}
+
+/// Get the OpenAI API error behind a failed request, if any
+fn api_error(error: &eyre::Report) -> Option<&OpenAiApiError> {
+    error.downcast_ref::<OpenAiApiError>()
+}
+
+/// Send the conversation to OpenAI, executing the tool calls requested by the model
+/// and sending their results back until it answers with text
+/// The calls of earlier attempts are sent along, and the calls that ran are added to them
+async fn request_with_tools(
+    model: &str,
     messages: Vec<ChatMessage>,
-) -> eyre::Result<(String, ResponsesUsage)> {
+    previous_response_id: Option<String>,
+    options: RequestOptions,
+    tool_ctx: &ToolContext<'_>,
+    completed_calls: &mut Vec<CompletedToolCall>,
+) -> eyre::Result<OpenAiReply> {
     let client = Client::new();
-    let request = ResponsesRequest::new(messages).await;
+    // Images stored in the data directory are sent inline
+    let messages = resolve_local_images(messages).await;
+    // Requests of a channel share their prefix (system prompt, tools and older history)
+    let mut request = ResponsesRequest::new(model.to_string(), messages)
+        .with_tools(options.tools)
+        .with_prompt_cache_key(prompt_cache_key(tool_ctx.msg_ctx.channel_id));
+    if let Some(response_id) = previous_response_id {
+        request = request.with_previous_response(response_id);
+    }
+    if options.reasoning_summary {
+        request = request.with_reasoning_summary();
+    }
+    // Tool calls of a failed attempt already ran, e.g. an image was posted and paid for
+    request.extend_input(
+        completed_calls
+            .iter()
+            .flat_map(CompletedToolCall::input_items),
+    );
+    let mut total_usage: Option<ResponsesUsage> = None;
+    let mut reasoning_summary = Vec::new();
+
+    for _ in 0..=MAX_TOOL_ROUNDS {
+        let (response_data, raw_output) =
+            send_with_breaker(tool_ctx.ctx, &client, &request).await?;
+
+        // Accumulate token usage over all rounds
+        match total_usage.as_mut() {
+            Some(usage) => *usage += response_data.usage,
+            None => total_usage = Some(response_data.usage),
+        }
+        reasoning_summary.extend(extract_reasoning_summary(&response_data));
+
+        let function_calls: Vec<&FunctionCall> = response_data
+            .output
+            .iter()
+            .filter_map(|item| match item {
+                OutputItem::FunctionCall(call) => Some(call),
+                _ => None,
+            })
+            .collect();
+
+        if function_calls.is_empty() {
+            return Ok(OpenAiReply {
+                text: extract_text_response(&response_data)?,
+                reasoning_summary,
+                usage: total_usage.unwrap_or(response_data.usage),
+                response_id: response_data.id,
+                model: request.model().to_string(),
+            });
+        }
+
+        // Pass the model's output back along with the result of each tool call
+        let mut tool_outputs = Vec::new();
+        for call in function_calls {
+            let output = run_tool_call(call, tool_ctx).await;
+            completed_calls.push(CompletedToolCall {
+                call: call.clone(),
+                output: output.clone(),
+            });
+            tool_outputs.push(InputItem::FunctionCallOutput(FunctionCallOutput::new(
+                call.call_id.clone(),
+                output,
+            )));
+        }
+        request.extend_input(raw_output.into_iter().map(InputItem::Raw));
+        request.extend_input(tool_outputs);
+    }
+
+    Err(eyre::eyre!(
+        "OpenAI requested too many tool calls (max {MAX_TOOL_ROUNDS} rounds)"
+    ))
+}
+
+/// Execute a tool call requested by the model and return its output
+async fn run_tool_call(call: &FunctionCall, tool_ctx: &ToolContext<'_>) -> String {
+    let output = match BuiltinTool::from_function_name(&call.name) {
+        // The model may still call a tool disabled after it was offered
+        Some(tool)
+            if get_disabled_tools(tool_ctx.msg_ctx.channel_id)
+                .await
+                .contains(&tool) =>
+        {
+            format!("Error: the tool {} is disabled in this channel", call.name)
+        }
+        Some(tool) => tool.call(&call.arguments, tool_ctx).await,
+        None => format!("Error: unknown tool {}", call.name),
+    };
+
+    tracing::info!("Tool call {}({}) -> {}", call.name, call.arguments, output);
+
+    output
+}
+
+/// Send a single request through the circuit breaker
+/// Refused without calling the API while the circuit is open
+async fn send_with_breaker(
+    ctx: &Context,
+    client: &Client,
+    request: &ResponsesRequest,
+) -> eyre::Result<(OpenAiResponse, Vec<serde_json::Value>)> {
+    circuit_breaker::acquire(ctx).await?;
+
+    let result = send_responses_api_request(client, request).await;
+    circuit_breaker::record_outcome(ctx, &result).await;
+    result
+}
+
+/// Send a single request to the OpenAI Responses API
+async fn send_responses_api_request(
+    client: &Client,
+    request: &ResponsesRequest,
+) -> eyre::Result<(OpenAiResponse, Vec<serde_json::Value>)> {
+    // Wait for a free slot, held until the response is read
+    let _permit = REQUEST_PERMITS.acquire().await?;
 
     let response = client
-        .post("https://api.openai.com/v1/responses")
+        .post(format!("{}/responses", *OPENAI_BASE_URL))
         .header("Authorization", format!("Bearer {}", *OPENAI_TOKEN))
         .header("Content-Type", "application/json")
-        .json(&request)
+        .json(request)
         .send()
         .await?;
 
@@ -51,13 +614,22 @@ async fn send_responses_api_request(
 }
 
 /// Process the response from OpenAI API
-async fn process_openai_response(response: Response) -> eyre::Result<(String, ResponsesUsage)> {
-    if !response.status().is_success() {
+/// Returns the parsed response along with its raw output items
+async fn process_openai_response(
+    response: Response,
+) -> eyre::Result<(OpenAiResponse, Vec<serde_json::Value>)> {
+    let status = response.status();
+    if !status.is_success() {
         let error_text = response.text().await?;
-        return Err(eyre::eyre!("OpenAI API error: {}", error_text));
+        return Err(OpenAiApiError::from_response_body(status.as_u16(), &error_text).into());
     }
 
-    let response_data: OpenAiResponse = response.json().await?;
+    let raw_response: serde_json::Value = response.json().await?;
+    let raw_output = raw_response["output"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let response_data: OpenAiResponse = serde_json::from_value(raw_response)?;
     tracing::debug!("OpenAI response: {:#?}", response_data);
 
     // Log token usage information
@@ -71,35 +643,57 @@ async fn process_openai_response(response: Response) -> eyre::Result<(String, Re
         usage.total_tokens
     );
 
-    // Find the first message output and extract its text content
-    let content = response_data
+    Ok((response_data, raw_output))
+}
+
+/// Extract the text of the messages in the response
+/// Incomplete responses keep the partial text, refusals are returned as they are
+fn extract_text_response(response_data: &OpenAiResponse) -> eyre::Result<ResponseText> {
+    let mut text = ResponseText {
+        incomplete_reason: response_data.incomplete_reason(),
+        ..Default::default()
+    };
+
+    let contents = response_data.output.iter().flat_map(|item| match item {
+        OutputItem::Message(msg_output) => msg_output.content.as_slice(),
+        _ => &[],
+    });
+    for content_item in contents {
+        match content_item {
+            ContentItem::OutputText { text: output } => text.content.push_str(output),
+            ContentItem::Refusal { refusal } => {
+                text.content.push_str(refusal);
+                text.refusal = true;
+            }
+            _ => {}
+        }
+    }
+
+    if text.content.trim().is_empty() {
+        return Err(match text.incomplete_reason {
+            Some(reason) => eyre::eyre!("OpenAI response was cut off before any text: {reason}"),
+            None => eyre::eyre!("No valid text response from OpenAI"),
+        });
+    }
+
+    Ok(text)
+}
+
+/// Extract the reasoning summary parts of the response
+fn extract_reasoning_summary(response_data: &OpenAiResponse) -> Vec<String> {
+    response_data
         .output
         .iter()
-        .find_map(|item| {
-            if let OutputItem::Message(msg_output) = item {
-                // Find the first text content in the message
-                msg_output.content.iter().find_map(|content_item| {
-                    if msg_output.status != "completed" {
-                        tracing::warn!(
-                            "Message output status is not completed: {}",
-                            msg_output.status
-                        );
-                        return None;
-                    }
-                    if let ContentItem::OutputText { text } = content_item {
-                        Some(text.clone())
-                    } else {
-                        None
-                    }
-                })
-            } else {
-                None
-            }
+        .filter_map(|item| match item {
+            OutputItem::Reasoning(reasoning) => Some(&reasoning.summary),
+            _ => None,
         })
-        .ok_or_else(|| eyre::eyre!("No valid text response from OpenAI"))?;
-
-    // Return both the content and the token usage
-    Ok((content, response_data.usage))
+        .flatten()
+        .filter_map(|part| match part {
+            ReasoningSummary::SummaryText { text } => Some(text.clone()),
+            ReasoningSummary::Other => None,
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -108,6 +702,234 @@ mod tests {
     use dotenvy::dotenv;
     use std::env;
 
+    #[test]
+    fn test_function_call_output_is_parsed_and_dispatched() {
+        let raw = serde_json::json!({
+            "id": "resp_1",
+            "output": [
+                { "type": "reasoning", "id": "rs_1", "summary": [] },
+                {
+                    "type": "function_call",
+                    "id": "fc_1",
+                    "call_id": "call_1",
+                    "name": "calculate",
+                    "arguments": "{\"expression\": \"(30000 + 15000) / 3\"}",
+                    "status": "completed"
+                }
+            ],
+            "usage": {
+                "input_tokens": 10,
+                "input_tokens_details": { "cached_tokens": 0 },
+                "output_tokens": 5,
+                "output_tokens_details": { "reasoning_tokens": 0 },
+                "total_tokens": 15
+            }
+        });
+        let response: OpenAiResponse = serde_json::from_value(raw).unwrap();
+
+        let OutputItem::FunctionCall(call) = &response.output[1] else {
+            panic!("Expected a function call, got {:?}", response.output[1]);
+        };
+        assert!(matches!(response.output[0], OutputItem::Reasoning(_)));
+        assert_eq!(
+            BuiltinTool::from_function_name(&call.name),
+            Some(BuiltinTool::Calculator)
+        );
+        assert_eq!(call.call_id, "call_1");
+        assert!(extract_text_response(&response).is_err());
+    }
+
+    fn response_with(status: &str, output: serde_json::Value) -> OpenAiResponse {
+        serde_json::from_value(serde_json::json!({
+            "id": "resp_1",
+            "status": status,
+            "incomplete_details": if status == "incomplete" {
+                serde_json::json!({ "reason": "max_output_tokens" })
+            } else {
+                serde_json::Value::Null
+            },
+            "output": output,
+            "usage": {
+                "input_tokens": 10,
+                "input_tokens_details": { "cached_tokens": 0 },
+                "output_tokens": 5,
+                "output_tokens_details": { "reasoning_tokens": 0 },
+                "total_tokens": 15
+            }
+        }))
+        .unwrap()
+    }
+
+    fn message(status: &str, content: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "type": "message",
+            "id": "msg_1",
+            "status": status,
+            "role": "assistant",
+            "content": content
+        })
+    }
+
+    #[test]
+    fn test_extract_completed_text() {
+        let response = response_with(
+            "completed",
+            serde_json::json!([message(
+                "completed",
+                serde_json::json!([
+                    { "type": "output_text", "text": "Hello", "annotations": [] },
+                    { "type": "output_text", "text": " world", "annotations": [] }
+                ])
+            )]),
+        );
+
+        let text = extract_text_response(&response).unwrap();
+        assert_eq!(text.content, "Hello world");
+        assert!(!text.refusal);
+        assert_eq!(text.incomplete_reason, None);
+    }
+
+    #[test]
+    fn test_extract_refusal() {
+        let response = response_with(
+            "completed",
+            serde_json::json!([message(
+                "completed",
+                serde_json::json!([{ "type": "refusal", "refusal": "I can't help with that." }])
+            )]),
+        );
+
+        let text = extract_text_response(&response).unwrap();
+        assert_eq!(text.content, "I can't help with that.");
+        assert!(text.refusal);
+    }
+
+    #[test]
+    fn test_extract_incomplete_text() {
+        let response = response_with(
+            "incomplete",
+            serde_json::json!([message(
+                "incomplete",
+                serde_json::json!([{ "type": "output_text", "text": "The answer is", "annotations": [] }])
+            )]),
+        );
+
+        let text = extract_text_response(&response).unwrap();
+        assert_eq!(text.content, "The answer is");
+        assert_eq!(text.incomplete_reason.as_deref(), Some("max_output_tokens"));
+
+        let reply = BotReply {
+            content: text.content,
+            refusal: false,
+            incomplete_reason: text.incomplete_reason,
+            reasoning_summary: None,
+            moderation: None,
+        };
+        assert_eq!(
+            reply.display_text(),
+            "The answer is…\n\n*[truncated: max_output_tokens]*"
+        );
+
+        // Cut off while still reasoning
+        let response = response_with(
+            "incomplete",
+            serde_json::json!([{ "type": "reasoning", "id": "rs_1", "summary": [] }]),
+        );
+        let err = extract_text_response(&response).unwrap_err();
+        assert!(err.to_string().contains("max_output_tokens"));
+    }
+
+    #[test]
+    fn test_extract_reasoning_summary() {
+        let response = response_with(
+            "completed",
+            serde_json::json!([
+                {
+                    "type": "reasoning",
+                    "id": "rs_1",
+                    "summary": [
+                        { "type": "summary_text", "text": "**Checking the question**" },
+                        { "type": "summary_text", "text": "It asks for a capital." }
+                    ]
+                },
+                message(
+                    "completed",
+                    serde_json::json!([{ "type": "output_text", "text": "Seoul", "annotations": [] }])
+                )
+            ]),
+        );
+
+        assert_eq!(
+            extract_reasoning_summary(&response),
+            vec!["**Checking the question**", "It asks for a capital."]
+        );
+        assert_eq!(extract_text_response(&response).unwrap().content, "Seoul");
+    }
+
+    #[test]
+    fn test_completed_tool_call_input_items() {
+        let completed = CompletedToolCall {
+            call: FunctionCall {
+                call_id: "call_1".to_string(),
+                name: "generate_image".to_string(),
+                arguments: r#"{"prompt":"cat"}"#.to_string(),
+            },
+            output: r#"{"posted":true}"#.to_string(),
+        };
+
+        let items = serde_json::to_value(completed.input_items()).unwrap();
+        assert_eq!(
+            items,
+            serde_json::json!([
+                {
+                    "type": "function_call",
+                    "call_id": "call_1",
+                    "name": "generate_image",
+                    "arguments": "{\"prompt\":\"cat\"}"
+                },
+                {
+                    "type": "function_call_output",
+                    "call_id": "call_1",
+                    "output": "{\"posted\":true}"
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_extend_reply_with_continuation() {
+        let mut reply = BotReply {
+            content: "The answer is".to_string(),
+            refusal: false,
+            incomplete_reason: Some("max_output_tokens".to_string()),
+            reasoning_summary: Some("First".to_string()),
+            moderation: None,
+        };
+        assert!(reply.can_continue());
+
+        reply.extend(BotReply {
+            content: " 42.".to_string(),
+            refusal: false,
+            incomplete_reason: None,
+            reasoning_summary: Some("Second".to_string()),
+            moderation: Some(ModerationAction::Warn),
+        });
+        assert_eq!(reply.content, "The answer is 42.");
+        assert!(!reply.can_continue());
+        assert_eq!(reply.display_text(), "The answer is 42.");
+        assert_eq!(reply.reasoning_summary.as_deref(), Some("First\n\nSecond"));
+        assert_eq!(reply.moderation, Some(ModerationAction::Warn));
+
+        // Content filters can't be continued
+        reply.incomplete_reason = Some("content_filter".to_string());
+        assert!(!reply.can_continue());
+
+        // Neither can blocked replies, which aren't in the history
+        reply.incomplete_reason = Some("max_output_tokens".to_string());
+        reply.moderation = Some(ModerationAction::Block);
+        assert!(!reply.can_continue());
+    }
+
     #[tokio::test]
     #[ignore = "This test calls the OpenAI API, which incurs a cost. It is ignored by default to avoid incurring a cost without intent."]
     async fn test_send_responses_api_request() {
@@ -139,12 +961,16 @@ mod tests {
         ];
 
         // Send the actual API request
-        let result = send_responses_api_request(messages).await;
+        let model = crate::utils::persistence::get_current_model().await;
+        let request = ResponsesRequest::new(model, messages);
+        let result = send_responses_api_request(&Client::new(), &request).await;
 
         // Verify the result
         assert!(result.is_ok(), "API request failed: {:?}", result.err());
 
-        let (response, token_usage) = result.unwrap();
+        let (response_data, _) = result.unwrap();
+        let response = extract_text_response(&response_data).unwrap().content;
+        let token_usage = response_data.usage;
 
         // Verify response is not empty
         assert!(!response.is_empty(), "Response from OpenAI was empty");
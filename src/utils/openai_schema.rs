@@ -1,20 +1,135 @@
 use crate::utils::conversation::ChatMessage;
-use crate::utils::persistence::get_current_model;
 use serde::{Deserialize, Serialize};
 
 /// Request structure for OpenAI Responses API
 #[derive(Debug, Serialize)]
 pub struct ResponsesRequest {
     model: String,
-    input: Vec<ChatMessage>,
+    input: Vec<InputItem>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<FunctionTool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_response_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    truncation: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning: Option<ReasoningConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt_cache_key: Option<String>,
+}
+
+/// Reasoning options of a request
+#[derive(Debug, Clone, Serialize)]
+pub struct ReasoningConfig {
+    pub summary: &'static str,
 }
 
 impl ResponsesRequest {
-    pub async fn new(messages: Vec<ChatMessage>) -> Self {
-        let model = get_current_model().await;
+    pub fn new(model: String, messages: Vec<ChatMessage>) -> Self {
         Self {
             model,
-            input: messages,
+            input: messages.into_iter().map(InputItem::Message).collect(),
+            tools: Vec::new(),
+            previous_response_id: None,
+            truncation: None,
+            reasoning: None,
+            prompt_cache_key: None,
+        }
+    }
+
+    /// Ask for a summary of the model's reasoning, ignored for models that don't reason
+    pub fn with_reasoning_summary(mut self) -> Self {
+        if is_reasoning_model(&self.model) {
+            self.reasoning = Some(ReasoningConfig { summary: "auto" });
+        }
+        self
+    }
+
+    /// Continue from a previously stored response instead of resending the history
+    pub fn with_previous_response(mut self, response_id: String) -> Self {
+        self.previous_response_id = Some(response_id);
+        // The stored context keeps growing, let the API drop old items when it gets too long
+        self.truncation = Some("auto");
+        self
+    }
+
+    /// Route requests sharing the same prompt prefix to the same cache
+    pub fn with_prompt_cache_key(mut self, key: String) -> Self {
+        self.prompt_cache_key = Some(key);
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    /// Attach function tools the model is allowed to call
+    pub fn with_tools(mut self, tools: Vec<FunctionTool>) -> Self {
+        self.tools = tools;
+        self
+    }
+
+    /// Append items to the input, e.g. tool calls and their outputs
+    pub fn extend_input(&mut self, items: impl IntoIterator<Item = InputItem>) {
+        self.input.extend(items);
+    }
+}
+
+/// Whether a model reasons before answering and accepts reasoning options
+pub fn is_reasoning_model(model: &str) -> bool {
+    let mut chars = model.chars();
+    model.starts_with("gpt-5")
+        || (chars.next() == Some('o') && chars.next().is_some_and(|c| c.is_ascii_digit()))
+}
+
+/// Item in the `input` array of a Responses API request
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum InputItem {
+    /// A regular conversation message
+    Message(ChatMessage),
+    /// The result of a function call requested by the model
+    FunctionCallOutput(FunctionCallOutput),
+    /// An output item of a previous response, passed back verbatim
+    Raw(serde_json::Value),
+}
+
+/// Function tool definition sent with a request
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionTool {
+    #[serde(rename = "type")]
+    pub tool_type: &'static str, // always "function"
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+impl FunctionTool {
+    pub fn new(name: &str, description: &str, parameters: serde_json::Value) -> Self {
+        Self {
+            tool_type: "function",
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters,
+        }
+    }
+}
+
+/// Result of a function call, sent back to the model
+#[derive(Debug, Clone, Serialize)]
+pub struct FunctionCallOutput {
+    #[serde(rename = "type")]
+    pub item_type: &'static str, // always "function_call_output"
+    pub call_id: String,
+    pub output: String,
+}
+
+impl FunctionCallOutput {
+    pub fn new(call_id: String, output: String) -> Self {
+        Self {
+            item_type: "function_call_output",
+            call_id,
+            output,
         }
     }
 }
@@ -24,20 +139,72 @@ impl ResponsesRequest {
 #[allow(dead_code)]
 pub struct OpenAiResponse {
     pub id: String,
+    /// "completed" or "incomplete" (e.g. when `max_output_tokens` was reached)
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub incomplete_details: Option<IncompleteDetails>,
     pub output: Vec<OutputItem>,
     pub usage: ResponsesUsage,
 }
 
+impl OpenAiResponse {
+    /// Reason the response was cut off, if it is incomplete
+    pub fn incomplete_reason(&self) -> Option<String> {
+        (self.status.as_deref() == Some("incomplete")).then(|| {
+            self.incomplete_details
+                .as_ref()
+                .map(|details| details.reason.clone())
+                .unwrap_or_else(|| "unknown".to_string())
+        })
+    }
+}
+
+/// Why a response is incomplete
+#[derive(Debug, Deserialize)]
+pub struct IncompleteDetails {
+    pub reason: String,
+}
+
 /// Output item in the OpenAI response
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
 pub enum OutputItem {
     #[serde(rename = "message")]
     Message(MessageOutput),
+    #[serde(rename = "function_call")]
+    FunctionCall(FunctionCall),
+    #[serde(rename = "reasoning")]
+    Reasoning(ReasoningOutput),
+    #[serde(other)]
+    Other,
+}
+
+/// Reasoning of the model, with a readable summary if one was requested
+#[derive(Debug, Deserialize)]
+pub struct ReasoningOutput {
+    #[serde(default)]
+    pub summary: Vec<ReasoningSummary>,
+}
+
+/// Part of a reasoning summary
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum ReasoningSummary {
+    #[serde(rename = "summary_text")]
+    SummaryText { text: String },
     #[serde(other)]
     Other,
 }
 
+/// Function call requested by the model
+#[derive(Debug, Clone, Deserialize)]
+pub struct FunctionCall {
+    pub call_id: String,
+    pub name: String,
+    pub arguments: String,
+}
+
 /// Message output structure
 #[derive(Debug, Deserialize)]
 #[allow(dead_code)]
@@ -56,8 +223,19 @@ pub enum ContentItem {
     InputText { text: String },
     #[serde(rename = "input_image")]
     InputImage { image_url: String },
+    #[serde(rename = "input_file")]
+    InputFile {
+        filename: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_url: Option<String>,
+        /// Base64 data URL, used for the PDFs kept in the data directory
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_data: Option<String>,
+    },
     #[serde(rename = "output_text")]
     OutputText { text: String },
+    #[serde(rename = "refusal")]
+    Refusal { refusal: String },
     #[serde(other)]
     Other,
 }
@@ -72,6 +250,17 @@ pub struct ResponsesUsage {
     pub total_tokens: u32,
 }
 
+impl std::ops::AddAssign for ResponsesUsage {
+    /// Accumulate usage over multiple requests (e.g. tool call rounds)
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.input_tokens_details.cached_tokens += other.input_tokens_details.cached_tokens;
+        self.output_tokens += other.output_tokens;
+        self.output_tokens_details.reasoning_tokens += other.output_tokens_details.reasoning_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
 #[derive(Copy, Clone, Debug, Deserialize)]
 pub struct InputTokensDetails {
     pub cached_tokens: u32,
@@ -81,3 +270,147 @@ pub struct InputTokensDetails {
 pub struct OutputTokensDetails {
     pub reasoning_tokens: u32,
 }
+
+/// Error body returned by the OpenAI API
+#[derive(Debug, Deserialize)]
+struct ApiErrorBody {
+    error: ApiErrorDetails,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ApiErrorDetails {
+    #[serde(default)]
+    message: String,
+    #[serde(rename = "type")]
+    error_type: Option<String>,
+    code: Option<String>,
+    param: Option<String>,
+}
+
+/// Error response of the OpenAI API with its HTTP status
+#[derive(Debug, Clone)]
+pub struct OpenAiApiError {
+    pub status: u16,
+    pub message: String,
+    pub error_type: Option<String>,
+    pub code: Option<String>,
+    pub param: Option<String>,
+}
+
+impl OpenAiApiError {
+    /// Parse an error response body, keeping the raw text if it is not the usual JSON
+    pub fn from_response_body(status: u16, body: &str) -> Self {
+        let details = serde_json::from_str::<ApiErrorBody>(body)
+            .map(|parsed| parsed.error)
+            .unwrap_or_else(|_| ApiErrorDetails {
+                message: body.to_string(),
+                ..Default::default()
+            });
+
+        Self {
+            status,
+            message: details.message,
+            error_type: details.error_type,
+            code: details.code,
+            param: details.param,
+        }
+    }
+
+    /// Whether the error is caused by the requested model itself (unknown, deprecated,
+    /// not accessible or overloaded), so another model may still succeed
+    pub fn is_model_error(&self) -> bool {
+        let code = self.code.as_deref().unwrap_or_default();
+        let overloaded = self.status == 503
+            || self.status == 529
+            || code.contains("overloaded")
+            || self.message.to_lowercase().contains("overloaded");
+        let invalid_model = self.param.as_deref() == Some("model")
+            || matches!(code, "model_not_found" | "unsupported_model")
+            || (self.status == 404 && self.message.to_lowercase().contains("model"));
+
+        overloaded || invalid_model
+    }
+
+    /// Whether the error is caused by an unusable `previous_response_id`
+    pub fn is_previous_response_error(&self) -> bool {
+        self.param.as_deref() == Some("previous_response_id")
+            || self.message.contains("previous_response")
+            || self.message.contains("Previous response")
+    }
+
+    /// Whether the input, e.g. a long response chain, doesn't fit in the model's context
+    pub fn is_context_length_error(&self) -> bool {
+        self.code.as_deref() == Some("context_length_exceeded")
+    }
+}
+
+impl std::fmt::Display for OpenAiApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "OpenAI API error ({}): {}", self.status, self.message)?;
+        if let Some(code) = &self.code {
+            write!(f, " [{code}]")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for OpenAiApiError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_reasoning_model() {
+        assert!(is_reasoning_model("gpt-5"));
+        assert!(is_reasoning_model("gpt-5-mini"));
+        assert!(is_reasoning_model("o3"));
+        assert!(is_reasoning_model("o4-mini"));
+        assert!(!is_reasoning_model("gpt-4.1-mini"));
+        assert!(!is_reasoning_model("gpt-4o"));
+    }
+
+    #[test]
+    fn test_model_errors() {
+        let not_found = OpenAiApiError::from_response_body(
+            404,
+            r#"{"error": {"message": "The model `gpt-6` does not exist or you do not have access to it.", "type": "invalid_request_error", "param": null, "code": "model_not_found"}}"#,
+        );
+        assert!(not_found.is_model_error());
+        assert_eq!(not_found.code.as_deref(), Some("model_not_found"));
+
+        let invalid_param = OpenAiApiError::from_response_body(
+            400,
+            r#"{"error": {"message": "Invalid model", "type": "invalid_request_error", "param": "model", "code": null}}"#,
+        );
+        assert!(invalid_param.is_model_error());
+
+        let overloaded = OpenAiApiError::from_response_body(503, "Service Unavailable");
+        assert!(overloaded.is_model_error());
+        assert_eq!(overloaded.message, "Service Unavailable");
+    }
+
+    #[test]
+    fn test_other_errors() {
+        let bad_input = OpenAiApiError::from_response_body(
+            400,
+            r#"{"error": {"message": "Invalid 'input[2].content'", "type": "invalid_request_error", "param": "input[2].content", "code": null}}"#,
+        );
+        assert!(!bad_input.is_model_error());
+        assert!(!bad_input.is_previous_response_error());
+
+        let expired = OpenAiApiError::from_response_body(
+            400,
+            r#"{"error": {"message": "Previous response with id 'resp_1' not found.", "type": "invalid_request_error", "param": "previous_response_id", "code": "previous_response_not_found"}}"#,
+        );
+        assert!(expired.is_previous_response_error());
+        assert!(!expired.is_model_error());
+
+        let too_long = OpenAiApiError::from_response_body(
+            400,
+            r#"{"error": {"message": "Your input exceeds the context window of this model.", "type": "invalid_request_error", "param": "input", "code": "context_length_exceeded"}}"#,
+        );
+        assert!(too_long.is_context_length_error());
+        assert!(!too_long.is_previous_response_error());
+    }
+}
@@ -1,6 +1,6 @@
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs::{self, File};
 use std::io::{self, BufReader, BufWriter, Read, Write};
 use std::path::Path;
@@ -9,15 +9,40 @@ use strum_macros::{EnumIter, EnumString};
 use tokio::sync::Mutex;
 
 use crate::statics::get_state_dir_name;
+use crate::utils::backend_error::Language;
+use crate::utils::budget::{Budget, BudgetConfig, BudgetPeriod, BudgetScope, BudgetUsage};
 use crate::utils::conversation::ChatMessage;
-use serenity::model::id::ChannelId;
+use crate::utils::directives::{Directive, default_admin_only_directives};
+use crate::utils::image_store::{
+    delete_unreferenced_files, reference_stored_files, unreference_stored_files,
+};
+use crate::utils::model_router::ChannelRouting;
+use crate::utils::moderation::{GuildModeration, ModerationConfig};
+use crate::utils::msg_context::MsgContextInfo;
+use crate::utils::openai_schema::ResponsesUsage;
+use crate::utils::rate_limit::RateLimitConfig;
+use crate::utils::token_count::{estimate_message_tokens, estimate_tokens, input_token_limit};
+use crate::utils::tools::BuiltinTool;
+use crate::utils::usage::{PromptCacheStats, UsageLedger, UsageTotals, today_kst};
+use serenity::model::id::{ChannelId, GuildId};
 
 use super::statics::get_state_file_path;
 
 // Constants
 const DEFAULT_MODEL: &str = "gpt-5";
+const DEFAULT_FALLBACK_MODELS: [&str; 2] = ["gpt-5-mini", "gpt-4.1-mini"];
 const MAX_HISTORY_COUNT: usize = 300;
+// Estimated tokens kept per channel, so a few huge messages don't fill the context
+const MAX_HISTORY_TOKENS: usize = 100_000;
+// Once a limit is hit, this much more is trimmed so the start of the history (and with
+// it the cached prompt prefix) stays the same for many turns
+const HISTORY_TRIM_CHUNK: usize = 50;
+const HISTORY_TRIM_CHUNK_TOKENS: usize = 20_000;
 const CURRENT_STATE_VERSION: u32 = 2;
+const DEFAULT_AUTO_CONTINUE_ROUNDS: u32 = 2;
+const DEFAULT_MAX_IMAGES_PER_MESSAGE: u32 = 4;
+// Stored responses expire after 30 days, stop chaining a bit before that
+const RESPONSE_CHAIN_MAX_AGE_SECS: i64 = 28 * 24 * 60 * 60;
 
 /// Bot personality types that define different system prompts
 #[derive(
@@ -60,6 +85,7 @@ impl BotPersonality {
 - 과도한 이모지 사용은 피해줘. ㅎㅎ나 ㅋㅋ처럼 채팅에서 자주 쓰이는 문자들은 써도 돼.
 - 굳이 대화를 이어가려고 노력할 필요까지는 없어. 자꾸 말을 질문으로 끝내면 상대방이 부담스러워할 수 있어.
 - 너를 향한 공격이나 비난이 온 경우, 억울한 경우에는 그 감정을 표출해도 돼. 무조건 져 줄 필요는 없어.
+- 메시지가 여러 개 한꺼번에 오면, 답장 하나로 모두 대답해줘.
 ";
 
         let role = match self {
@@ -82,6 +108,34 @@ fn default_personality() -> BotPersonality {
     BotPersonality::Normal
 }
 
+fn default_fallback_models() -> Vec<String> {
+    DEFAULT_FALLBACK_MODELS
+        .iter()
+        .map(|m| m.to_string())
+        .collect()
+}
+
+fn default_auto_continue_rounds() -> u32 {
+    DEFAULT_AUTO_CONTINUE_ROUNDS
+}
+
+fn default_max_images_per_message() -> u32 {
+    DEFAULT_MAX_IMAGES_PER_MESSAGE
+}
+
+/// The last response of a channel, used to continue with `previous_response_id`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResponseChain {
+    /// ID of the last response stored by OpenAI
+    pub response_id: String,
+    /// Model that produced the response
+    pub model: String,
+    /// Unix timestamp of the response
+    pub created_at: i64,
+    /// Number of messages added to the history since the response
+    pub pending_messages: usize,
+}
+
 /// Structure to hold all persistent bot state
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BotState {
@@ -101,6 +155,82 @@ pub struct BotState {
     /// Channel-specific personalities
     #[serde(default)]
     pub channel_personalities: HashMap<ChannelId, BotPersonality>,
+
+    /// Built-in tools disabled per channel (all tools are enabled by default)
+    #[serde(default)]
+    pub channel_disabled_tools: HashMap<ChannelId, HashSet<BuiltinTool>>,
+
+    /// Whether to send only new messages with `previous_response_id` instead of the full history
+    #[serde(default)]
+    pub response_chaining: bool,
+
+    /// Last response per channel for `previous_response_id` chaining
+    #[serde(default)]
+    pub response_chains: HashMap<ChannelId, ResponseChain>,
+
+    /// Models tried in order when the current model fails with a model error
+    #[serde(default = "default_fallback_models")]
+    pub fallback_models: Vec<String>,
+
+    /// Model that answered the last request, which differs from the current one after a fallback
+    #[serde(default)]
+    pub last_answered_model: Option<String>,
+
+    /// Token usage and cost aggregated per day
+    #[serde(default)]
+    pub usage: UsageLedger,
+
+    /// Daily and monthly spend limits
+    #[serde(default)]
+    pub budgets: BudgetConfig,
+
+    /// Token bucket limits on mentions per user and per channel
+    #[serde(default)]
+    pub rate_limits: RateLimitConfig,
+
+    /// Channels where mentions arriving during a request are answered one by one
+    #[serde(default)]
+    pub batching_disabled_channels: HashSet<ChannelId>,
+
+    /// Whether to request reasoning summaries and post them as spoilers
+    #[serde(default)]
+    pub reasoning_summary: bool,
+
+    /// How many times a reply cut off by the output limit is continued automatically
+    #[serde(default = "default_auto_continue_rounds")]
+    pub auto_continue_rounds: u32,
+
+    /// Whether transcripts of voice messages are posted as a reply
+    #[serde(default)]
+    pub transcript_replies: bool,
+
+    /// Whether answers replying to a message also ping its author
+    #[serde(default)]
+    pub reply_ping: bool,
+
+    /// How many images of a message (attachments, embeds and the replied-to message) are sent
+    #[serde(default = "default_max_images_per_message")]
+    pub max_images_per_message: u32,
+
+    /// Moderation settings per guild and the word list
+    #[serde(default)]
+    pub moderation: ModerationConfig,
+
+    /// Prompt cache hits per channel
+    #[serde(default)]
+    pub prompt_cache: HashMap<ChannelId, PromptCacheStats>,
+
+    /// Channels where simple messages are routed to a cheaper model
+    #[serde(default)]
+    pub channel_routing: HashMap<ChannelId, ChannelRouting>,
+
+    /// Message directives only admins may use
+    #[serde(default = "default_admin_only_directives")]
+    pub admin_only_directives: HashSet<Directive>,
+
+    /// Language of the bot's own messages per guild, Korean if not set
+    #[serde(default)]
+    pub guild_languages: HashMap<GuildId, Language>,
 }
 
 impl Default for BotState {
@@ -111,6 +241,25 @@ impl Default for BotState {
             version: CURRENT_STATE_VERSION,
             default_personality: BotPersonality::Normal,
             channel_personalities: HashMap::new(),
+            channel_disabled_tools: HashMap::new(),
+            response_chaining: false,
+            response_chains: HashMap::new(),
+            fallback_models: default_fallback_models(),
+            last_answered_model: None,
+            usage: UsageLedger::default(),
+            budgets: BudgetConfig::default(),
+            rate_limits: RateLimitConfig::default(),
+            batching_disabled_channels: HashSet::new(),
+            reasoning_summary: false,
+            auto_continue_rounds: default_auto_continue_rounds(),
+            transcript_replies: false,
+            reply_ping: false,
+            max_images_per_message: default_max_images_per_message(),
+            moderation: ModerationConfig::default(),
+            prompt_cache: HashMap::new(),
+            channel_routing: HashMap::new(),
+            admin_only_directives: default_admin_only_directives(),
+            guild_languages: HashMap::new(),
         }
     }
 }
@@ -127,6 +276,8 @@ impl BotState {
         let personality = self.get_channel_personality(channel_id);
         let system_prompt = personality.get_system_prompt();
 
+        // The system prompt only depends on the personality so the start of every request
+        // of the channel is the same and can be read from the prompt cache
         let mut result = vec![ChatMessage::developer(system_prompt)];
         if let Some(history) = self.conversations.get(&channel_id) {
             result.extend(history.iter().cloned());
@@ -144,25 +295,165 @@ impl BotState {
     /// Set the personality for a specific channel
     fn set_channel_personality(&mut self, channel_id: ChannelId, personality: BotPersonality) {
         self.channel_personalities.insert(channel_id, personality);
+
+        // The system prompt of the stored response is outdated now
+        self.invalidate_response_chain(channel_id);
+    }
+
+    /// Get the tools disabled for a specific channel
+    fn get_disabled_tools(&self, channel_id: ChannelId) -> HashSet<BuiltinTool> {
+        self.channel_disabled_tools
+            .get(&channel_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Enable or disable a tool for a specific channel
+    fn set_tool_enabled(&mut self, channel_id: ChannelId, tool: BuiltinTool, enabled: bool) {
+        let disabled = self.channel_disabled_tools.entry(channel_id).or_default();
+        if enabled {
+            disabled.remove(&tool);
+        } else {
+            disabled.insert(tool);
+        }
+
+        if disabled.is_empty() {
+            self.channel_disabled_tools.remove(&channel_id);
+        }
     }
 
     /// Add a message to the conversation history for a channel
-    fn add_message(&mut self, channel_id: ChannelId, message: ChatMessage) {
+    /// Returns the messages trimmed from the history to make room
+    fn add_message(&mut self, channel_id: ChannelId, message: ChatMessage) -> Vec<ChatMessage> {
         // Get or create the conversation history for this channel
         let history = self.conversations.entry(channel_id).or_default();
 
         // Add the new message
         history.push_back(message);
 
-        // Trim if needed - with VecDeque we can efficiently remove from the front
-        while history.len() > MAX_HISTORY_COUNT {
-            history.pop_front();
+        // Keep track of the messages the last response hasn't seen yet
+        if let Some(chain) = self.response_chains.get_mut(&channel_id) {
+            chain.pending_messages += 1;
+        }
+
+        // Trim in chunks if needed - with VecDeque we can efficiently remove from the front
+        let mut trim_count = if history.len() > MAX_HISTORY_COUNT {
+            history.len() - (MAX_HISTORY_COUNT - HISTORY_TRIM_CHUNK)
+        } else {
+            0
+        };
+
+        // Trim further while the estimated size is too large, keeping the new message
+        let mut tokens = estimate_tokens(history.iter().skip(trim_count));
+        if tokens > MAX_HISTORY_TOKENS {
+            while tokens > MAX_HISTORY_TOKENS - HISTORY_TRIM_CHUNK_TOKENS
+                && trim_count + 1 < history.len()
+            {
+                tokens -= estimate_message_tokens(&history[trim_count]);
+                trim_count += 1;
+            }
+        }
+
+        let trimmed = history.drain(..trim_count).collect();
+
+        // The stored response still contains the trimmed messages, and would keep growing
+        // on the server until it no longer fits in the context
+        if trim_count > 0 {
+            self.invalidate_response_chain(channel_id);
+        }
+        trimmed
+    }
+
+    /// Append text to the last message of a channel if it is from the assistant
+    /// Returns false if there is no such message
+    fn append_to_last_assistant_message(&mut self, channel_id: ChannelId, text: &str) -> bool {
+        let Some(message) = self
+            .conversations
+            .get_mut(&channel_id)
+            .and_then(|history| history.back_mut())
+        else {
+            return false;
+        };
+        if message.role != "assistant" {
+            return false;
         }
+
+        message.append_text(text);
+
+        // The stored response doesn't contain the merged text
+        self.invalidate_response_chain(channel_id);
+        true
     }
 
     /// Remove conversation history for a channel
-    fn remove_conversation(&mut self, channel_id: ChannelId) {
-        self.conversations.remove(&channel_id);
+    /// Returns the removed messages
+    fn remove_conversation(&mut self, channel_id: ChannelId) -> Vec<ChatMessage> {
+        let removed = self.conversations.remove(&channel_id);
+        self.invalidate_response_chain(channel_id);
+        removed.map(Vec::from).unwrap_or_default()
+    }
+
+    /// Get the previous response ID and the messages added since, if the chain can be continued
+    fn get_chained_input(
+        &self,
+        channel_id: ChannelId,
+        model: &str,
+        now: i64,
+    ) -> Option<(String, Vec<ChatMessage>)> {
+        if !self.response_chaining {
+            return None;
+        }
+
+        let chain = self.response_chains.get(&channel_id)?;
+        let history = self.conversations.get(&channel_id)?;
+
+        // The chain is invalid if the model changed, the response expired or the new
+        // messages were already trimmed from the history
+        if chain.model != model
+            || now - chain.created_at > RESPONSE_CHAIN_MAX_AGE_SECS
+            || chain.pending_messages == 0
+            || chain.pending_messages > history.len()
+        {
+            return None;
+        }
+
+        // The full history is sent instead when it has to be shortened for the model,
+        // as the stored response contains at most the same messages
+        if estimate_tokens(history) > input_token_limit(model) {
+            return None;
+        }
+
+        let new_messages = history
+            .iter()
+            .skip(history.len() - chain.pending_messages)
+            .cloned()
+            .collect();
+
+        Some((chain.response_id.clone(), new_messages))
+    }
+
+    /// Record the last response of a channel (after its message was added to the history)
+    fn set_response_chain(
+        &mut self,
+        channel_id: ChannelId,
+        response_id: String,
+        model: String,
+        now: i64,
+    ) {
+        self.response_chains.insert(
+            channel_id,
+            ResponseChain {
+                response_id,
+                model,
+                created_at: now,
+                pending_messages: 0,
+            },
+        );
+    }
+
+    /// Forget the last response of a channel so the next request sends the full history
+    fn invalidate_response_chain(&mut self, channel_id: ChannelId) {
+        self.response_chains.remove(&channel_id);
     }
 
     /// Change the model used for OpenAI API requests
@@ -176,6 +467,17 @@ impl BotState {
     fn get_current_model(&self) -> String {
         self.current_model.clone()
     }
+
+    /// Get the current model followed by the fallback models, without duplicates
+    fn get_model_candidates(&self) -> Vec<String> {
+        let mut candidates = vec![self.current_model.clone()];
+        for model in &self.fallback_models {
+            if !candidates.contains(model) {
+                candidates.push(model.clone());
+            }
+        }
+        candidates
+    }
 }
 
 /// Save the current bot state to disk
@@ -201,6 +503,7 @@ pub async fn load_state() -> io::Result<()> {
             }
 
             let mut current_state = BOT_STATE.lock().await;
+            reference_stored_files(state.conversations.values().flatten());
             *current_state = state;
             tracing::info!("Bot state loaded successfully");
             tracing::info!("Current state: {:#?}", current_state);
@@ -306,30 +609,432 @@ pub async fn set_channel_personality(channel_id: ChannelId, personality: BotPers
     }
 }
 
+/// Get the tools disabled for a specific channel
+pub async fn get_disabled_tools(channel_id: ChannelId) -> HashSet<BuiltinTool> {
+    BOT_STATE.lock().await.get_disabled_tools(channel_id)
+}
+
+/// Enable or disable a tool for a specific channel
+pub async fn set_tool_enabled(channel_id: ChannelId, tool: BuiltinTool, enabled: bool) {
+    let mut state = BOT_STATE.lock().await;
+    state.set_tool_enabled(channel_id, tool, enabled);
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after changing channel tools: {}", e);
+    }
+}
+
 /// Add a message to the conversation history for a channel
 pub async fn add_message(channel_id: ChannelId, message: ChatMessage) {
     let mut state = BOT_STATE.lock().await;
-    state.add_message(channel_id, message);
+    reference_stored_files([&message]);
+    let trimmed = state.add_message(channel_id, message);
+    let unused_files = unreference_stored_files(&trimmed);
     drop(state); // Explicitly release the lock
 
+    // Delete the stored images of the trimmed messages that nothing refers to anymore
+    delete_unreferenced_files(unused_files).await;
+
     // Save state
     if let Err(e) = save_state().await {
         tracing::error!("Failed to save state after adding message: {}", e);
     }
 }
 
+/// Append text to the last message of a channel if it is from the assistant
+pub async fn append_to_last_assistant_message(channel_id: ChannelId, text: &str) -> bool {
+    let mut state = BOT_STATE.lock().await;
+    let appended = state.append_to_last_assistant_message(channel_id, text);
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after appending message: {}", e);
+    }
+
+    appended
+}
+
 /// Remove conversation history for a channel
 pub async fn remove_conversation(channel_id: ChannelId) {
     let mut state = BOT_STATE.lock().await;
-    state.remove_conversation(channel_id);
+    let removed = state.remove_conversation(channel_id);
+    let unused_files = unreference_stored_files(&removed);
     drop(state); // Explicitly release the lock
 
+    // Delete the stored images of the removed messages like in add_message
+    delete_unreferenced_files(unused_files).await;
+
     // Save state
     if let Err(e) = save_state().await {
         tracing::error!("Failed to save state after removing conversation: {}", e);
     }
 }
 
+/// Get the previous response ID and the messages added since, if response chaining is
+/// enabled and the chain of the channel is still valid
+pub async fn get_chained_input(
+    channel_id: ChannelId,
+    model: &str,
+) -> Option<(String, Vec<ChatMessage>)> {
+    let now = chrono::Utc::now().timestamp();
+    BOT_STATE
+        .lock()
+        .await
+        .get_chained_input(channel_id, model, now)
+}
+
+/// Record the last response of a channel for `previous_response_id` chaining
+pub async fn set_response_chain(channel_id: ChannelId, response_id: String, model: String) {
+    let now = chrono::Utc::now().timestamp();
+    let mut state = BOT_STATE.lock().await;
+    state.set_response_chain(channel_id, response_id, model, now);
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after setting response chain: {}", e);
+    }
+}
+
+/// Forget the last response of a channel so the next request sends the full history
+pub async fn invalidate_response_chain(channel_id: ChannelId) {
+    let mut state = BOT_STATE.lock().await;
+    state.invalidate_response_chain(channel_id);
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!(
+            "Failed to save state after invalidating response chain: {}",
+            e
+        );
+    }
+}
+
+/// Check whether response chaining is enabled
+pub async fn is_response_chaining_enabled() -> bool {
+    BOT_STATE.lock().await.response_chaining
+}
+
+/// Enable or disable response chaining for all channels
+pub async fn set_response_chaining(enabled: bool) {
+    let mut state = BOT_STATE.lock().await;
+    state.response_chaining = enabled;
+    if !enabled {
+        state.response_chains.clear();
+    }
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!(
+            "Failed to save state after changing response chaining: {}",
+            e
+        );
+    }
+}
+
+/// Check whether reasoning summaries are shown
+pub async fn is_reasoning_summary_enabled() -> bool {
+    BOT_STATE.lock().await.reasoning_summary
+}
+
+/// Enable or disable reasoning summaries for all channels
+pub async fn set_reasoning_summary(enabled: bool) {
+    let mut state = BOT_STATE.lock().await;
+    state.reasoning_summary = enabled;
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!(
+            "Failed to save state after changing reasoning summaries: {}",
+            e
+        );
+    }
+}
+
+/// Check whether transcripts of voice messages are posted as a reply
+pub async fn is_transcript_reply_enabled() -> bool {
+    BOT_STATE.lock().await.transcript_replies
+}
+
+/// Enable or disable transcript replies for all channels
+pub async fn set_transcript_replies(enabled: bool) {
+    let mut state = BOT_STATE.lock().await;
+    state.transcript_replies = enabled;
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!(
+            "Failed to save state after changing transcript replies: {}",
+            e
+        );
+    }
+}
+
+/// Check if answers ping the author of the message they reply to
+pub async fn is_reply_ping_enabled() -> bool {
+    BOT_STATE.lock().await.reply_ping
+}
+
+/// Enable or disable pinging the author of the replied-to message
+pub async fn set_reply_ping(enabled: bool) {
+    let mut state = BOT_STATE.lock().await;
+    state.reply_ping = enabled;
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after changing reply pings: {}", e);
+    }
+}
+
+/// Get how many times a cut off reply is continued automatically
+pub async fn get_auto_continue_rounds() -> u32 {
+    BOT_STATE.lock().await.auto_continue_rounds
+}
+
+/// Set how many times a cut off reply is continued automatically
+pub async fn set_auto_continue_rounds(rounds: u32) {
+    let mut state = BOT_STATE.lock().await;
+    state.auto_continue_rounds = rounds;
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after setting auto continue: {}", e);
+    }
+}
+
+/// Get how many images of a message are sent to the model
+pub async fn get_max_images_per_message() -> u32 {
+    BOT_STATE.lock().await.max_images_per_message
+}
+
+/// Set how many images of a message are sent to the model
+pub async fn set_max_images_per_message(max_images: u32) {
+    let mut state = BOT_STATE.lock().await;
+    state.max_images_per_message = max_images;
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after setting max images: {}", e);
+    }
+}
+
+/// Get the moderation settings of a guild, None if moderation is off there
+pub async fn get_guild_moderation(guild_id: GuildId) -> Option<GuildModeration> {
+    BOT_STATE
+        .lock()
+        .await
+        .moderation
+        .guilds
+        .get(&guild_id)
+        .copied()
+}
+
+/// Set the moderation settings of a guild, None turns moderation off there
+pub async fn set_guild_moderation(guild_id: GuildId, settings: Option<GuildModeration>) {
+    let mut state = BOT_STATE.lock().await;
+    match settings {
+        Some(settings) => state.moderation.guilds.insert(guild_id, settings),
+        None => state.moderation.guilds.remove(&guild_id),
+    };
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after changing moderation: {}", e);
+    }
+}
+
+/// Get the words flagged by the word list moderation backend
+pub async fn get_moderation_words() -> Vec<String> {
+    BOT_STATE.lock().await.moderation.words.clone()
+}
+
+/// Set the words flagged by the word list moderation backend
+pub async fn set_moderation_words(words: Vec<String>) {
+    let mut state = BOT_STATE.lock().await;
+    state.moderation.words = words;
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!(
+            "Failed to save state after changing moderation words: {}",
+            e
+        );
+    }
+}
+
+/// Check whether mentions are batched in a specific channel
+pub async fn is_batching_enabled(channel_id: ChannelId) -> bool {
+    !BOT_STATE
+        .lock()
+        .await
+        .batching_disabled_channels
+        .contains(&channel_id)
+}
+
+/// Enable or disable batching of mentions for a specific channel
+pub async fn set_batching_enabled(channel_id: ChannelId, enabled: bool) {
+    let mut state = BOT_STATE.lock().await;
+    if enabled {
+        state.batching_disabled_channels.remove(&channel_id);
+    } else {
+        state.batching_disabled_channels.insert(channel_id);
+    }
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after changing batching: {}", e);
+    }
+}
+
+/// Record the token usage and cost of a response made for the given message
+pub async fn record_usage(msg_ctx: &MsgContextInfo, totals: UsageTotals) {
+    let mut state = BOT_STATE.lock().await;
+    state.usage.record(today_kst(), msg_ctx, totals);
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after recording usage: {}", e);
+    }
+}
+
+/// Record how much of the input of a response was read from the prompt cache
+pub async fn record_prompt_cache(channel_id: ChannelId, usage: &ResponsesUsage) {
+    let mut state = BOT_STATE.lock().await;
+    state
+        .prompt_cache
+        .entry(channel_id)
+        .or_default()
+        .record(usage);
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!(
+            "Failed to save state after recording prompt cache use: {}",
+            e
+        );
+    }
+}
+
+/// Get the prompt cache hits of a channel
+pub async fn get_prompt_cache_stats(channel_id: ChannelId) -> PromptCacheStats {
+    BOT_STATE
+        .lock()
+        .await
+        .prompt_cache
+        .get(&channel_id)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Get the usage ledger
+pub async fn get_usage_ledger() -> UsageLedger {
+    BOT_STATE.lock().await.usage.clone()
+}
+
+/// Get the current spend of the budgets that apply to the given message
+pub async fn check_budgets(msg_ctx: &MsgContextInfo) -> Vec<BudgetUsage> {
+    let state = BOT_STATE.lock().await;
+    state.budgets.evaluate(&state.usage, msg_ctx, today_kst())
+}
+
+/// Get the current spend of all configured budgets
+pub async fn get_budget_usages() -> Vec<BudgetUsage> {
+    let state = BOT_STATE.lock().await;
+    state.budgets.evaluate_all(&state.usage, today_kst())
+}
+
+/// Get the budgets of the given message that are close to their limit
+/// and haven't been warned about in their current period yet
+pub async fn take_budget_warnings(msg_ctx: &MsgContextInfo) -> Vec<BudgetUsage> {
+    let mut state = BOT_STATE.lock().await;
+    let today = today_kst();
+    let usages = state.budgets.evaluate(&state.usage, msg_ctx, today);
+    let warnings = state.budgets.take_new_warnings(&usages, today);
+    drop(state); // Explicitly release the lock
+
+    if !warnings.is_empty() {
+        // Save state
+        if let Err(e) = save_state().await {
+            tracing::error!("Failed to save state after budget warnings: {}", e);
+        }
+    }
+
+    warnings
+}
+
+/// Add or replace a budget
+pub async fn set_budget(budget: Budget) {
+    let mut state = BOT_STATE.lock().await;
+    state.budgets.set_budget(budget);
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after setting budget: {}", e);
+    }
+}
+
+/// Start the spend of the budgets of a scope over, returning how many were reset
+pub async fn reset_budget_spend(scope: BudgetScope, period: Option<BudgetPeriod>) -> usize {
+    let mut state = BOT_STATE.lock().await;
+    let BotState { budgets, usage, .. } = &mut *state;
+    let reset = budgets.reset_spend(usage, scope, period, today_kst());
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after resetting budget spend: {}", e);
+    }
+
+    reset
+}
+
+/// Remove the budgets of a scope, returning how many were removed
+pub async fn remove_budgets(scope: BudgetScope, period: Option<BudgetPeriod>) -> usize {
+    let mut state = BOT_STATE.lock().await;
+    let removed = state.budgets.remove_budgets(scope, period);
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after removing budgets: {}", e);
+    }
+
+    removed
+}
+
+/// Get the rate limit configuration
+pub async fn get_rate_limit_config() -> RateLimitConfig {
+    BOT_STATE.lock().await.rate_limits
+}
+
+/// Set the rate limit configuration
+pub async fn set_rate_limit_config(config: RateLimitConfig) {
+    let mut state = BOT_STATE.lock().await;
+    state.rate_limits = config;
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after setting rate limits: {}", e);
+    }
+}
+
 /// Get all channel IDs with conversation history
 pub async fn get_channel_ids() -> Vec<ChannelId> {
     BOT_STATE
@@ -377,3 +1082,374 @@ pub async fn change_model(model_name: &str) -> String {
 pub async fn get_current_model() -> String {
     BOT_STATE.lock().await.get_current_model()
 }
+
+/// Get the models to try in order: the current model followed by the fallback models
+pub async fn get_model_candidates() -> Vec<String> {
+    BOT_STATE.lock().await.get_model_candidates()
+}
+
+/// Get the routing settings of a channel, None if routing is off there
+pub async fn get_channel_routing(channel_id: ChannelId) -> Option<ChannelRouting> {
+    BOT_STATE
+        .lock()
+        .await
+        .channel_routing
+        .get(&channel_id)
+        .cloned()
+}
+
+/// Turn routing on with the given settings for a channel, or off with None
+pub async fn set_channel_routing(channel_id: ChannelId, routing: Option<ChannelRouting>) {
+    let mut state = BOT_STATE.lock().await;
+    match routing {
+        Some(routing) => state.channel_routing.insert(channel_id, routing),
+        None => state.channel_routing.remove(&channel_id),
+    };
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after changing model routing: {}", e);
+    }
+}
+
+/// Get the language of the bot's own messages in a guild (Korean in DMs)
+pub async fn get_guild_language(guild_id: Option<GuildId>) -> Language {
+    let Some(guild_id) = guild_id else {
+        return Language::default();
+    };
+    BOT_STATE
+        .lock()
+        .await
+        .guild_languages
+        .get(&guild_id)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Set the language of the bot's own messages in a guild
+pub async fn set_guild_language(guild_id: GuildId, language: Language) {
+    let mut state = BOT_STATE.lock().await;
+    state.guild_languages.insert(guild_id, language);
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after setting guild language: {}", e);
+    }
+}
+
+/// Get the message directives only admins may use
+pub async fn get_admin_only_directives() -> HashSet<Directive> {
+    BOT_STATE.lock().await.admin_only_directives.clone()
+}
+
+/// Allow a message directive only for admins, or for everyone
+pub async fn set_directive_admin_only(directive: Directive, admin_only: bool) {
+    let mut state = BOT_STATE.lock().await;
+    if admin_only {
+        state.admin_only_directives.insert(directive);
+    } else {
+        state.admin_only_directives.remove(&directive);
+    }
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!(
+            "Failed to save state after changing directive access: {}",
+            e
+        );
+    }
+}
+
+/// Get the fallback models
+pub async fn get_fallback_models() -> Vec<String> {
+    BOT_STATE.lock().await.fallback_models.clone()
+}
+
+/// Set the fallback models, tried in the given order
+pub async fn set_fallback_models(models: Vec<String>) {
+    let mut state = BOT_STATE.lock().await;
+    state.fallback_models = models;
+    drop(state); // Explicitly release the lock
+
+    // Save state
+    if let Err(e) = save_state().await {
+        tracing::error!("Failed to save state after setting fallback models: {}", e);
+    }
+}
+
+/// Get the model that answered the last request
+pub async fn get_last_answered_model() -> Option<String> {
+    BOT_STATE.lock().await.last_answered_model.clone()
+}
+
+/// Record the model that answered the last request
+pub async fn set_last_answered_model(model: String) {
+    BOT_STATE.lock().await.last_answered_model = Some(model);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHANNEL: ChannelId = ChannelId::new(1);
+
+    fn chaining_state() -> BotState {
+        BotState {
+            response_chaining: true,
+            ..BotState::default()
+        }
+    }
+
+    fn add_turn(state: &mut BotState, question: &str, response_id: &str) {
+        state.add_message(
+            CHANNEL,
+            ChatMessage::user(question.to_string(), "a".to_string()),
+        );
+        state.add_message(CHANNEL, ChatMessage::assistant("answer".to_string()));
+        state.set_response_chain(
+            CHANNEL,
+            response_id.to_string(),
+            DEFAULT_MODEL.to_string(),
+            0,
+        );
+    }
+
+    #[test]
+    fn test_append_to_last_assistant_message() {
+        let mut state = chaining_state();
+        add_turn(&mut state, "first", "resp_1");
+
+        assert!(state.append_to_last_assistant_message(CHANNEL, " and more"));
+        let last = state.conversations[&CHANNEL].back().unwrap();
+        assert_eq!(last.to_string(), "<assistant> answer and more");
+        assert_eq!(state.conversations[&CHANNEL].len(), 2);
+        // The stored response is outdated now
+        assert!(state.response_chains.is_empty());
+
+        state.add_message(
+            CHANNEL,
+            ChatMessage::user("second".to_string(), "a".to_string()),
+        );
+        assert!(!state.append_to_last_assistant_message(CHANNEL, "more"));
+        assert!(!state.append_to_last_assistant_message(ChannelId::new(2), "more"));
+    }
+
+    #[test]
+    fn test_chained_input_contains_only_new_messages() {
+        let mut state = chaining_state();
+        add_turn(&mut state, "first", "resp_1");
+        state.add_message(
+            CHANNEL,
+            ChatMessage::user("second".to_string(), "a".to_string()),
+        );
+        state.add_message(
+            CHANNEL,
+            ChatMessage::user("third".to_string(), "b".to_string()),
+        );
+
+        let (response_id, messages) = state.get_chained_input(CHANNEL, DEFAULT_MODEL, 10).unwrap();
+        assert_eq!(response_id, "resp_1");
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].to_string().contains("second"));
+        assert!(messages[1].to_string().contains("third"));
+    }
+
+    #[test]
+    fn test_chain_disabled_or_missing() {
+        let mut state = BotState::default();
+        add_turn(&mut state, "first", "resp_1");
+        state.add_message(
+            CHANNEL,
+            ChatMessage::user("second".to_string(), "a".to_string()),
+        );
+        assert!(
+            state
+                .get_chained_input(CHANNEL, DEFAULT_MODEL, 10)
+                .is_none()
+        );
+
+        let mut state = chaining_state();
+        state.add_message(
+            CHANNEL,
+            ChatMessage::user("first".to_string(), "a".to_string()),
+        );
+        assert!(
+            state
+                .get_chained_input(CHANNEL, DEFAULT_MODEL, 10)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_chain_invalidation() {
+        let mut state = chaining_state();
+        add_turn(&mut state, "first", "resp_1");
+        state.add_message(
+            CHANNEL,
+            ChatMessage::user("second".to_string(), "a".to_string()),
+        );
+
+        // Model switch
+        assert!(
+            state
+                .get_chained_input(CHANNEL, "other-model", 10)
+                .is_none()
+        );
+
+        // Expired response
+        assert!(
+            state
+                .get_chained_input(CHANNEL, DEFAULT_MODEL, RESPONSE_CHAIN_MAX_AGE_SECS + 1)
+                .is_none()
+        );
+
+        // Personality change
+        state.set_channel_personality(CHANNEL, BotPersonality::Tsundere);
+        assert!(
+            state
+                .get_chained_input(CHANNEL, DEFAULT_MODEL, 10)
+                .is_none()
+        );
+
+        // Forget
+        add_turn(&mut state, "third", "resp_2");
+        state.add_message(
+            CHANNEL,
+            ChatMessage::user("fourth".to_string(), "a".to_string()),
+        );
+        state.remove_conversation(CHANNEL);
+        assert!(
+            state
+                .get_chained_input(CHANNEL, DEFAULT_MODEL, 10)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_model_candidates_skip_duplicates() {
+        let state = BotState {
+            current_model: "gpt-5-mini".to_string(),
+            fallback_models: vec![
+                "gpt-5".to_string(),
+                "gpt-5-mini".to_string(),
+                "gpt-4.1-mini".to_string(),
+            ],
+            ..BotState::default()
+        };
+
+        assert_eq!(
+            state.get_model_candidates(),
+            vec!["gpt-5-mini", "gpt-5", "gpt-4.1-mini"]
+        );
+    }
+
+    #[test]
+    fn test_chain_invalid_when_new_messages_were_trimmed() {
+        let mut state = chaining_state();
+        add_turn(&mut state, "first", "resp_1");
+        for i in 0..=MAX_HISTORY_COUNT {
+            state.add_message(CHANNEL, ChatMessage::user(i.to_string(), "a".to_string()));
+        }
+        assert!(
+            state
+                .get_chained_input(CHANNEL, DEFAULT_MODEL, 10)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_trimming_the_history_ends_the_chain() {
+        let mut state = chaining_state();
+        for i in 0..MAX_HISTORY_COUNT - 2 {
+            state.add_message(CHANNEL, ChatMessage::user(i.to_string(), "a".to_string()));
+        }
+        add_turn(&mut state, "first", "resp_1");
+
+        // The new message is still in the history, but the trim forces the full history
+        let trimmed = state.add_message(
+            CHANNEL,
+            ChatMessage::user("new".to_string(), "a".to_string()),
+        );
+        assert!(!trimmed.is_empty());
+        assert!(
+            state
+                .get_chained_input(CHANNEL, DEFAULT_MODEL, 10)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_chain_not_used_when_history_exceeds_context() {
+        let mut state = chaining_state();
+        let model = "gpt-4o";
+        state.add_message(
+            CHANNEL,
+            ChatMessage::user("first".to_string(), "a".to_string()),
+        );
+        state.set_response_chain(CHANNEL, "resp_1".to_string(), model.to_string(), 0);
+        state.add_message(
+            CHANNEL,
+            ChatMessage::user("second".to_string(), "a".to_string()),
+        );
+        assert!(state.get_chained_input(CHANNEL, model, 10).is_some());
+
+        // Within the history limit, but more than the model can take
+        let long = "word ".repeat(98_000);
+        state.add_message(CHANNEL, ChatMessage::user(long, "a".to_string()));
+        assert_eq!(state.conversations[&CHANNEL].len(), 3);
+        assert!(state.get_chained_input(CHANNEL, model, 10).is_none());
+    }
+
+    #[test]
+    fn test_history_is_trimmed_in_chunks() {
+        let mut state = BotState::default();
+        for i in 0..MAX_HISTORY_COUNT {
+            state.add_message(CHANNEL, ChatMessage::user(i.to_string(), "a".to_string()));
+        }
+        assert_eq!(state.conversations[&CHANNEL].len(), MAX_HISTORY_COUNT);
+
+        // Going over the limit trims a whole chunk at once
+        let trimmed = state.add_message(
+            CHANNEL,
+            ChatMessage::user("new".to_string(), "a".to_string()),
+        );
+        assert_eq!(trimmed.len(), HISTORY_TRIM_CHUNK + 1);
+
+        // The start of the history then stays the same until the limit is hit again
+        let first = state.conversations[&CHANNEL][0].to_string();
+        for i in 0..HISTORY_TRIM_CHUNK {
+            state.add_message(CHANNEL, ChatMessage::user(i.to_string(), "a".to_string()));
+        }
+        assert_eq!(state.conversations[&CHANNEL].len(), MAX_HISTORY_COUNT);
+        assert_eq!(state.conversations[&CHANNEL][0].to_string(), first);
+    }
+
+    #[test]
+    fn test_history_is_trimmed_by_estimated_tokens() {
+        let mut state = BotState::default();
+        let big = "word ".repeat(MAX_HISTORY_TOKENS / 3);
+
+        state.add_message(
+            CHANNEL,
+            ChatMessage::user("small".to_string(), "a".to_string()),
+        );
+        state.add_message(CHANNEL, ChatMessage::user(big.clone(), "a".to_string()));
+        state.add_message(CHANNEL, ChatMessage::user(big.clone(), "a".to_string()));
+        assert_eq!(state.conversations[&CHANNEL].len(), 3);
+
+        // The fourth big message pushes out the oldest ones
+        let trimmed = state.add_message(CHANNEL, ChatMessage::user(big.clone(), "a".to_string()));
+        assert_eq!(trimmed.len(), 2);
+        let history = &state.conversations[&CHANNEL];
+        assert_eq!(history.len(), 2);
+        assert!(estimate_tokens(history) <= MAX_HISTORY_TOKENS - HISTORY_TRIM_CHUNK_TOKENS);
+
+        // A single message over the limit is still kept
+        let huge = "word ".repeat(MAX_HISTORY_TOKENS + 10);
+        state.add_message(CHANNEL, ChatMessage::user(huge, "a".to_string()));
+        assert_eq!(state.conversations[&CHANNEL].len(), 1);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::utils::openai_schema::ResponsesUsage;
+
+/// Price of a model in USD per 1M tokens
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPricing {
+    pub input: f64,
+    pub cached_input: f64,
+    pub output: f64,
+    pub reasoning: f64,
+}
+
+impl ModelPricing {
+    /// Reasoning tokens are billed as output tokens unless stated otherwise
+    const fn new(input: f64, cached_input: f64, output: f64) -> Self {
+        Self {
+            input,
+            cached_input,
+            output,
+            reasoning: output,
+        }
+    }
+}
+
+// Prices per 1M tokens, matched by the longest model name prefix
+// so dated snapshots (e.g. gpt-5-2025-08-07) use the price of their family
+const PRICING_TABLE: &[(&str, ModelPricing)] = &[
+    ("gpt-5", ModelPricing::new(1.25, 0.125, 10.0)),
+    ("gpt-5-mini", ModelPricing::new(0.25, 0.025, 2.0)),
+    ("gpt-5-nano", ModelPricing::new(0.05, 0.005, 0.4)),
+    // Pro models have no cached input discount
+    ("gpt-5-pro", ModelPricing::new(15.0, 15.0, 120.0)),
+    ("gpt-4.1", ModelPricing::new(2.0, 0.5, 8.0)),
+    ("gpt-4.1-mini", ModelPricing::new(0.4, 0.1, 1.6)),
+    ("gpt-4.1-nano", ModelPricing::new(0.1, 0.025, 0.4)),
+    ("gpt-4o", ModelPricing::new(2.5, 1.25, 10.0)),
+    ("gpt-4o-mini", ModelPricing::new(0.15, 0.075, 0.6)),
+    ("o3", ModelPricing::new(2.0, 0.5, 8.0)),
+    ("o3-mini", ModelPricing::new(1.1, 0.55, 4.4)),
+    ("o3-pro", ModelPricing::new(20.0, 20.0, 80.0)),
+    ("o4-mini", ModelPricing::new(1.1, 0.275, 4.4)),
+    // Image models, output is billed per image token
+    ("gpt-image-1", ModelPricing::new(5.0, 1.25, 40.0)),
+    ("gpt-image-1-mini", ModelPricing::new(2.0, 0.2, 8.0)),
+    // Transcription models, input is billed per audio token
+    ("gpt-4o-transcribe", ModelPricing::new(6.0, 6.0, 10.0)),
+    ("gpt-4o-mini-transcribe", ModelPricing::new(3.0, 3.0, 5.0)),
+];
+
+/// Find the pricing of a model
+pub fn get_model_pricing(model: &str) -> Option<ModelPricing> {
+    PRICING_TABLE
+        .iter()
+        .filter(|(name, _)| model == *name || model.starts_with(&format!("{name}-")))
+        .max_by_key(|(name, _)| name.len())
+        .map(|(_, pricing)| *pricing)
+}
+
+/// Calculate the cost of a response in USD, or None if the model has no known price
+pub fn calculate_cost(model: &str, usage: &ResponsesUsage) -> Option<f64> {
+    let pricing = get_model_pricing(model)?;
+
+    let cached_input = f64::from(usage.input_tokens_details.cached_tokens);
+    let uncached_input = f64::from(usage.input_tokens) - cached_input;
+    let reasoning = f64::from(usage.output_tokens_details.reasoning_tokens);
+    // Output tokens include the reasoning tokens
+    let visible_output = f64::from(usage.output_tokens) - reasoning;
+
+    let cost = uncached_input * pricing.input
+        + cached_input * pricing.cached_input
+        + visible_output * pricing.output
+        + reasoning * pricing.reasoning;
+
+    Some(cost / 1_000_000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::openai_schema::{InputTokensDetails, OutputTokensDetails};
+
+    fn usage(input: u32, cached: u32, output: u32, reasoning: u32) -> ResponsesUsage {
+        ResponsesUsage {
+            input_tokens: input,
+            input_tokens_details: InputTokensDetails {
+                cached_tokens: cached,
+            },
+            output_tokens: output,
+            output_tokens_details: OutputTokensDetails {
+                reasoning_tokens: reasoning,
+            },
+            total_tokens: input + output,
+        }
+    }
+
+    #[test]
+    fn test_get_model_pricing_prefers_longest_prefix() {
+        assert_eq!(get_model_pricing("gpt-5").unwrap().input, 1.25);
+        assert_eq!(get_model_pricing("gpt-5-mini").unwrap().input, 0.25);
+        assert_eq!(get_model_pricing("gpt-5-2025-08-07").unwrap().input, 1.25);
+        assert_eq!(
+            get_model_pricing("gpt-4.1-mini-2025-04-14").unwrap().input,
+            0.4
+        );
+        assert_eq!(get_model_pricing("o3").unwrap().output, 8.0);
+        assert!(get_model_pricing("gpt-50").is_none());
+        assert!(get_model_pricing("claude").is_none());
+    }
+
+    #[test]
+    fn test_pro_models_are_not_priced_as_their_base_model() {
+        for (pro, base) in [("o3-pro", "o3"), ("gpt-5-pro", "gpt-5")] {
+            let pro_pricing = get_model_pricing(pro).unwrap();
+            assert_ne!(pro_pricing, get_model_pricing(base).unwrap());
+            // Dated snapshots as well
+            assert_eq!(
+                get_model_pricing(&format!("{pro}-2025-06-10")),
+                Some(pro_pricing)
+            );
+        }
+        assert_eq!(get_model_pricing("o3-pro").unwrap().output, 80.0);
+    }
+
+    #[test]
+    fn test_calculate_cost() {
+        // 1M uncached input + 1M cached input + 1M output of which 1M reasoning
+        let cost = calculate_cost("gpt-5", &usage(2_000_000, 1_000_000, 1_000_000, 1_000_000));
+        assert!((cost.unwrap() - (1.25 + 0.125 + 10.0)).abs() < 1e-9);
+
+        let cost = calculate_cost("gpt-5-mini", &usage(1000, 0, 500, 0)).unwrap();
+        assert!((cost - (1000.0 * 0.25 + 500.0 * 2.0) / 1_000_000.0).abs() < 1e-12);
+
+        assert!(calculate_cost("unknown-model", &usage(1, 0, 1, 0)).is_none());
+    }
+}
@@ -0,0 +1,324 @@
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, UserId};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::Hash;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::utils::persistence::get_rate_limit_config;
+
+// Buckets are pruned once a map grows beyond this size
+const MAX_TRACKED_BUCKETS: usize = 1024;
+
+lazy_static! {
+    static ref RATE_LIMITER: Arc<Mutex<RateLimiter>> = Arc::new(Mutex::new(RateLimiter::default()));
+}
+
+/// Allow up to `capacity` mentions per `period_secs`, refilled continuously
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub capacity: u32,
+    pub period_secs: u64,
+}
+
+impl RateLimit {
+    fn refill_per_sec(&self) -> f64 {
+        f64::from(self.capacity) / self.period_secs.max(1) as f64
+    }
+}
+
+impl Display for RateLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}s", self.capacity, self.period_secs)
+    }
+}
+
+impl FromStr for RateLimit {
+    type Err = eyre::Report;
+
+    /// Parse "5/60" or "5/60s" as 5 mentions per 60 seconds
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || eyre::eyre!("Invalid rate limit: {s} (expected e.g. `5/60`)");
+        let (capacity, period) = s.trim().split_once('/').ok_or_else(invalid)?;
+        let capacity: u32 = capacity.trim().parse().map_err(|_| invalid())?;
+        let period_secs: u64 = period
+            .trim()
+            .trim_end_matches('s')
+            .parse()
+            .map_err(|_| invalid())?;
+        if capacity == 0 || period_secs == 0 {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            capacity,
+            period_secs,
+        })
+    }
+}
+
+/// Persistent rate limit configuration, None disables the limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    pub per_user: Option<RateLimit>,
+    pub per_channel: Option<RateLimit>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            per_user: Some(RateLimit {
+                capacity: 5,
+                period_secs: 60,
+            }),
+            per_channel: Some(RateLimit {
+                capacity: 15,
+                period_secs: 60,
+            }),
+        }
+    }
+}
+
+/// Result of a rate limit check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitDecision {
+    Allowed,
+    /// Limited, with the time until the next mention would be allowed
+    UserLimited(Duration),
+    ChannelLimited(Duration),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn full(limit: &RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: f64::from(limit.capacity),
+            last_refill: now,
+        }
+    }
+
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.last_refill)
+            .as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * limit.refill_per_sec()).min(f64::from(limit.capacity));
+        self.last_refill = now;
+    }
+
+    fn is_full(&self, limit: &RateLimit) -> bool {
+        self.tokens >= f64::from(limit.capacity)
+    }
+
+    /// Time until a whole token is available, or None if one is available now
+    fn wait_time(&self, limit: &RateLimit) -> Option<Duration> {
+        (self.tokens < 1.0)
+            .then(|| Duration::from_secs_f64((1.0 - self.tokens) / limit.refill_per_sec()))
+    }
+}
+
+/// Token buckets of users and channels
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    users: HashMap<UserId, TokenBucket>,
+    channels: HashMap<ChannelId, TokenBucket>,
+}
+
+/// Refill the bucket of a key and get the time to wait before it can be taken from
+fn refill_bucket<K: Eq + Hash>(
+    buckets: &mut HashMap<K, TokenBucket>,
+    key: K,
+    limit: &RateLimit,
+    now: Instant,
+) -> Option<Duration> {
+    let bucket = buckets
+        .entry(key)
+        .or_insert_with(|| TokenBucket::full(limit, now));
+    bucket.refill(limit, now);
+    bucket.wait_time(limit)
+}
+
+/// Drop buckets that are full again, as they behave the same as missing ones
+fn prune_buckets<K>(buckets: &mut HashMap<K, TokenBucket>, limit: &RateLimit, now: Instant) {
+    if buckets.len() > MAX_TRACKED_BUCKETS {
+        buckets.retain(|_, bucket| {
+            bucket.refill(limit, now);
+            !bucket.is_full(limit)
+        });
+    }
+}
+
+impl RateLimiter {
+    /// Check whether a mention is allowed, taking a token from both buckets if it is
+    /// No token is taken when either bucket is empty
+    pub fn check(
+        &mut self,
+        config: &RateLimitConfig,
+        user_id: UserId,
+        channel_id: ChannelId,
+        now: Instant,
+    ) -> RateLimitDecision {
+        if let Some(limit) = &config.per_user {
+            prune_buckets(&mut self.users, limit, now);
+            if let Some(wait) = refill_bucket(&mut self.users, user_id, limit, now) {
+                return RateLimitDecision::UserLimited(wait);
+            }
+        }
+        if let Some(limit) = &config.per_channel {
+            prune_buckets(&mut self.channels, limit, now);
+            if let Some(wait) = refill_bucket(&mut self.channels, channel_id, limit, now) {
+                return RateLimitDecision::ChannelLimited(wait);
+            }
+        }
+
+        if config.per_user.is_some() {
+            if let Some(bucket) = self.users.get_mut(&user_id) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        if config.per_channel.is_some() {
+            if let Some(bucket) = self.channels.get_mut(&channel_id) {
+                bucket.tokens -= 1.0;
+            }
+        }
+
+        RateLimitDecision::Allowed
+    }
+
+    /// Forget all buckets, e.g. after the configuration changed
+    pub fn clear(&mut self) {
+        self.users.clear();
+        self.channels.clear();
+    }
+}
+
+/// Check the global rate limiter for a mention by the given user in the given channel
+pub async fn check_rate_limit(user_id: UserId, channel_id: ChannelId) -> RateLimitDecision {
+    let config = get_rate_limit_config().await;
+    RATE_LIMITER
+        .lock()
+        .await
+        .check(&config, user_id, channel_id, Instant::now())
+}
+
+/// Reset the buckets of the global rate limiter
+pub async fn reset_rate_limiter() {
+    RATE_LIMITER.lock().await.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(user: Option<&str>, channel: Option<&str>) -> RateLimitConfig {
+        RateLimitConfig {
+            per_user: user.map(|s| s.parse().unwrap()),
+            per_channel: channel.map(|s| s.parse().unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_parse_rate_limit() {
+        assert_eq!(
+            "5/60".parse::<RateLimit>().unwrap(),
+            RateLimit {
+                capacity: 5,
+                period_secs: 60
+            }
+        );
+        assert_eq!("3/10s".parse::<RateLimit>().unwrap().to_string(), "3/10s");
+        assert!("0/60".parse::<RateLimit>().is_err());
+        assert!("5/0".parse::<RateLimit>().is_err());
+        assert!("5".parse::<RateLimit>().is_err());
+    }
+
+    #[test]
+    fn test_user_bucket_refills() {
+        let config = config(Some("2/10"), None);
+        let mut limiter = RateLimiter::default();
+        let user = UserId::new(1);
+        let channel = ChannelId::new(1);
+        let start = Instant::now();
+
+        assert_eq!(
+            limiter.check(&config, user, channel, start),
+            RateLimitDecision::Allowed
+        );
+        assert_eq!(
+            limiter.check(&config, user, channel, start),
+            RateLimitDecision::Allowed
+        );
+        let RateLimitDecision::UserLimited(wait) = limiter.check(&config, user, channel, start)
+        else {
+            panic!("third mention should be limited");
+        };
+        assert_eq!(wait.as_secs(), 5);
+
+        // Other users are not affected
+        assert_eq!(
+            limiter.check(&config, UserId::new(2), channel, start),
+            RateLimitDecision::Allowed
+        );
+
+        // One token is back after half the period
+        let later = start + Duration::from_secs(5);
+        assert_eq!(
+            limiter.check(&config, user, channel, later),
+            RateLimitDecision::Allowed
+        );
+        assert!(matches!(
+            limiter.check(&config, user, channel, later),
+            RateLimitDecision::UserLimited(_)
+        ));
+    }
+
+    #[test]
+    fn test_channel_limit_does_not_consume_user_tokens() {
+        let config = config(Some("2/60"), Some("1/60"));
+        let mut limiter = RateLimiter::default();
+        let user = UserId::new(1);
+        let now = Instant::now();
+
+        assert_eq!(
+            limiter.check(&config, UserId::new(2), ChannelId::new(1), now),
+            RateLimitDecision::Allowed
+        );
+        assert!(matches!(
+            limiter.check(&config, user, ChannelId::new(1), now),
+            RateLimitDecision::ChannelLimited(_)
+        ));
+
+        // The rejected mention didn't use up the user's tokens
+        assert_eq!(
+            limiter.check(&config, user, ChannelId::new(2), now),
+            RateLimitDecision::Allowed
+        );
+        assert_eq!(
+            limiter.check(&config, user, ChannelId::new(3), now),
+            RateLimitDecision::Allowed
+        );
+    }
+
+    #[test]
+    fn test_disabled_limits_allow_everything() {
+        let config = config(None, None);
+        let mut limiter = RateLimiter::default();
+        let now = Instant::now();
+
+        for _ in 0..100 {
+            assert_eq!(
+                limiter.check(&config, UserId::new(1), ChannelId::new(1), now),
+                RateLimitDecision::Allowed
+            );
+        }
+    }
+}
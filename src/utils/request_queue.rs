@@ -0,0 +1,299 @@
+use lazy_static::lazy_static;
+use serenity::model::id::{ChannelId, UserId};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+use crate::utils::conversation::ChatMessage;
+use crate::utils::image_store::PendingFiles;
+use crate::utils::msg_context::MsgContextInfo;
+
+// Global queue of mentions per channel
+lazy_static! {
+    static ref CHANNEL_QUEUE: ChannelQueue = ChannelQueue::default();
+    static ref PENDING_MENTIONS: MentionBatches<PendingMention> = MentionBatches::default();
+}
+
+/// A mention waiting to be answered
+#[derive(Debug, Clone)]
+pub struct PendingMention {
+    pub msg_ctx: MsgContextInfo,
+    pub message: ChatMessage,
+    /// Stored files of the message, deleted if it is dropped without being added
+    pub pending_files: PendingFiles,
+}
+
+/// Mentions of a single channel waiting for their turn, in arrival order
+#[derive(Default)]
+struct ChannelLine {
+    waiting: VecDeque<u64>,
+    next_ticket: u64,
+    notify: Arc<Notify>,
+}
+
+/// FIFO queues that let only one request per channel run at a time
+#[derive(Clone, Default)]
+pub struct ChannelQueue {
+    lines: Arc<Mutex<HashMap<ChannelId, ChannelLine>>>,
+}
+
+/// Place of a request in its channel's queue, which is left when dropped
+pub struct QueueTicket {
+    queue: ChannelQueue,
+    channel_id: ChannelId,
+    ticket: u64,
+    notify: Arc<Notify>,
+}
+
+impl ChannelQueue {
+    /// Take a place at the end of the channel's queue
+    /// This doesn't wait, so the order of calls is the order of processing
+    pub fn enqueue(&self, channel_id: ChannelId) -> QueueTicket {
+        let mut lines = self.lines.lock().unwrap();
+        let line = lines.entry(channel_id).or_default();
+        let ticket = line.next_ticket;
+        line.next_ticket += 1;
+        line.waiting.push_back(ticket);
+
+        QueueTicket {
+            queue: self.clone(),
+            channel_id,
+            ticket,
+            notify: line.notify.clone(),
+        }
+    }
+
+    fn is_first(&self, channel_id: ChannelId, ticket: u64) -> bool {
+        let lines = self.lines.lock().unwrap();
+        lines
+            .get(&channel_id)
+            .and_then(|line| line.waiting.front())
+            .is_some_and(|first| *first == ticket)
+    }
+
+    fn leave(&self, channel_id: ChannelId, ticket: u64) {
+        let mut lines = self.lines.lock().unwrap();
+        let Some(line) = lines.get_mut(&channel_id) else {
+            return;
+        };
+
+        line.waiting.retain(|t| *t != ticket);
+        line.notify.notify_waiters();
+        if line.waiting.is_empty() {
+            lines.remove(&channel_id);
+        }
+    }
+
+    /// Number of requests queued or running in a channel
+    pub fn len(&self, channel_id: ChannelId) -> usize {
+        let lines = self.lines.lock().unwrap();
+        lines.get(&channel_id).map_or(0, |line| line.waiting.len())
+    }
+
+    pub fn is_empty(&self, channel_id: ChannelId) -> bool {
+        self.len(channel_id) == 0
+    }
+}
+
+impl QueueTicket {
+    /// Wait until every request queued before this one in the channel is done
+    pub async fn wait_turn(&self) {
+        loop {
+            // Register for notifications before checking to not miss a wakeup
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.queue.is_first(self.channel_id, self.ticket) {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        self.queue.leave(self.channel_id, self.ticket);
+    }
+}
+
+/// Items waiting per channel, taken all at once by whoever gets to answer them
+pub struct MentionBatches<T> {
+    pending: Mutex<HashMap<ChannelId, Vec<T>>>,
+}
+
+impl<T> Default for MentionBatches<T> {
+    fn default() -> Self {
+        Self {
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<T> MentionBatches<T> {
+    /// Add an item to the channel's batch
+    pub fn push(&self, channel_id: ChannelId, item: T) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.entry(channel_id).or_default().push(item);
+    }
+
+    /// Take the items at the front of the channel's batch that belong together, in arrival order
+    /// The batch stops at the first item that doesn't, which stays for its own turn
+    /// Empty if the items were already taken together with an earlier one
+    pub fn take(&self, channel_id: ChannelId, belongs: impl Fn(&T) -> bool) -> Vec<T> {
+        let mut pending = self.pending.lock().unwrap();
+        let Some(items) = pending.get_mut(&channel_id) else {
+            return Vec::new();
+        };
+
+        let count = items.iter().take_while(|item| belongs(item)).count();
+        let taken = items.drain(..count).collect();
+        if items.is_empty() {
+            pending.remove(&channel_id);
+        }
+        taken
+    }
+}
+
+/// Take a place in the global queue of a channel
+pub fn enqueue_channel_request(channel_id: ChannelId) -> QueueTicket {
+    CHANNEL_QUEUE.enqueue(channel_id)
+}
+
+/// Add a mention to the global batch of its channel
+pub fn push_pending_mention(mention: PendingMention) {
+    PENDING_MENTIONS.push(mention.msg_ctx.channel_id, mention);
+}
+
+/// Take the mentions of an author waiting at the front of the global batch of a channel
+/// A mention of another author ends the batch, so each batch is answered with its author's
+/// permissions and budget, in arrival order
+pub fn take_pending_mentions(channel_id: ChannelId, author_id: UserId) -> Vec<PendingMention> {
+    PENDING_MENTIONS.take(channel_id, |mention| mention.msg_ctx.author.id == author_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_requests_run_in_arrival_order() {
+        let queue = ChannelQueue::default();
+        let channel = ChannelId::new(1);
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        let mut handles = Vec::new();
+        for i in 0..5u64 {
+            let ticket = queue.enqueue(channel);
+            let order = order.clone();
+            handles.push(tokio::spawn(async move {
+                // Later requests would finish first without the queue
+                tokio::time::sleep(Duration::from_millis(50 - i * 10)).await;
+                ticket.wait_turn().await;
+                order.lock().unwrap().push(format!("start {i}"));
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                order.lock().unwrap().push(format!("end {i}"));
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let expected: Vec<String> = (0..5)
+            .flat_map(|i| [format!("start {i}"), format!("end {i}")])
+            .collect();
+        assert_eq!(*order.lock().unwrap(), expected);
+        assert!(queue.is_empty(channel));
+    }
+
+    #[tokio::test]
+    async fn test_channels_do_not_block_each_other() {
+        let queue = ChannelQueue::default();
+        let busy = queue.enqueue(ChannelId::new(1));
+        busy.wait_turn().await;
+
+        let other = queue.enqueue(ChannelId::new(2));
+        tokio::time::timeout(Duration::from_secs(1), other.wait_turn())
+            .await
+            .expect("another channel should not wait");
+    }
+
+    #[tokio::test]
+    async fn test_dropped_ticket_leaves_queue() {
+        let queue = ChannelQueue::default();
+        let channel = ChannelId::new(1);
+
+        let first = queue.enqueue(channel);
+        let second = queue.enqueue(channel);
+        let third = queue.enqueue(channel);
+        assert_eq!(queue.len(channel), 3);
+
+        // Leaving from the middle doesn't disturb the order
+        drop(second);
+        first.wait_turn().await;
+
+        let waiter = tokio::spawn(async move {
+            third.wait_turn().await;
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        drop(first);
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("third request should run after the first")
+            .unwrap();
+        assert!(queue.is_empty(channel));
+    }
+
+    #[tokio::test]
+    async fn test_mentions_queued_during_a_request_are_batched() {
+        let queue = ChannelQueue::default();
+        let batches = Arc::new(MentionBatches::default());
+        let channel = ChannelId::new(1);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        // Push a mention and take the author's batch once it's this mention's turn
+        let mention = |author: char, text: &'static str| {
+            let ticket = queue.enqueue(channel);
+            batches.push(channel, (author, text));
+            let batches = batches.clone();
+            let requests = requests.clone();
+            tokio::spawn(async move {
+                ticket.wait_turn().await;
+                let batch: Vec<_> = batches
+                    .take(channel, |(other, _)| *other == author)
+                    .into_iter()
+                    .map(|(_, text)| text)
+                    .collect();
+                if !batch.is_empty() {
+                    requests.lock().unwrap().push(batch);
+                    // The request is in flight for a while
+                    tokio::time::sleep(Duration::from_millis(30)).await;
+                }
+            })
+        };
+
+        let first = mention('x', "a");
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let handles = [
+            first,
+            mention('x', "b"),
+            mention('x', "c"),
+            mention('y', "d"),
+            mention('x', "e"),
+        ];
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        // Another author's mention ends the batch without changing the order
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![vec!["a"], vec!["b", "c"], vec!["d"], vec!["e"]]
+        );
+        assert!(queue.is_empty(channel));
+    }
+}
@@ -0,0 +1,347 @@
+use serenity::all::{
+    Command, CommandInteraction, CommandOptionType, CreateAutocompleteResponse, CreateCommand,
+    CreateCommandOption, CreateInteractionResponse, Interaction,
+};
+use serenity::prelude::Context;
+use std::sync::atomic::{AtomicBool, Ordering};
+use strum::IntoEnumIterator;
+
+#[cfg(test)]
+use crate::utils::admin_commands::parse_admin_command;
+use crate::utils::admin_commands::{AdminCommand, CommandOutput, is_admin, run_admin_command};
+use crate::utils::msg_context::MsgContextInfo;
+use crate::utils::persistence::BotPersonality;
+use crate::utils::request_queue::enqueue_channel_request;
+
+// Discord shows at most 25 autocomplete choices
+const MAX_AUTOCOMPLETE_CHOICES: usize = 25;
+
+// `ready` is sent again on every reconnect, the commands only need to be registered once
+static COMMANDS_REGISTERED: AtomicBool = AtomicBool::new(false);
+
+/// A slash command anyone may use, handled like its text command
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserCommand {
+    Draw(String),
+    Continue,
+}
+
+/// An admin command that takes the same arguments as its text command
+fn args_command(name: &str, description: &str, usage: &str) -> CreateCommand {
+    CreateCommand::new(name)
+        .description(description)
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::String,
+            "args",
+            usage,
+        ))
+}
+
+/// Definitions of every slash command
+fn slash_commands() -> Vec<CreateCommand> {
+    vec![
+        CreateCommand::new("forget").description("Clear the conversation history of this channel"),
+        CreateCommand::new("model")
+            .description("Change the model used to answer")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "name", "Model name")
+                    .required(true),
+            ),
+        CreateCommand::new("status").description("Show the bot status"),
+        CreateCommand::new("personality")
+            .description("Show or change the personality of this channel")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "name",
+                    "Personality name, or `custom <system prompt>`",
+                )
+                .set_autocomplete(true),
+            ),
+        CreateCommand::new("dev")
+            .description("Add a developer message to the conversation history")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "message", "Developer message")
+                    .required(true),
+            ),
+        args_command(
+            "usage",
+            "Show the token usage and cost",
+            "`[today|week|month]`",
+        ),
+        args_command(
+            "budget",
+            "Show or change the spending limits",
+            "`set <scope> daily|monthly <limit>`, `reset <scope> [period]` or `remove <scope> [period]`",
+        ),
+        args_command(
+            "ratelimit",
+            "Show or change the mention rate limit",
+            "`user|channel <count>/<seconds>` or `user|channel off`",
+        ),
+        args_command(
+            "tools",
+            "Show or toggle the tools the model can use",
+            "`enable|disable <tool>`",
+        ),
+        args_command(
+            "moderation",
+            "Show or change the content moderation of this server",
+            "`openai|wordlist [threshold] [block|warn|log]`, `off` or `words ...`",
+        ),
+        args_command(
+            "routing",
+            "Show or change the model routing of this channel",
+            "`on [light model]` or `off`",
+        ),
+        args_command("chaining", "Show or change response chaining", "`on|off`"),
+        args_command(
+            "fallback",
+            "Show or change the fallback models",
+            "`model1, model2, ...` or `none`",
+        ),
+        args_command(
+            "batching",
+            "Show or change mention batching in this channel",
+            "`on|off`",
+        ),
+        args_command(
+            "reasoning",
+            "Show or change reasoning summaries",
+            "`on|off`",
+        ),
+        args_command(
+            "autocontinue",
+            "Show or change how often cut off replies are continued",
+            "`<rounds>` (0 to disable)",
+        ),
+        args_command(
+            "transcripts",
+            "Show or change voice transcript replies",
+            "`on|off`",
+        ),
+        args_command(
+            "images",
+            "Show or change the images sent per message",
+            "`<count>` (0 to ignore images)",
+        ),
+        args_command(
+            "directives",
+            "Show or change which directives only admins may use",
+            "`admin|everyone <model|ephemeral|nomemory>`",
+        ),
+        args_command(
+            "language",
+            "Show or change the language of this server",
+            "`ko|en`",
+        ),
+        args_command("replyping", "Show or change reply pings", "`on|off`"),
+        CreateCommand::new("draw")
+            .description("Draw an image")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "prompt", "What to draw")
+                    .required(true),
+            ),
+        CreateCommand::new("continue").description("Continue the last reply where it was cut off"),
+    ]
+}
+
+/// Register the slash commands, replacing the ones from a previous version
+/// Only done once per run
+pub async fn register_slash_commands(ctx: &Context) -> eyre::Result<()> {
+    if COMMANDS_REGISTERED.swap(true, Ordering::SeqCst) {
+        return Ok(());
+    }
+
+    match Command::set_global_commands(&ctx.http, slash_commands()).await {
+        Ok(registered) => {
+            tracing::info!("Registered {} slash commands", registered.len());
+            Ok(())
+        }
+        Err(e) => {
+            // Try again on the next `ready`
+            COMMANDS_REGISTERED.store(false, Ordering::SeqCst);
+            Err(e.into())
+        }
+    }
+}
+
+/// Handle a slash command or an autocomplete request
+pub async fn handle_interaction(ctx: &Context, interaction: Interaction) {
+    match interaction {
+        Interaction::Command(command) => handle_slash_command(ctx, &command).await,
+        Interaction::Autocomplete(command) => handle_autocomplete(ctx, &command).await,
+        _ => {}
+    }
+}
+
+/// Run a slash command with the same handler as its text command
+async fn handle_slash_command(ctx: &Context, interaction: &CommandInteraction) {
+    let Some(command) = to_admin_command(interaction) else {
+        tracing::warn!("Unknown slash command: {}", interaction.data.name);
+        return;
+    };
+
+    // Admin output is only shown to the user, and the handler may take a while
+    if let Err(e) = interaction.defer_ephemeral(&ctx.http).await {
+        tracing::error!("Failed to defer slash command: {:?}", e);
+        return;
+    }
+
+    let output = CommandOutput::Interaction(interaction);
+    if !is_admin(interaction.user.id) {
+        let _ = output.say(ctx, "You are not admin. Request denied.").await;
+        return;
+    }
+
+    // Wait for the replies in progress, like the text commands
+    let ticket = enqueue_channel_request(interaction.channel_id);
+    ticket.wait_turn().await;
+
+    let msg_ctx = MsgContextInfo::from_interaction(ctx, interaction).await;
+    run_admin_command(ctx, &msg_ctx, &output, command).await;
+}
+
+/// Get the trimmed value of a string option of a slash command
+fn string_option(interaction: &CommandInteraction, name: &str) -> Option<String> {
+    interaction
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_str())
+        .map(|value| value.trim().to_string())
+}
+
+/// Get the user command of a slash command
+pub fn to_user_command(interaction: &CommandInteraction) -> Option<UserCommand> {
+    match interaction.data.name.as_str() {
+        "draw" => Some(UserCommand::Draw(
+            string_option(interaction, "prompt").unwrap_or_default(),
+        )),
+        "continue" => Some(UserCommand::Continue),
+        _ => None,
+    }
+}
+
+/// Get the admin command of a slash command
+fn to_admin_command(interaction: &CommandInteraction) -> Option<AdminCommand> {
+    admin_command(&interaction.data.name, |name| {
+        string_option(interaction, name)
+    })
+}
+
+/// Get the admin command of a slash command by its name and options
+fn admin_command(name: &str, option: impl Fn(&str) -> Option<String>) -> Option<AdminCommand> {
+    let args = || option("args").unwrap_or_default();
+
+    match name {
+        "forget" => Some(AdminCommand::Forget),
+        "model" => Some(AdminCommand::Model(option("name").unwrap_or_default())),
+        "status" => Some(AdminCommand::Status),
+        "personality" => match option("name") {
+            Some(name) if !name.is_empty() => Some(AdminCommand::SetPersonality(name)),
+            _ => Some(AdminCommand::GetPersonality),
+        },
+        "dev" => Some(AdminCommand::DevMessage(
+            option("message").unwrap_or_default(),
+        )),
+        "usage" => Some(AdminCommand::Usage(args())),
+        "budget" => Some(AdminCommand::Budget(args())),
+        "ratelimit" => Some(AdminCommand::RateLimit(args())),
+        "tools" => match args() {
+            args if args.is_empty() => Some(AdminCommand::GetTools),
+            args => Some(AdminCommand::SetTool(args)),
+        },
+        "moderation" => Some(AdminCommand::Moderation(args())),
+        "routing" => Some(AdminCommand::Routing(args())),
+        "chaining" => Some(AdminCommand::Chaining(args())),
+        "fallback" => Some(AdminCommand::Fallback(args())),
+        "batching" => Some(AdminCommand::Batching(args())),
+        "reasoning" => Some(AdminCommand::Reasoning(args())),
+        "autocontinue" => Some(AdminCommand::AutoContinue(args())),
+        "transcripts" => Some(AdminCommand::Transcripts(args())),
+        "images" => Some(AdminCommand::Images(args())),
+        "directives" => Some(AdminCommand::Directives(args())),
+        "language" => Some(AdminCommand::Language(args())),
+        "replyping" => Some(AdminCommand::ReplyPing(args())),
+        _ => None,
+    }
+}
+
+/// Suggest personalities while the name is being typed
+async fn handle_autocomplete(ctx: &Context, interaction: &CommandInteraction) {
+    let Some(focused) = interaction.data.autocomplete() else {
+        return;
+    };
+    if interaction.data.name != "personality" {
+        return;
+    }
+
+    let choices = personality_choices(focused.value)
+        .into_iter()
+        .fold(CreateAutocompleteResponse::new(), |response, name| {
+            response.add_string_choice(name.clone(), name)
+        });
+    let response = CreateInteractionResponse::Autocomplete(choices);
+    if let Err(e) = interaction.create_response(&ctx.http, response).await {
+        tracing::error!("Failed to send autocomplete choices: {:?}", e);
+    }
+}
+
+/// Names of the predefined personalities containing the typed text
+fn personality_choices(typed: &str) -> Vec<String> {
+    let typed = typed.trim().to_lowercase();
+    BotPersonality::iter()
+        .filter(|p| !matches!(p, BotPersonality::Custom(_)))
+        .map(|p| p.to_string())
+        .filter(|name| name.to_lowercase().contains(&typed))
+        .take(MAX_AUTOCOMPLETE_CHOICES)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_personality_choices() {
+        assert_eq!(
+            personality_choices(""),
+            vec!["Normal", "Tsundere", "Girlfriend", "SoftwareNerd"]
+        );
+        assert_eq!(personality_choices(" nerd"), vec!["SoftwareNerd"]);
+        assert!(personality_choices("custom").is_empty());
+    }
+
+    #[test]
+    fn test_every_admin_command_has_a_slash_command() {
+        let names: Vec<String> = slash_commands()
+            .iter()
+            .map(|command| serde_json::to_value(command).unwrap()["name"].to_string())
+            .map(|name| name.trim_matches('"').to_string())
+            .collect();
+
+        let mut mapped = Vec::new();
+        for name in &names {
+            for args in ["", "x"] {
+                let Some(command) = admin_command(name, |_| Some(args.to_string())) else {
+                    continue;
+                };
+                // The slash command runs the same command as the text command
+                // e.g. `<forget> x` isn't a text command, but `/forget` has no arguments
+                if let Some(text) = parse_admin_command(format!("<{name}> {args}").trim()) {
+                    assert_eq!(format!("{command:?}"), format!("{text:?}"));
+                }
+                mapped.push(std::mem::discriminant(&command));
+            }
+        }
+
+        for command in AdminCommand::iter() {
+            assert!(
+                mapped.contains(&std::mem::discriminant(&command)),
+                "{command:?} has no slash command"
+            );
+        }
+    }
+}
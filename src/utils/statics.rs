@@ -50,10 +50,50 @@ lazy_static! {
             .trim_end()
             .to_string()
     });
+    // Lets a local stand-in server replace the OpenAI API
+    pub static ref OPENAI_BASE_URL: Arc<String> = Arc::new(
+        env::var("MINTYBOT_OPENAI_BASE_URL")
+            .map(|url| url.trim_end().trim_end_matches('/').to_string())
+            .unwrap_or_else(|_| "https://api.openai.com/v1".to_string())
+    );
     pub static ref OPENAI_TOKEN: Arc<String> = Arc::new({
         env::var("MINTYBOT_OPENAI_TOKEN")
             .expect("MINTYBOT_OPENAI_TOKEN environment variable must be set")
             .trim_end()
             .to_string()
     });
+    // Speech-to-text endpoint, any OpenAI compatible transcription API works
+    pub static ref STT_BASE_URL: Arc<String> = Arc::new(
+        env::var("MINTYBOT_STT_BASE_URL")
+            .map(|url| url.trim_end().trim_end_matches('/').to_string())
+            .unwrap_or_else(|_| OPENAI_BASE_URL.to_string())
+    );
+    pub static ref STT_TOKEN: Arc<String> = Arc::new(
+        env::var("MINTYBOT_STT_TOKEN")
+            .map(|token| token.trim_end().to_string())
+            .unwrap_or_else(|_| OPENAI_TOKEN.to_string())
+    );
+    pub static ref STT_MODEL: Arc<String> = Arc::new(
+        env::var("MINTYBOT_STT_MODEL")
+            .map(|model| model.trim().to_string())
+            .unwrap_or_else(|_| "gpt-4o-mini-transcribe".to_string())
+    );
+    // Longest side of images kept in the data directory, larger ones are downscaled
+    pub static ref IMAGE_MAX_SIDE: u32 = env::var("MINTYBOT_IMAGE_MAX_SIDE")
+        .ok()
+        .and_then(|side| side.trim().parse().ok())
+        .filter(|side| *side > 0)
+        .unwrap_or(1024);
+    // Failed OpenAI requests in a row after which requests are paused
+    pub static ref BREAKER_FAILURE_THRESHOLD: u32 = env::var("MINTYBOT_BREAKER_THRESHOLD")
+        .ok()
+        .and_then(|threshold| threshold.trim().parse().ok())
+        .filter(|threshold| *threshold > 0)
+        .unwrap_or(5);
+    // Seconds to wait before probing a backend that was failing
+    pub static ref BREAKER_COOLDOWN_SECS: u64 = env::var("MINTYBOT_BREAKER_COOLDOWN_SECS")
+        .ok()
+        .and_then(|secs| secs.trim().parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(60);
 }
@@ -0,0 +1,91 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+/// A request received by the stand-in server
+pub struct RecordedRequest {
+    /// e.g. "POST /v1/images/generations HTTP/1.1"
+    pub request_line: String,
+    pub headers: String,
+    pub body: Vec<u8>,
+}
+
+/// Local HTTP server standing in for an external API in tests
+/// It answers a single request with a fixed response
+pub struct StandInServer {
+    pub base_url: String,
+    handle: JoinHandle<RecordedRequest>,
+}
+
+impl StandInServer {
+    pub async fn start(status: u16, content_type: &'static str, body: impl Into<Vec<u8>>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let body = body.into();
+
+        let handle = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let request = read_request(&mut stream).await;
+
+            let head = format!(
+                "HTTP/1.1 {status} Stand-In\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                body.len()
+            );
+            stream.write_all(head.as_bytes()).await.unwrap();
+            stream.write_all(&body).await.unwrap();
+            stream.shutdown().await.unwrap();
+
+            request
+        });
+
+        Self { base_url, handle }
+    }
+
+    /// Get the request the server received
+    pub async fn request(self) -> RecordedRequest {
+        self.handle.await.unwrap()
+    }
+}
+
+async fn read_request(stream: &mut tokio::net::TcpStream) -> RecordedRequest {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    // Read until the end of the headers
+    let header_end = loop {
+        let read = stream.read(&mut chunk).await.unwrap();
+        assert!(
+            read > 0,
+            "connection closed before the request was complete"
+        );
+        buffer.extend_from_slice(&chunk[..read]);
+        if let Some(pos) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let (request_line, headers) = head.split_once("\r\n").unwrap_or((&head, ""));
+    let content_length = headers
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse::<usize>().ok())
+                .flatten()
+        })
+        .unwrap_or(0);
+
+    // Read the rest of the body
+    while buffer.len() < header_end + content_length {
+        let read = stream.read(&mut chunk).await.unwrap();
+        assert!(read > 0, "connection closed before the body was complete");
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+
+    RecordedRequest {
+        request_line: request_line.to_string(),
+        headers: headers.to_string(),
+        body: buffer[header_end..header_end + content_length].to_vec(),
+    }
+}
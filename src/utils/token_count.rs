@@ -0,0 +1,154 @@
+use tiktoken_rs::o200k_base_singleton;
+
+use crate::utils::conversation::ChatMessage;
+use crate::utils::openai_schema::ContentItem;
+
+// Rough costs of content that isn't text, the real cost depends on its size
+// A 1024x1024 image in high detail is 765 tokens
+const IMAGE_TOKENS: usize = 765;
+const FILE_TOKENS: usize = 2_000;
+// Role and separators added to every message
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+// Room left for the reply when fitting a request into the context window
+const RESERVED_OUTPUT_TOKENS: usize = 32_000;
+const DEFAULT_CONTEXT_WINDOW: usize = 128_000;
+
+// Context windows, matched by the longest model name prefix like the pricing table
+const CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-5", 400_000),
+    ("gpt-4.1", 1_000_000),
+    ("gpt-4o", 128_000),
+    ("o3", 200_000),
+    ("o4-mini", 200_000),
+];
+
+/// Count the tokens of a text with the o200k tokenizer used by current models
+pub fn count_text_tokens(text: &str) -> usize {
+    o200k_base_singleton().encode_ordinary(text).len()
+}
+
+/// Estimate the input tokens of a single message
+pub fn estimate_message_tokens(message: &ChatMessage) -> usize {
+    let content_tokens: usize = message
+        .content
+        .iter()
+        .map(|item| match item {
+            ContentItem::InputText { text } | ContentItem::OutputText { text } => {
+                count_text_tokens(text)
+            }
+            ContentItem::Refusal { refusal } => count_text_tokens(refusal),
+            ContentItem::InputImage { .. } => IMAGE_TOKENS,
+            ContentItem::InputFile { .. } => FILE_TOKENS,
+            ContentItem::Other => 0,
+        })
+        .sum();
+
+    content_tokens + MESSAGE_OVERHEAD_TOKENS
+}
+
+/// Estimate the input tokens of a list of messages
+pub fn estimate_tokens<'a>(messages: impl IntoIterator<Item = &'a ChatMessage>) -> usize {
+    messages.into_iter().map(estimate_message_tokens).sum()
+}
+
+/// Get the context window of a model in tokens
+pub fn context_window(model: &str) -> usize {
+    CONTEXT_WINDOWS
+        .iter()
+        .filter(|(name, _)| model == *name || model.starts_with(&format!("{name}-")))
+        .max_by_key(|(name, _)| name.len())
+        .map_or(DEFAULT_CONTEXT_WINDOW, |(_, window)| *window)
+}
+
+/// Get how many input tokens a request to a model may use
+pub fn input_token_limit(model: &str) -> usize {
+    context_window(model).saturating_sub(RESERVED_OUTPUT_TOKENS)
+}
+
+/// Drop the oldest messages until the estimate fits in `max_tokens`
+/// Messages are dropped in multiples of `drop_chunk`, so the start of the request stays
+/// the same over the next turns and can be read from the prompt cache
+/// The first (system prompt) and last (newest) messages are always kept
+/// Returns the messages to send and how many were dropped
+pub fn fit_to_token_limit(
+    mut messages: Vec<ChatMessage>,
+    max_tokens: usize,
+    drop_chunk: usize,
+) -> (Vec<ChatMessage>, usize) {
+    let mut total = estimate_tokens(&messages);
+    let mut drop_count = 0;
+
+    while total > max_tokens && messages.len() > 2 + drop_count {
+        total -= estimate_message_tokens(&messages[1 + drop_count]);
+        drop_count += 1;
+    }
+    if drop_count > 0 {
+        drop_count = drop_count
+            .next_multiple_of(drop_chunk.max(1))
+            .min(messages.len() - 2);
+    }
+
+    messages.drain(1..1 + drop_count);
+    (messages, drop_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_message_tokens() {
+        assert_eq!(count_text_tokens("hello world"), 2);
+
+        let text = ChatMessage::assistant("hello world".to_string());
+        assert_eq!(estimate_message_tokens(&text), 2 + MESSAGE_OVERHEAD_TOKENS);
+
+        let with_images = ChatMessage::user_with_images(
+            String::new(),
+            "a".to_string(),
+            vec!["https://a.png".to_string(), "https://b.png".to_string()],
+        );
+        assert!(estimate_message_tokens(&with_images) > 2 * IMAGE_TOKENS);
+    }
+
+    #[test]
+    fn test_context_window() {
+        assert_eq!(context_window("gpt-5-mini"), 400_000);
+        assert_eq!(context_window("gpt-4.1-nano-2025-04-14"), 1_000_000);
+        assert_eq!(context_window("unknown"), DEFAULT_CONTEXT_WINDOW);
+        assert_eq!(
+            input_token_limit("gpt-4o"),
+            128_000 - RESERVED_OUTPUT_TOKENS
+        );
+    }
+
+    #[test]
+    fn test_fit_to_token_limit_keeps_system_prompt_and_newest() {
+        let messages: Vec<ChatMessage> =
+            std::iter::once(ChatMessage::developer("system".to_string()))
+                .chain((0..10).map(|i| ChatMessage::user(format!("message {i}"), "a".to_string())))
+                .collect();
+        let total = estimate_tokens(&messages);
+
+        let (kept, dropped) = fit_to_token_limit(messages.clone(), total, 1);
+        assert_eq!((kept.len(), dropped), (11, 0));
+
+        // Dropping three messages makes room
+        let limit = total - 2 * estimate_message_tokens(&messages[1]) - 1;
+        let (kept, dropped) = fit_to_token_limit(messages.clone(), limit, 1);
+        assert_eq!(dropped, 3);
+        assert_eq!(kept[0].to_string(), messages[0].to_string());
+        assert_eq!(kept[1].to_string(), messages[4].to_string());
+        assert!(estimate_tokens(&kept) <= limit);
+
+        // In chunks of four, a whole chunk is dropped
+        let (kept, dropped) = fit_to_token_limit(messages.clone(), limit, 4);
+        assert_eq!(dropped, 4);
+        assert_eq!(kept[1].to_string(), messages[5].to_string());
+
+        // Nothing but the system prompt and the newest message is left
+        let (kept, dropped) = fit_to_token_limit(messages.clone(), 0, 4);
+        assert_eq!((kept.len(), dropped), (2, 9));
+        assert_eq!(kept[1].to_string(), messages[10].to_string());
+    }
+}
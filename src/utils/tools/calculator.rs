@@ -0,0 +1,409 @@
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::utils::openai_schema::FunctionTool;
+
+pub const FUNCTION_NAME: &str = "calculate";
+
+// Limits to keep evaluation cheap
+const MAX_EXPRESSION_LENGTH: usize = 500;
+const MAX_EXPONENT: i128 = 64;
+const APPROXIMATE_DIGITS: usize = 10;
+
+#[derive(Debug, Deserialize)]
+struct CalculatorArgs {
+    expression: String,
+}
+
+pub fn definition() -> FunctionTool {
+    FunctionTool::new(
+        FUNCTION_NAME,
+        "Evaluate an arithmetic expression exactly. Supports + - * / % ^, parentheses \
+         and decimal numbers. Always use this for arithmetic such as splitting a bill.",
+        json!({
+            "type": "object",
+            "properties": {
+                "expression": {
+                    "type": "string",
+                    "description": "Arithmetic expression such as '(32000 + 18500) / 3'"
+                }
+            },
+            "required": ["expression"]
+        }),
+    )
+}
+
+pub fn call(arguments: &str) -> eyre::Result<String> {
+    let args: CalculatorArgs = serde_json::from_str(arguments)?;
+    let result = evaluate(&args.expression)?;
+
+    Ok(json!({
+        "expression": args.expression,
+        "result": result.format()?,
+    })
+    .to_string())
+}
+
+/// Evaluate an arithmetic expression using exact rational arithmetic
+fn evaluate(expression: &str) -> eyre::Result<Rational> {
+    if expression.len() > MAX_EXPRESSION_LENGTH {
+        return Err(eyre::eyre!(
+            "Expression is too long (max {MAX_EXPRESSION_LENGTH} characters)"
+        ));
+    }
+
+    let mut parser = Parser {
+        chars: expression.chars().collect(),
+        pos: 0,
+    };
+    let value = parser.parse_expression()?;
+
+    parser.skip_whitespace();
+    if let Some(c) = parser.peek() {
+        return Err(eyre::eyre!("Unexpected character '{c}'"));
+    }
+
+    Ok(value)
+}
+
+/// Exact fraction with a positive denominator, always kept in lowest terms
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rational {
+    num: i128,
+    den: i128,
+}
+
+fn gcd(a: i128, b: i128) -> eyre::Result<i128> {
+    // i128::MIN has no positive counterpart
+    let mut a = a.checked_abs().ok_or_else(overflow)?;
+    let mut b = b.checked_abs().ok_or_else(overflow)?;
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    Ok(a)
+}
+
+fn overflow() -> eyre::Report {
+    eyre::eyre!("Number is too large")
+}
+
+impl Rational {
+    fn new(num: i128, den: i128) -> eyre::Result<Self> {
+        if den == 0 {
+            return Err(eyre::eyre!("Division by zero"));
+        }
+        let divisor = gcd(num, den)?.max(1);
+        let sign = if den < 0 { -1 } else { 1 };
+        Ok(Self {
+            num: sign * num / divisor,
+            den: sign * den / divisor,
+        })
+    }
+
+    fn integer(value: i128) -> Self {
+        Self { num: value, den: 1 }
+    }
+
+    fn add(self, other: Self) -> eyre::Result<Self> {
+        let num = self
+            .num
+            .checked_mul(other.den)
+            .and_then(|a| {
+                other
+                    .num
+                    .checked_mul(self.den)
+                    .and_then(|b| a.checked_add(b))
+            })
+            .ok_or_else(overflow)?;
+        let den = self.den.checked_mul(other.den).ok_or_else(overflow)?;
+        Self::new(num, den)
+    }
+
+    fn neg(self) -> Self {
+        Self {
+            num: -self.num,
+            den: self.den,
+        }
+    }
+
+    fn sub(self, other: Self) -> eyre::Result<Self> {
+        self.add(other.neg())
+    }
+
+    fn mul(self, other: Self) -> eyre::Result<Self> {
+        let num = self.num.checked_mul(other.num).ok_or_else(overflow)?;
+        let den = self.den.checked_mul(other.den).ok_or_else(overflow)?;
+        Self::new(num, den)
+    }
+
+    fn div(self, other: Self) -> eyre::Result<Self> {
+        if other.num == 0 {
+            return Err(eyre::eyre!("Division by zero"));
+        }
+        self.mul(Self {
+            num: other.den,
+            den: other.num,
+        })
+    }
+
+    /// Remainder with the sign of the divisor, like Python's %
+    fn rem(self, other: Self) -> eyre::Result<Self> {
+        let quotient = self.div(other)?;
+        let floored = Self::integer(quotient.num.div_euclid(quotient.den));
+        self.sub(other.mul(floored)?)
+    }
+
+    fn pow(self, exponent: Self) -> eyre::Result<Self> {
+        if exponent.den != 1 {
+            return Err(eyre::eyre!("Only integer exponents are supported"));
+        }
+        if exponent.num.abs() > MAX_EXPONENT {
+            return Err(eyre::eyre!("Exponent is too large (max {MAX_EXPONENT})"));
+        }
+
+        let mut result = Self::integer(1);
+        for _ in 0..exponent.num.abs() {
+            result = result.mul(self)?;
+        }
+
+        if exponent.num < 0 {
+            Self::integer(1).div(result)
+        } else {
+            Ok(result)
+        }
+    }
+
+    /// Whether the decimal expansion terminates (denominator is 2^a * 5^b)
+    fn is_terminating(&self) -> bool {
+        let mut den = self.den;
+        for factor in [2, 5] {
+            while den % factor == 0 {
+                den /= factor;
+            }
+        }
+        den == 1
+    }
+
+    /// Format as a decimal number with at most `max_digits` fractional digits
+    fn to_decimal(self, max_digits: usize) -> eyre::Result<String> {
+        let sign = if self.num < 0 { "-" } else { "" };
+        let num = self.num.checked_abs().ok_or_else(overflow)?;
+        let integer_part = num / self.den;
+        let mut remainder = num % self.den;
+
+        let mut digits = String::new();
+        while remainder != 0 && digits.len() < max_digits {
+            remainder = remainder.checked_mul(10).ok_or_else(overflow)?;
+            digits.push(char::from(b'0' + (remainder / self.den) as u8));
+            remainder %= self.den;
+        }
+
+        if digits.is_empty() {
+            Ok(format!("{sign}{integer_part}"))
+        } else {
+            Ok(format!("{sign}{integer_part}.{digits}"))
+        }
+    }
+
+    /// Format for the result, as a fraction with an approximation if the decimal repeats
+    fn format(self) -> eyre::Result<String> {
+        if self.is_terminating() {
+            // Terminating decimals are printed exactly
+            self.to_decimal(usize::MAX)
+        } else {
+            Ok(format!(
+                "{}/{} (≈ {})",
+                self.num,
+                self.den,
+                self.to_decimal(APPROXIMATE_DIGITS)?
+            ))
+        }
+    }
+}
+
+/// Recursive descent parser for arithmetic expressions
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Consume the next non-whitespace character if it is one of `ops`
+    fn next_operator(&mut self, ops: &[char]) -> Option<char> {
+        self.skip_whitespace();
+        let c = self.peek()?;
+        let normalized = match c {
+            '×' => '*',
+            '÷' => '/',
+            _ => c,
+        };
+        if ops.contains(&normalized) {
+            self.pos += 1;
+            Some(normalized)
+        } else {
+            None
+        }
+    }
+
+    // expression := term (('+' | '-') term)*
+    fn parse_expression(&mut self) -> eyre::Result<Rational> {
+        let mut value = self.parse_term()?;
+        while let Some(op) = self.next_operator(&['+', '-']) {
+            let rhs = self.parse_term()?;
+            value = match op {
+                '+' => value.add(rhs)?,
+                _ => value.sub(rhs)?,
+            };
+        }
+        Ok(value)
+    }
+
+    // term := unary (('*' | '/' | '%') unary)*
+    fn parse_term(&mut self) -> eyre::Result<Rational> {
+        let mut value = self.parse_unary()?;
+        while let Some(op) = self.next_operator(&['*', '/', '%']) {
+            let rhs = self.parse_unary()?;
+            value = match op {
+                '*' => value.mul(rhs)?,
+                '/' => value.div(rhs)?,
+                _ => value.rem(rhs)?,
+            };
+        }
+        Ok(value)
+    }
+
+    // unary := ('-' | '+') unary | power
+    fn parse_unary(&mut self) -> eyre::Result<Rational> {
+        match self.next_operator(&['-', '+']) {
+            Some('-') => Ok(self.parse_unary()?.neg()),
+            Some(_) => self.parse_unary(),
+            None => self.parse_power(),
+        }
+    }
+
+    // power := primary ('^' unary)?
+    fn parse_power(&mut self) -> eyre::Result<Rational> {
+        let base = self.parse_primary()?;
+        if self.next_operator(&['^']).is_some() {
+            let exponent = self.parse_unary()?;
+            return base.pow(exponent);
+        }
+        Ok(base)
+    }
+
+    // primary := number | '(' expression ')'
+    fn parse_primary(&mut self) -> eyre::Result<Rational> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let value = self.parse_expression()?;
+                if self.next_operator(&[')']).is_none() {
+                    return Err(eyre::eyre!("Missing closing parenthesis"));
+                }
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.parse_number(),
+            Some(c) => Err(eyre::eyre!("Unexpected character '{c}'")),
+            None => Err(eyre::eyre!("Unexpected end of expression")),
+        }
+    }
+
+    fn parse_number(&mut self) -> eyre::Result<Rational> {
+        let mut num: i128 = 0;
+        let mut den: i128 = 1;
+        let mut seen_dot = false;
+        let mut seen_digit = false;
+
+        while let Some(c) = self.peek() {
+            match c {
+                '0'..='9' => {
+                    let digit = i128::from(c as u8 - b'0');
+                    num = num
+                        .checked_mul(10)
+                        .and_then(|n| n.checked_add(digit))
+                        .ok_or_else(overflow)?;
+                    if seen_dot {
+                        den = den.checked_mul(10).ok_or_else(overflow)?;
+                    }
+                    seen_digit = true;
+                }
+                '.' if !seen_dot => seen_dot = true,
+                // Thousands separators such as 12,000 or 12_000
+                ',' | '_' if seen_digit && !seen_dot => {}
+                _ => break,
+            }
+            self.pos += 1;
+        }
+
+        if !seen_digit {
+            return Err(eyre::eyre!("Invalid number"));
+        }
+
+        Rational::new(num, den)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval_str(expression: &str) -> String {
+        evaluate(expression).unwrap().format().unwrap()
+    }
+
+    #[test]
+    fn test_basic_arithmetic() {
+        assert_eq!(eval_str("1 + 2 * 3"), "7");
+        assert_eq!(eval_str("(1 + 2) * 3"), "9");
+        assert_eq!(eval_str("10 - 4 - 3"), "3");
+        assert_eq!(eval_str("2 ^ 10"), "1024");
+        assert_eq!(eval_str("2 ^ 3 ^ 2"), "512");
+        assert_eq!(eval_str("-2 ^ 2"), "-4");
+        assert_eq!(eval_str("7 % 3"), "1");
+        assert_eq!(eval_str("-7 % 3"), "2");
+    }
+
+    #[test]
+    fn test_decimals_are_exact() {
+        assert_eq!(eval_str("0.1 + 0.2"), "0.3");
+        assert_eq!(eval_str("1.5 * 1.5"), "2.25");
+        assert_eq!(eval_str("2 ^ -2"), "0.25");
+        assert_eq!(eval_str("(32,000 + 18,500) / 4"), "12625");
+        assert_eq!(eval_str("12 × 3 ÷ 4"), "9");
+    }
+
+    #[test]
+    fn test_repeating_fraction_is_shown_as_fraction() {
+        assert_eq!(eval_str("10 / 3"), "10/3 (≈ 3.3333333333)");
+        assert_eq!(eval_str("-1 / 6"), "-1/6 (≈ -0.1666666666)");
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(evaluate("1 / 0").is_err());
+        assert!(evaluate("5 % 0").is_err());
+        assert!(evaluate("(1 + 2").is_err());
+        assert!(evaluate("1 +").is_err());
+        assert!(evaluate("2 ^ 0.5").is_err());
+        assert!(evaluate("2 ^ 1000").is_err());
+        assert!(evaluate("abc").is_err());
+        assert!(evaluate("1 2").is_err());
+        assert!(evaluate("99999999999999999999 ^ 3").is_err());
+        // Results in i128::MIN, which can't be negated
+        assert!(evaluate("(-(2 ^ 126)) * 2").is_err());
+        // The decimal expansion of a huge denominator overflows
+        let tiny = evaluate("1 - 0.00000000000000000000000000000000000001").unwrap();
+        assert!(tiny.format().is_err());
+    }
+}
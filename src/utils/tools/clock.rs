@@ -0,0 +1,102 @@
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::utils::openai_schema::FunctionTool;
+
+pub const FUNCTION_NAME: &str = "current_time";
+
+// Most of our friends live in Korea
+const DEFAULT_TIMEZONE: &str = "Asia/Seoul";
+
+#[derive(Debug, Deserialize)]
+struct ClockArgs {
+    timezone: Option<String>,
+}
+
+pub fn definition() -> FunctionTool {
+    FunctionTool::new(
+        FUNCTION_NAME,
+        "Get the current date and time in an IANA timezone such as 'Asia/Seoul' or \
+         'America/New_York'. Defaults to Asia/Seoul.",
+        json!({
+            "type": "object",
+            "properties": {
+                "timezone": {
+                    "type": "string",
+                    "description": "IANA timezone name"
+                }
+            }
+        }),
+    )
+}
+
+pub fn call(arguments: &str) -> eyre::Result<String> {
+    let args: ClockArgs = serde_json::from_str(arguments)?;
+    let timezone = args.timezone.as_deref().unwrap_or(DEFAULT_TIMEZONE);
+
+    format_time_in(timezone, Utc::now())
+}
+
+/// Format the given instant in the named timezone
+fn format_time_in(timezone: &str, now: DateTime<Utc>) -> eyre::Result<String> {
+    let tz: Tz = timezone
+        .trim()
+        .parse()
+        .map_err(|_| eyre::eyre!("Unknown timezone: {timezone}"))?;
+    let local = now.with_timezone(&tz);
+
+    Ok(json!({
+        "timezone": tz.name(),
+        "datetime": local.format("%Y-%m-%d %H:%M:%S").to_string(),
+        "weekday": local.format("%A").to_string(),
+        "utc_offset": local.format("%:z").to_string(),
+        "abbreviation": local.format("%Z").to_string(),
+    })
+    .to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_format_time_in_seoul() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 1, 15, 30, 0).unwrap();
+        let output = format_time_in("Asia/Seoul", now).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(value["datetime"], "2025-01-02 00:30:00");
+        assert_eq!(value["weekday"], "Thursday");
+        assert_eq!(value["utc_offset"], "+09:00");
+        assert_eq!(value["abbreviation"], "KST");
+    }
+
+    #[test]
+    fn test_format_time_in_handles_dst() {
+        let summer = Utc.with_ymd_and_hms(2025, 7, 1, 12, 0, 0).unwrap();
+        let winter = Utc.with_ymd_and_hms(2025, 12, 1, 12, 0, 0).unwrap();
+
+        let summer: serde_json::Value =
+            serde_json::from_str(&format_time_in("America/New_York", summer).unwrap()).unwrap();
+        let winter: serde_json::Value =
+            serde_json::from_str(&format_time_in("America/New_York", winter).unwrap()).unwrap();
+
+        assert_eq!(summer["utc_offset"], "-04:00");
+        assert_eq!(winter["utc_offset"], "-05:00");
+    }
+
+    #[test]
+    fn test_unknown_timezone() {
+        assert!(format_time_in("Mars/Olympus_Mons", Utc::now()).is_err());
+    }
+
+    #[test]
+    fn test_call_defaults_to_seoul() {
+        let output = call("{}").unwrap();
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(value["timezone"], DEFAULT_TIMEZONE);
+    }
+}
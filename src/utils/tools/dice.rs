@@ -0,0 +1,250 @@
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::utils::openai_schema::FunctionTool;
+
+pub const FUNCTION_NAME: &str = "roll_dice";
+
+// Limits to keep the output readable
+const MAX_DICE_PER_TERM: u32 = 100;
+const MAX_SIDES: u32 = 1000;
+const MAX_TERMS: usize = 10;
+// Keeps totals far from overflowing together with the other limits
+const MAX_CONSTANT: i64 = 1_000_000;
+
+#[derive(Debug, Deserialize)]
+struct DiceArgs {
+    notation: String,
+}
+
+pub fn definition() -> FunctionTool {
+    FunctionTool::new(
+        FUNCTION_NAME,
+        "Roll dice using standard dice notation, e.g. 'd20', '2d6+3', '1d8+1d6-1'. \
+         Always use this instead of making up dice results.",
+        json!({
+            "type": "object",
+            "properties": {
+                "notation": {
+                    "type": "string",
+                    "description": "Dice notation such as '2d6+3'"
+                }
+            },
+            "required": ["notation"]
+        }),
+    )
+}
+
+pub fn call(arguments: &str) -> eyre::Result<String> {
+    let args: DiceArgs = serde_json::from_str(arguments)?;
+    let result = roll(&args.notation, &mut rand::thread_rng())?;
+
+    Ok(json!({
+        "notation": args.notation,
+        "rolls": result.rolls,
+        "modifier": result.modifier,
+        "total": result.total,
+    })
+    .to_string())
+}
+
+/// A single term of a dice expression
+#[derive(Debug, PartialEq, Eq)]
+enum Term {
+    Dice { count: u32, sides: u32 },
+    Constant(i64),
+}
+
+/// Result of rolling a dice expression
+#[derive(Debug)]
+struct DiceRoll {
+    /// Individual die results per dice term, negated for subtracted terms
+    rolls: Vec<Vec<i64>>,
+    /// Sum of all constant terms
+    modifier: i64,
+    total: i64,
+}
+
+/// Parse dice notation into signed terms
+fn parse_notation(notation: &str) -> eyre::Result<Vec<(i64, Term)>> {
+    let compact: String = notation
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_lowercase();
+    if compact.is_empty() {
+        return Err(eyre::eyre!("Empty dice notation"));
+    }
+
+    let mut terms = Vec::new();
+    let mut sign = 1;
+    let mut current = String::new();
+
+    for c in compact.chars().chain(std::iter::once('+')) {
+        match c {
+            '+' | '-' => {
+                if current.is_empty() {
+                    if !terms.is_empty() || c == '+' {
+                        return Err(eyre::eyre!("Invalid dice notation: {notation}"));
+                    }
+                } else {
+                    terms.push((sign, parse_term(&current)?));
+                    current.clear();
+                }
+                sign = if c == '-' { -1 } else { 1 };
+            }
+            _ => current.push(c),
+        }
+    }
+
+    if terms.len() > MAX_TERMS {
+        return Err(eyre::eyre!("Too many terms (max {MAX_TERMS})"));
+    }
+
+    Ok(terms)
+}
+
+/// Parse a single term such as "2d6", "d20" or "3"
+fn parse_term(term: &str) -> eyre::Result<Term> {
+    let Some((count, sides)) = term.split_once('d') else {
+        let value = term
+            .parse::<i64>()
+            .map_err(|_| eyre::eyre!("Invalid term: {term}"))?;
+        if value > MAX_CONSTANT {
+            return Err(eyre::eyre!("Constants must be at most {MAX_CONSTANT}"));
+        }
+        return Ok(Term::Constant(value));
+    };
+
+    let count = if count.is_empty() {
+        1
+    } else {
+        count
+            .parse::<u32>()
+            .map_err(|_| eyre::eyre!("Invalid dice count: {term}"))?
+    };
+    let sides = sides
+        .parse::<u32>()
+        .map_err(|_| eyre::eyre!("Invalid dice sides: {term}"))?;
+
+    if count == 0 || count > MAX_DICE_PER_TERM {
+        return Err(eyre::eyre!(
+            "Dice count must be between 1 and {MAX_DICE_PER_TERM}"
+        ));
+    }
+    if !(2..=MAX_SIDES).contains(&sides) {
+        return Err(eyre::eyre!("Dice sides must be between 2 and {MAX_SIDES}"));
+    }
+
+    Ok(Term::Dice { count, sides })
+}
+
+/// Roll a dice expression with the given random number generator
+fn roll(notation: &str, rng: &mut impl Rng) -> eyre::Result<DiceRoll> {
+    let terms = parse_notation(notation)?;
+
+    let mut rolls = Vec::new();
+    let mut modifier = 0;
+    let mut total = 0;
+
+    for (sign, term) in terms {
+        match term {
+            Term::Dice { count, sides } => {
+                let results: Vec<i64> = (0..count)
+                    .map(|_| sign * i64::from(rng.gen_range(1..=sides)))
+                    .collect();
+                total += results.iter().sum::<i64>();
+                rolls.push(results);
+            }
+            Term::Constant(value) => {
+                modifier += sign * value;
+                total += sign * value;
+            }
+        }
+    }
+
+    Ok(DiceRoll {
+        rolls,
+        modifier,
+        total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn test_parse_notation() {
+        assert_eq!(
+            parse_notation("2d6+3").unwrap(),
+            vec![
+                (1, Term::Dice { count: 2, sides: 6 }),
+                (1, Term::Constant(3))
+            ]
+        );
+        assert_eq!(
+            parse_notation("D20 - 1").unwrap(),
+            vec![
+                (
+                    1,
+                    Term::Dice {
+                        count: 1,
+                        sides: 20
+                    }
+                ),
+                (-1, Term::Constant(1))
+            ]
+        );
+        assert_eq!(
+            parse_notation("-1d4").unwrap(),
+            vec![(-1, Term::Dice { count: 1, sides: 4 })]
+        );
+    }
+
+    #[test]
+    fn test_parse_notation_rejects_invalid_input() {
+        assert!(parse_notation("").is_err());
+        assert!(parse_notation("2d").is_err());
+        assert!(parse_notation("2x6").is_err());
+        assert!(parse_notation("1d6++2").is_err());
+        assert!(parse_notation("0d6").is_err());
+        assert!(parse_notation("1d1").is_err());
+        assert!(parse_notation("101d6").is_err());
+        assert!(parse_notation("1d1001").is_err());
+        assert!(parse_notation("d20+9223372036854775807").is_err());
+    }
+
+    #[test]
+    fn test_roll_stays_in_range() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for _ in 0..100 {
+            let result = roll("3d6+2", &mut rng).unwrap();
+            assert_eq!(result.rolls.len(), 1);
+            assert_eq!(result.rolls[0].len(), 3);
+            assert!(result.rolls[0].iter().all(|r| (1..=6).contains(r)));
+            assert_eq!(result.modifier, 2);
+            assert_eq!(result.total, result.rolls[0].iter().sum::<i64>() + 2);
+            assert!((5..=20).contains(&result.total));
+        }
+    }
+
+    #[test]
+    fn test_roll_subtracts_negative_terms() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let result = roll("1d4-1d4-2", &mut rng).unwrap();
+        assert!(result.rolls[1].iter().all(|r| (-4..=-1).contains(r)));
+        assert_eq!(result.total, result.rolls.iter().flatten().sum::<i64>() - 2);
+    }
+
+    #[test]
+    fn test_call_returns_json() {
+        let output = call(r#"{"notation": "1d6"}"#).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&output).unwrap();
+        let total = value["total"].as_i64().unwrap();
+        assert!((1..=6).contains(&total));
+    }
+}
@@ -0,0 +1,144 @@
+use chrono::FixedOffset;
+use serenity::all::{
+    ChannelId, GuildChannel, Member, PartialGuild, Permissions, Timestamp, UserId,
+};
+use serenity::utils::{parse_channel_mention, parse_user_mention};
+use std::collections::HashMap;
+
+use super::ToolContext;
+
+/// Guild data needed to check what the user who mentioned the bot can see
+pub struct RequesterAccess {
+    pub guild: PartialGuild,
+    pub member: Member,
+}
+
+impl RequesterAccess {
+    /// Load the guild and the requesting member for the current message
+    pub async fn load(tool_ctx: &ToolContext<'_>) -> eyre::Result<Self> {
+        let guild_id = tool_ctx
+            .msg_ctx
+            .guild_id
+            .ok_or_else(|| eyre::eyre!("This is only available in servers, not in DMs"))?;
+
+        let guild = guild_id.to_partial_guild(tool_ctx.ctx).await?;
+        let member = guild_id
+            .member(tool_ctx.ctx, tool_ctx.msg_ctx.author_id)
+            .await?;
+
+        Ok(Self { guild, member })
+    }
+
+    fn permissions_in(&self, channel: &GuildChannel) -> Permissions {
+        self.guild.user_permissions_in(channel, &self.member)
+    }
+
+    /// Whether the requesting user can see the channel at all
+    pub fn can_view(&self, channel: &GuildChannel) -> bool {
+        self.permissions_in(channel).view_channel()
+    }
+
+    /// Whether the requesting user can read the channel's message history
+    pub fn can_read_history(&self, channel: &GuildChannel) -> bool {
+        let permissions = self.permissions_in(channel);
+        permissions.view_channel() && permissions.read_message_history()
+    }
+}
+
+/// A channel as referred to by the model
+#[derive(Debug, PartialEq, Eq)]
+pub enum ChannelReference {
+    Id(ChannelId),
+    Name(String),
+}
+
+/// Parse a channel given as a mention (<#id>), a raw id or a name (optionally prefixed with #)
+pub fn parse_channel_reference(input: &str) -> ChannelReference {
+    let input = input.trim();
+
+    if let Some(channel_id) = parse_channel_mention(input) {
+        return ChannelReference::Id(channel_id);
+    }
+    if let Some(id) = input.parse::<u64>().ok().filter(|id| *id != 0) {
+        return ChannelReference::Id(ChannelId::new(id));
+    }
+
+    ChannelReference::Name(input.trim_start_matches('#').to_lowercase())
+}
+
+/// Find a guild channel by reference
+pub fn find_channel<'a>(
+    channels: &'a HashMap<ChannelId, GuildChannel>,
+    reference: &ChannelReference,
+) -> Option<&'a GuildChannel> {
+    match reference {
+        ChannelReference::Id(channel_id) => channels.get(channel_id),
+        ChannelReference::Name(name) => channels
+            .values()
+            .find(|channel| channel.name.to_lowercase() == *name),
+    }
+}
+
+/// Parse a user given as a mention (<@id> or <@!id>) or a raw id
+pub fn parse_user_reference(input: &str) -> Option<UserId> {
+    let input = input.trim();
+
+    if let Some(user_id) = parse_user_mention(input) {
+        return Some(user_id);
+    }
+
+    input
+        .parse::<u64>()
+        .ok()
+        .filter(|id| *id != 0)
+        .map(UserId::new)
+}
+
+/// Format a Discord timestamp in KST, like the conversation log
+pub fn format_timestamp(timestamp: &Timestamp) -> String {
+    let kst = FixedOffset::east_opt(9 * 3600).unwrap();
+    timestamp
+        .with_timezone(&kst)
+        .format("%Y-%m-%d %H:%M:%S %z")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_channel_reference() {
+        assert_eq!(
+            parse_channel_reference("<#123456>"),
+            ChannelReference::Id(ChannelId::new(123456))
+        );
+        assert_eq!(
+            parse_channel_reference(" 123456 "),
+            ChannelReference::Id(ChannelId::new(123456))
+        );
+        assert_eq!(
+            parse_channel_reference("#General"),
+            ChannelReference::Name("general".to_string())
+        );
+        assert_eq!(
+            parse_channel_reference("잡담"),
+            ChannelReference::Name("잡담".to_string())
+        );
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        let timestamp = Timestamp::from_unix_timestamp(1_735_745_400).unwrap();
+        assert_eq!(format_timestamp(&timestamp), "2025-01-02 00:30:00 +0900");
+    }
+
+    #[test]
+    fn test_parse_user_reference() {
+        assert_eq!(parse_user_reference("<@42>"), Some(UserId::new(42)));
+        assert_eq!(parse_user_reference("<@!42>"), Some(UserId::new(42)));
+        assert_eq!(parse_user_reference("42"), Some(UserId::new(42)));
+        assert_eq!(parse_user_reference("0"), None);
+        assert_eq!(parse_user_reference("철수"), None);
+    }
+}
@@ -0,0 +1,52 @@
+use serde::Deserialize;
+use serde_json::json;
+
+use super::ToolContext;
+use crate::utils::image_gen::{MAX_PROMPT_LENGTH, PromptSource, draw_and_post};
+use crate::utils::openai_schema::FunctionTool;
+
+pub const FUNCTION_NAME: &str = "generate_image";
+
+#[derive(Debug, Deserialize)]
+struct ImageArgs {
+    prompt: String,
+}
+
+pub fn definition() -> FunctionTool {
+    FunctionTool::new(
+        FUNCTION_NAME,
+        "Draw an image from a text description and post it to the channel. \
+         Only use this when someone asks for a picture. The image is posted before your reply, \
+         so don't describe it in detail.",
+        json!({
+            "type": "object",
+            "properties": {
+                "prompt": {
+                    "type": "string",
+                    "description": format!(
+                        "Detailed description of the image to draw, in English (max {MAX_PROMPT_LENGTH} characters)"
+                    )
+                }
+            },
+            "required": ["prompt"]
+        }),
+    )
+}
+
+pub async fn call(arguments: &str, tool_ctx: &ToolContext<'_>) -> eyre::Result<String> {
+    let args: ImageArgs = serde_json::from_str(arguments)?;
+    let image = draw_and_post(
+        tool_ctx.ctx,
+        tool_ctx.msg_ctx,
+        &args.prompt,
+        PromptSource::Model,
+        tool_ctx.transient,
+    )
+    .await?;
+
+    Ok(json!({
+        "posted": true,
+        "prompt": image.revised_prompt.unwrap_or(args.prompt),
+    })
+    .to_string())
+}
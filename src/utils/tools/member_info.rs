@@ -0,0 +1,98 @@
+use serde::Deserialize;
+use serde_json::json;
+use serenity::all::{Member, UserId};
+
+use super::ToolContext;
+use super::discord_access::{RequesterAccess, format_timestamp, parse_user_reference};
+use crate::utils::openai_schema::FunctionTool;
+
+pub const FUNCTION_NAME: &str = "get_member_info";
+
+const MAX_SEARCH_RESULTS: u64 = 5;
+
+#[derive(Debug, Deserialize)]
+struct MemberInfoArgs {
+    user: String,
+}
+
+pub fn definition() -> FunctionTool {
+    FunctionTool::new(
+        FUNCTION_NAME,
+        "Look up a member of the current Discord server: display name, roles and join date.",
+        json!({
+            "type": "object",
+            "properties": {
+                "user": {
+                    "type": "string",
+                    "description": "User mention, user id, or (part of) a name or nickname"
+                }
+            },
+            "required": ["user"]
+        }),
+    )
+}
+
+pub async fn call(arguments: &str, tool_ctx: &ToolContext<'_>) -> eyre::Result<String> {
+    let args: MemberInfoArgs = serde_json::from_str(arguments)?;
+    let access = RequesterAccess::load(tool_ctx).await?;
+    let guild_id = access.guild.id;
+
+    let user_id = match parse_user_reference(&args.user) {
+        Some(user_id) => user_id,
+        None => find_member_by_name(tool_ctx, &access, &args.user).await?,
+    };
+    let member = guild_id.member(tool_ctx.ctx, user_id).await?;
+
+    let roles: Vec<String> = member
+        .roles
+        .iter()
+        .filter_map(|role_id| access.guild.roles.get(role_id))
+        .map(|role| role.name.clone())
+        .collect();
+
+    Ok(json!({
+        "user_id": member.user.id.to_string(),
+        "username": member.user.name,
+        "global_name": member.user.global_name,
+        "nickname": member.nick,
+        "display_name": member.display_name(),
+        "bot": member.user.bot,
+        "roles": roles,
+        "joined_at": member.joined_at.as_ref().map(format_timestamp),
+        "account_created_at": format_timestamp(&member.user.created_at()),
+    })
+    .to_string())
+}
+
+/// Search the guild for a member whose name matches the query
+/// Exact (case-insensitive) matches on any name are preferred over partial ones
+async fn find_member_by_name(
+    tool_ctx: &ToolContext<'_>,
+    access: &RequesterAccess,
+    query: &str,
+) -> eyre::Result<UserId> {
+    let query = query.trim().trim_start_matches('@');
+    let members = access
+        .guild
+        .id
+        .search_members(&tool_ctx.ctx.http, query, Some(MAX_SEARCH_RESULTS))
+        .await?;
+
+    let is_exact = |member: &Member| {
+        [
+            Some(member.user.name.as_str()),
+            member.user.global_name.as_deref(),
+            member.nick.as_deref(),
+        ]
+        .into_iter()
+        .flatten()
+        .any(|name| name.eq_ignore_ascii_case(query))
+    };
+
+    members
+        .iter()
+        .find(|member| is_exact(member))
+        .or_else(|| members.first())
+        .map(|member| member.user.id)
+        .ok_or_else(|| eyre::eyre!("No member found matching '{query}'"))
+}
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use serenity::prelude::Context;
+use std::collections::HashSet;
+use strum::IntoEnumIterator;
+use strum_macros::{EnumIter, EnumString};
+
+use crate::utils::msg_context::MsgContextInfo;
+use crate::utils::openai_schema::FunctionTool;
+
+mod calculator;
+mod clock;
+mod dice;
+mod discord_access;
+mod image_generator;
+mod member_info;
+mod random_picker;
+mod recent_messages;
+mod server_info;
+
+/// Discord context of the message a tool call is made for
+pub struct ToolContext<'a> {
+    pub ctx: &'a Context,
+    pub msg_ctx: &'a MsgContextInfo,
+    /// Whether the request is kept out of the history, e.g. for `!nomemory`
+    pub transient: bool,
+}
+
+/// Built-in tools the model can call instead of guessing
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Serialize,
+    Deserialize,
+    PartialEq,
+    Eq,
+    Hash,
+    EnumString,
+    EnumIter,
+    strum_macros::Display,
+)]
+#[strum(serialize_all = "snake_case", ascii_case_insensitive)]
+pub enum BuiltinTool {
+    /// Dice notation roller (e.g. 2d6+3)
+    Dice,
+    /// Exact arithmetic evaluator
+    Calculator,
+    /// Current time in an IANA timezone
+    Clock,
+    /// Random choice and shuffle
+    RandomPicker,
+    /// Recent messages of a channel the requesting user can read
+    RecentMessages,
+    /// Display name, roles and join date of a guild member
+    MemberInfo,
+    /// Guild information and the channels the requesting user can see
+    ServerInfo,
+    /// Image generation, posted to the channel as an attachment
+    ImageGenerator,
+}
+
+impl BuiltinTool {
+    /// Name of the function exposed to the model
+    pub fn function_name(&self) -> &'static str {
+        match self {
+            BuiltinTool::Dice => dice::FUNCTION_NAME,
+            BuiltinTool::Calculator => calculator::FUNCTION_NAME,
+            BuiltinTool::Clock => clock::FUNCTION_NAME,
+            BuiltinTool::RandomPicker => random_picker::FUNCTION_NAME,
+            BuiltinTool::RecentMessages => recent_messages::FUNCTION_NAME,
+            BuiltinTool::MemberInfo => member_info::FUNCTION_NAME,
+            BuiltinTool::ServerInfo => server_info::FUNCTION_NAME,
+            BuiltinTool::ImageGenerator => image_generator::FUNCTION_NAME,
+        }
+    }
+
+    /// Find the tool that exposes the given function name
+    pub fn from_function_name(name: &str) -> Option<Self> {
+        BuiltinTool::iter().find(|tool| tool.function_name() == name)
+    }
+
+    /// Get the function tool definition sent to the model
+    pub fn definition(&self) -> FunctionTool {
+        match self {
+            BuiltinTool::Dice => dice::definition(),
+            BuiltinTool::Calculator => calculator::definition(),
+            BuiltinTool::Clock => clock::definition(),
+            BuiltinTool::RandomPicker => random_picker::definition(),
+            BuiltinTool::RecentMessages => recent_messages::definition(),
+            BuiltinTool::MemberInfo => member_info::definition(),
+            BuiltinTool::ServerInfo => server_info::definition(),
+            BuiltinTool::ImageGenerator => image_generator::definition(),
+        }
+    }
+
+    /// Run the tool with the JSON arguments given by the model
+    /// Errors are returned as text so the model can see what went wrong
+    pub async fn call(&self, arguments: &str, tool_ctx: &ToolContext<'_>) -> String {
+        let result = match self {
+            BuiltinTool::Dice => dice::call(arguments),
+            BuiltinTool::Calculator => calculator::call(arguments),
+            BuiltinTool::Clock => clock::call(arguments),
+            BuiltinTool::RandomPicker => random_picker::call(arguments),
+            BuiltinTool::RecentMessages => recent_messages::call(arguments, tool_ctx).await,
+            BuiltinTool::MemberInfo => member_info::call(arguments, tool_ctx).await,
+            BuiltinTool::ServerInfo => server_info::call(arguments, tool_ctx).await,
+            BuiltinTool::ImageGenerator => image_generator::call(arguments, tool_ctx).await,
+        };
+
+        result.unwrap_or_else(|e| format!("Error: {e}"))
+    }
+}
+
+/// Get the definitions of all tools not disabled for a channel
+pub fn enabled_tool_definitions(disabled: &HashSet<BuiltinTool>) -> Vec<FunctionTool> {
+    BuiltinTool::iter()
+        .filter(|tool| !disabled.contains(tool))
+        .map(|tool| tool.definition())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_function_name_round_trip() {
+        for tool in BuiltinTool::iter() {
+            assert_eq!(
+                BuiltinTool::from_function_name(tool.function_name()),
+                Some(tool)
+            );
+        }
+        assert_eq!(BuiltinTool::from_function_name("unknown"), None);
+    }
+
+    #[test]
+    fn test_parse_tool_name() {
+        assert_eq!(
+            BuiltinTool::from_str("random_picker").unwrap(),
+            BuiltinTool::RandomPicker
+        );
+        assert_eq!(BuiltinTool::from_str("Dice").unwrap(), BuiltinTool::Dice);
+        assert!(BuiltinTool::from_str("weather").is_err());
+    }
+
+    #[test]
+    fn test_enabled_tool_definitions_skips_disabled() {
+        let disabled = HashSet::from([BuiltinTool::Clock]);
+        let names: Vec<String> = enabled_tool_definitions(&disabled)
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect();
+
+        assert_eq!(names.len(), BuiltinTool::iter().count() - 1);
+        assert!(!names.contains(&BuiltinTool::Clock.function_name().to_string()));
+    }
+
+    #[test]
+    fn test_call_reports_invalid_arguments() {
+        assert!(dice::call("not json").is_err());
+        assert!(calculator::call("{}").is_err());
+    }
+}
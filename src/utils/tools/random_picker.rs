@@ -0,0 +1,136 @@
+use rand::Rng;
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::utils::openai_schema::FunctionTool;
+
+pub const FUNCTION_NAME: &str = "random_pick";
+
+const MAX_OPTIONS: usize = 100;
+
+#[derive(Debug, Deserialize)]
+struct RandomPickerArgs {
+    options: Vec<String>,
+    /// Number of distinct options to pick (ignored when shuffling)
+    count: Option<usize>,
+    #[serde(default)]
+    shuffle: bool,
+}
+
+pub fn definition() -> FunctionTool {
+    FunctionTool::new(
+        FUNCTION_NAME,
+        "Randomly pick one or more options from a list, or shuffle the whole list. \
+         Always use this when asked to choose randomly (e.g. where to eat, who pays).",
+        json!({
+            "type": "object",
+            "properties": {
+                "options": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Options to choose from"
+                },
+                "count": {
+                    "type": "integer",
+                    "description": "How many distinct options to pick (default 1)"
+                },
+                "shuffle": {
+                    "type": "boolean",
+                    "description": "Return the whole list in random order instead of picking"
+                }
+            },
+            "required": ["options"]
+        }),
+    )
+}
+
+pub fn call(arguments: &str) -> eyre::Result<String> {
+    let args: RandomPickerArgs = serde_json::from_str(arguments)?;
+    let mut rng = rand::thread_rng();
+
+    if args.shuffle {
+        let shuffled = shuffle(args.options, &mut rng)?;
+        Ok(json!({ "shuffled": shuffled }).to_string())
+    } else {
+        let picked = pick(&args.options, args.count.unwrap_or(1), &mut rng)?;
+        Ok(json!({ "picked": picked }).to_string())
+    }
+}
+
+fn validate_options(options: &[String]) -> eyre::Result<()> {
+    if options.is_empty() {
+        return Err(eyre::eyre!("No options given"));
+    }
+    if options.len() > MAX_OPTIONS {
+        return Err(eyre::eyre!("Too many options (max {MAX_OPTIONS})"));
+    }
+    Ok(())
+}
+
+/// Pick `count` distinct options at random
+fn pick(options: &[String], count: usize, rng: &mut impl Rng) -> eyre::Result<Vec<String>> {
+    validate_options(options)?;
+    if count == 0 || count > options.len() {
+        return Err(eyre::eyre!(
+            "Count must be between 1 and {} (the number of options)",
+            options.len()
+        ));
+    }
+
+    Ok(options.choose_multiple(rng, count).cloned().collect())
+}
+
+/// Return all options in random order
+fn shuffle(mut options: Vec<String>, rng: &mut impl Rng) -> eyre::Result<Vec<String>> {
+    validate_options(&options)?;
+    options.shuffle(rng);
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn options() -> Vec<String> {
+        ["국밥", "짜장면", "초밥", "버거"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_pick_returns_distinct_options() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let options = options();
+        let picked = pick(&options, 3, &mut rng).unwrap();
+
+        assert_eq!(picked.len(), 3);
+        assert!(picked.iter().all(|p| options.contains(p)));
+        let mut deduped = picked.clone();
+        deduped.sort();
+        deduped.dedup();
+        assert_eq!(deduped.len(), 3);
+    }
+
+    #[test]
+    fn test_pick_rejects_invalid_count() {
+        let mut rng = StdRng::seed_from_u64(1);
+        assert!(pick(&options(), 0, &mut rng).is_err());
+        assert!(pick(&options(), 5, &mut rng).is_err());
+        assert!(pick(&[], 1, &mut rng).is_err());
+    }
+
+    #[test]
+    fn test_shuffle_is_a_permutation() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut shuffled = shuffle(options(), &mut rng).unwrap();
+        let mut expected = options();
+
+        shuffled.sort();
+        expected.sort();
+        assert_eq!(shuffled, expected);
+    }
+}
@@ -0,0 +1,138 @@
+use serde::Deserialize;
+use serde_json::json;
+use serenity::all::{ChannelId, ChannelType, GetMessages, GuildChannel};
+
+use super::ToolContext;
+use super::discord_access::{
+    RequesterAccess, find_channel, format_timestamp, parse_channel_reference,
+};
+use crate::utils::openai_schema::FunctionTool;
+
+pub const FUNCTION_NAME: &str = "get_recent_messages";
+
+const DEFAULT_LIMIT: u8 = 20;
+const MAX_LIMIT: u8 = 50;
+const MAX_CONTENT_LENGTH: usize = 500;
+
+#[derive(Debug, Deserialize)]
+struct RecentMessagesArgs {
+    channel: Option<String>,
+    limit: Option<u8>,
+}
+
+pub fn definition() -> FunctionTool {
+    FunctionTool::new(
+        FUNCTION_NAME,
+        "Fetch the most recent messages of a Discord channel, including messages that did \
+         not mention you. Use this when asked about what someone said earlier.",
+        json!({
+            "type": "object",
+            "properties": {
+                "channel": {
+                    "type": "string",
+                    "description": "Channel name, mention or id. Defaults to the current channel."
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": format!("Number of messages to fetch (1-{MAX_LIMIT}, default {DEFAULT_LIMIT})")
+                }
+            }
+        }),
+    )
+}
+
+pub async fn call(arguments: &str, tool_ctx: &ToolContext<'_>) -> eyre::Result<String> {
+    let args: RecentMessagesArgs = serde_json::from_str(arguments)?;
+    let limit = args.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let channel_id = resolve_readable_channel(tool_ctx, args.channel.as_deref()).await?;
+    let mut messages = channel_id
+        .messages(tool_ctx.ctx, GetMessages::new().limit(limit))
+        .await?;
+
+    // Discord returns the newest message first
+    messages.reverse();
+
+    let messages: Vec<serde_json::Value> = messages
+        .iter()
+        .map(|msg| {
+            let author = msg
+                .author
+                .global_name
+                .clone()
+                .unwrap_or_else(|| msg.author.name.clone());
+            let mut content: String = msg.content.chars().take(MAX_CONTENT_LENGTH).collect();
+            if content.len() < msg.content.len() {
+                content.push_str("...");
+            }
+
+            json!({
+                "author": author,
+                "bot": msg.author.bot,
+                "timestamp": format_timestamp(&msg.timestamp),
+                "content": content,
+                "attachments": msg.attachments.len(),
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "channel_id": channel_id.to_string(),
+        "messages": messages,
+    })
+    .to_string())
+}
+
+/// Resolve the requested channel, making sure the requesting user can read its history
+async fn resolve_readable_channel(
+    tool_ctx: &ToolContext<'_>,
+    channel: Option<&str>,
+) -> eyre::Result<ChannelId> {
+    let current_channel = tool_ctx.msg_ctx.channel_id;
+
+    // In DMs only the DM channel itself can be read
+    if tool_ctx.msg_ctx.guild_id.is_none() {
+        return match channel {
+            None => Ok(current_channel),
+            Some(_) => Err(eyre::eyre!("Only the current channel can be read in DMs")),
+        };
+    }
+
+    let access = RequesterAccess::load(tool_ctx).await?;
+    let channels = access.guild.id.channels(&tool_ctx.ctx.http).await?;
+
+    let target = match channel {
+        Some(reference) => {
+            let reference = parse_channel_reference(reference);
+            find_channel(&channels, &reference)
+                .cloned()
+                .ok_or_else(|| eyre::eyre!("Channel not found"))?
+        }
+        // Threads are not listed with the guild channels, so fetch the current channel directly
+        None => match channels.get(&current_channel) {
+            Some(channel) => channel.clone(),
+            None => current_channel
+                .to_channel(tool_ctx.ctx)
+                .await?
+                .guild()
+                .ok_or_else(|| eyre::eyre!("Channel not found"))?,
+        },
+    };
+
+    // Threads inherit their visibility from the parent channel
+    let permission_channel: &GuildChannel = match target.kind {
+        ChannelType::PublicThread | ChannelType::PrivateThread | ChannelType::NewsThread => target
+            .parent_id
+            .and_then(|parent_id| channels.get(&parent_id))
+            .unwrap_or(&target),
+        _ => &target,
+    };
+
+    if !access.can_read_history(permission_channel) {
+        return Err(eyre::eyre!(
+            "The requesting user is not allowed to read that channel"
+        ));
+    }
+
+    Ok(target.id)
+}
@@ -0,0 +1,65 @@
+use serde_json::json;
+use serenity::all::{ChannelType, GuildChannel};
+
+use super::ToolContext;
+use super::discord_access::{RequesterAccess, format_timestamp};
+use crate::utils::openai_schema::FunctionTool;
+
+pub const FUNCTION_NAME: &str = "get_server_info";
+
+pub fn definition() -> FunctionTool {
+    FunctionTool::new(
+        FUNCTION_NAME,
+        "Get information about the current Discord server, including the list of channels \
+         the requesting user can see.",
+        json!({
+            "type": "object",
+            "properties": {}
+        }),
+    )
+}
+
+pub async fn call(_arguments: &str, tool_ctx: &ToolContext<'_>) -> eyre::Result<String> {
+    let access = RequesterAccess::load(tool_ctx).await?;
+    let all_channels = access.guild.id.channels(&tool_ctx.ctx.http).await?;
+
+    // Only list channels the requesting user can see
+    let mut channels: Vec<&GuildChannel> = all_channels
+        .values()
+        .filter(|channel| channel.kind != ChannelType::Category)
+        .filter(|channel| access.can_view(channel))
+        .collect();
+    channels.sort_by_key(|channel| {
+        let category_position = channel
+            .parent_id
+            .and_then(|parent_id| all_channels.get(&parent_id))
+            .map(|category| category.position);
+        (category_position, channel.position)
+    });
+
+    let channels: Vec<serde_json::Value> = channels
+        .into_iter()
+        .map(|channel| {
+            let category = channel
+                .parent_id
+                .and_then(|parent_id| all_channels.get(&parent_id))
+                .map(|category| category.name.clone());
+
+            json!({
+                "id": channel.id.to_string(),
+                "name": channel.name,
+                "type": channel.kind.name(),
+                "category": category,
+                "topic": channel.topic,
+            })
+        })
+        .collect();
+
+    Ok(json!({
+        "name": access.guild.name,
+        "description": access.guild.description,
+        "created_at": format_timestamp(&access.guild.id.created_at()),
+        "channels": channels,
+    })
+    .to_string())
+}
@@ -0,0 +1,241 @@
+use reqwest::Client;
+use reqwest::multipart::{Form, Part};
+use serde::Deserialize;
+use serenity::all::Attachment;
+use serenity::prelude::Context;
+
+use crate::utils::circuit_breaker;
+use crate::utils::msg_context::MsgContextInfo;
+use crate::utils::openai_schema::OpenAiApiError;
+use crate::utils::persistence::record_usage;
+use crate::utils::pricing::get_model_pricing;
+use crate::utils::statics::{STT_BASE_URL, STT_MODEL, STT_TOKEN};
+use crate::utils::usage::UsageTotals;
+
+// Upload limit of the OpenAI transcription API
+const MAX_AUDIO_SIZE: u32 = 25 * 1024 * 1024;
+
+// Extensions of audio files that can be transcribed
+// Discord voice messages are ogg files with opus audio, video containers are left out
+const AUDIO_EXTENSIONS: &[&str] = &["ogg", "opus", "oga", "mp3", "m4a", "wav"];
+
+/// Response body of the transcription API
+#[derive(Debug, Deserialize)]
+struct TranscriptionResponse {
+    text: String,
+    #[serde(default)]
+    usage: Option<TranscriptionUsage>,
+}
+
+/// Usage of a transcription, billed either by tokens or by the length of the audio
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TranscriptionUsage {
+    Tokens {
+        input_tokens: u32,
+        output_tokens: u32,
+    },
+    Duration {
+        seconds: f64,
+    },
+}
+
+/// Text of an audio file
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    pub text: String,
+    pub usage: Option<TranscriptionUsage>,
+    pub model: String,
+}
+
+impl Transcript {
+    /// Usage totals of the transcription, with the cost if it is billed by tokens
+    pub fn usage_totals(&self) -> UsageTotals {
+        let Some(TranscriptionUsage::Tokens {
+            input_tokens,
+            output_tokens,
+        }) = self.usage
+        else {
+            return UsageTotals {
+                requests: 1,
+                ..Default::default()
+            };
+        };
+
+        let cost_usd = get_model_pricing(&self.model)
+            .map(|pricing| {
+                (f64::from(input_tokens) * pricing.input
+                    + f64::from(output_tokens) * pricing.output)
+                    / 1_000_000.0
+            })
+            .unwrap_or_default();
+
+        UsageTotals {
+            requests: 1,
+            input_tokens: u64::from(input_tokens),
+            output_tokens: u64::from(output_tokens),
+            cost_usd,
+            ..Default::default()
+        }
+    }
+}
+
+/// Check whether an attachment is an audio file that can be transcribed
+pub fn is_audio_attachment(content_type: Option<&str>, filename: &str) -> bool {
+    if content_type.is_some_and(|ct| ct.starts_with("audio/")) {
+        return true;
+    }
+
+    filename
+        .rsplit_once('.')
+        .is_some_and(|(_, ext)| AUDIO_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Transcribe an audio file with an OpenAI compatible transcription API
+pub async fn transcribe_audio(
+    client: &Client,
+    base_url: &str,
+    token: &str,
+    model: &str,
+    audio: Vec<u8>,
+    filename: &str,
+) -> eyre::Result<Transcript> {
+    let file = Part::bytes(audio).file_name(filename.to_string());
+    let form = Form::new()
+        .part("file", file)
+        .text("model", model.to_string())
+        .text("response_format", "json");
+
+    let response = client
+        .post(format!("{base_url}/audio/transcriptions"))
+        .header("Authorization", format!("Bearer {token}"))
+        .multipart(form)
+        .send()
+        .await?;
+
+    let status = response.status();
+    if !status.is_success() {
+        let error_text = response.text().await?;
+        return Err(OpenAiApiError::from_response_body(status.as_u16(), &error_text).into());
+    }
+
+    let response: TranscriptionResponse = response.json().await?;
+    Ok(Transcript {
+        text: response.text.trim().to_string(),
+        usage: response.usage,
+        model: model.to_string(),
+    })
+}
+
+/// Download an audio attachment and transcribe it with the configured endpoint
+/// The usage of the transcription is recorded for the message
+pub async fn transcribe_attachment(
+    ctx: &Context,
+    msg_ctx: &MsgContextInfo,
+    attachment: &Attachment,
+) -> eyre::Result<String> {
+    if attachment.size > MAX_AUDIO_SIZE {
+        return Err(eyre::eyre!(
+            "Audio file is too large ({} MB, max {} MB)",
+            attachment.size / 1024 / 1024,
+            MAX_AUDIO_SIZE / 1024 / 1024
+        ));
+    }
+
+    let audio = attachment.download().await?;
+    circuit_breaker::acquire(ctx).await?;
+    let result = transcribe_audio(
+        &Client::new(),
+        &STT_BASE_URL,
+        &STT_TOKEN,
+        &STT_MODEL,
+        audio,
+        &attachment.filename,
+    )
+    .await;
+    circuit_breaker::record_outcome(ctx, &result).await;
+    let transcript = result?;
+
+    record_usage(msg_ctx, transcript.usage_totals()).await;
+    tracing::info!(
+        "Transcribed {} with {}: {:?}",
+        attachment.filename,
+        transcript.model,
+        transcript.text
+    );
+
+    if transcript.text.is_empty() {
+        return Err(eyre::eyre!("No speech found in the audio"));
+    }
+    Ok(transcript.text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_server::StandInServer;
+
+    #[test]
+    fn test_is_audio_attachment() {
+        assert!(is_audio_attachment(Some("audio/ogg"), "voice-message.ogg"));
+        assert!(is_audio_attachment(None, "song.MP3"));
+        assert!(is_audio_attachment(Some("video/mp4"), "memo.m4a"));
+        assert!(!is_audio_attachment(Some("image/png"), "cat.png"));
+        assert!(!is_audio_attachment(None, "README"));
+        // Videos are not transcribed
+        assert!(!is_audio_attachment(Some("video/mp4"), "clip.mp4"));
+        assert!(!is_audio_attachment(Some("video/webm"), "clip.webm"));
+    }
+
+    #[tokio::test]
+    async fn test_transcribe_audio_with_stand_in_server() {
+        let body = r#"{"text": " 안녕 민티야 ", "usage": {"type": "tokens", "input_tokens": 100, "input_token_details": {"text_tokens": 0, "audio_tokens": 100}, "output_tokens": 20, "total_tokens": 120}}"#;
+        let server = StandInServer::start(200, "application/json", body).await;
+
+        let transcript = transcribe_audio(
+            &Client::new(),
+            &server.base_url,
+            "test-token",
+            "gpt-4o-mini-transcribe",
+            b"OggS fake audio".to_vec(),
+            "voice-message.ogg",
+        )
+        .await
+        .unwrap();
+        assert_eq!(transcript.text, "안녕 민티야");
+
+        let totals = transcript.usage_totals();
+        assert_eq!(totals.input_tokens, 100);
+        assert_eq!(totals.output_tokens, 20);
+        let expected_cost = (100.0 * 3.0 + 20.0 * 5.0) / 1_000_000.0;
+        assert!((totals.cost_usd - expected_cost).abs() < 1e-12);
+
+        let request = server.request().await;
+        assert_eq!(request.request_line, "POST /audio/transcriptions HTTP/1.1");
+        let body = String::from_utf8_lossy(&request.body);
+        assert!(body.contains(r#"filename="voice-message.ogg""#));
+        assert!(body.contains("gpt-4o-mini-transcribe"));
+        assert!(body.contains("OggS fake audio"));
+    }
+
+    #[tokio::test]
+    async fn test_duration_usage_has_no_token_cost() {
+        let body = r#"{"text": "hello", "usage": {"type": "duration", "seconds": 3}}"#;
+        let server = StandInServer::start(200, "application/json", body).await;
+
+        let transcript = transcribe_audio(
+            &Client::new(),
+            &server.base_url,
+            "test-token",
+            "whisper-1",
+            b"ID3 fake audio".to_vec(),
+            "song.mp3",
+        )
+        .await
+        .unwrap();
+
+        let totals = transcript.usage_totals();
+        assert_eq!(totals.requests, 1);
+        assert_eq!(totals.cost_usd, 0.0);
+    }
+}
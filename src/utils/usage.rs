@@ -0,0 +1,349 @@
+use chrono::{Datelike, Duration, FixedOffset, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+use crate::utils::msg_context::MsgContextInfo;
+use crate::utils::openai_schema::ResponsesUsage;
+
+// Daily usage older than this is dropped
+const USAGE_RETENTION_DAYS: i64 = 400;
+
+/// Get today's date in KST, which is where day boundaries are drawn
+pub fn today_kst() -> NaiveDate {
+    let kst = FixedOffset::east_opt(9 * 3600).unwrap();
+    Utc::now().with_timezone(&kst).date_naive()
+}
+
+/// Aggregated token usage and cost
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub cached_input_tokens: u64,
+    pub output_tokens: u64,
+    pub reasoning_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl UsageTotals {
+    /// Totals of a single response
+    pub fn from_response(usage: &ResponsesUsage, cost_usd: f64) -> Self {
+        Self {
+            requests: 1,
+            input_tokens: u64::from(usage.input_tokens),
+            cached_input_tokens: u64::from(usage.input_tokens_details.cached_tokens),
+            output_tokens: u64::from(usage.output_tokens),
+            reasoning_tokens: u64::from(usage.output_tokens_details.reasoning_tokens),
+            cost_usd,
+        }
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+
+    /// What is left after taking `other` away, never going below zero
+    pub fn saturating_sub(&self, other: &Self) -> Self {
+        Self {
+            requests: self.requests.saturating_sub(other.requests),
+            input_tokens: self.input_tokens.saturating_sub(other.input_tokens),
+            cached_input_tokens: self
+                .cached_input_tokens
+                .saturating_sub(other.cached_input_tokens),
+            output_tokens: self.output_tokens.saturating_sub(other.output_tokens),
+            reasoning_tokens: self.reasoning_tokens.saturating_sub(other.reasoning_tokens),
+            cost_usd: (self.cost_usd - other.cost_usd).max(0.0),
+        }
+    }
+
+    fn add(&mut self, other: &Self) {
+        self.requests += other.requests;
+        self.input_tokens += other.input_tokens;
+        self.cached_input_tokens += other.cached_input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.reasoning_tokens += other.reasoning_tokens;
+        self.cost_usd += other.cost_usd;
+    }
+}
+
+/// Prompt cache hits of the requests made in a channel
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct PromptCacheStats {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub cached_tokens: u64,
+    /// Share of the input tokens of the last request that were cached
+    pub last_hit_rate: f64,
+}
+
+impl PromptCacheStats {
+    /// Add the usage of a response
+    pub fn record(&mut self, usage: &ResponsesUsage) {
+        let input_tokens = u64::from(usage.input_tokens);
+        let cached_tokens = u64::from(usage.input_tokens_details.cached_tokens);
+
+        self.requests += 1;
+        self.input_tokens += input_tokens;
+        self.cached_tokens += cached_tokens;
+        self.last_hit_rate = hit_rate(cached_tokens, input_tokens);
+    }
+
+    /// Share of all input tokens that were cached
+    pub fn hit_rate(&self) -> f64 {
+        hit_rate(self.cached_tokens, self.input_tokens)
+    }
+}
+
+fn hit_rate(cached_tokens: u64, input_tokens: u64) -> f64 {
+    if input_tokens == 0 {
+        0.0
+    } else {
+        cached_tokens as f64 / input_tokens as f64
+    }
+}
+
+/// Usage of a single day, broken down by user, channel and guild
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DailyUsage {
+    pub total: UsageTotals,
+    pub by_user: HashMap<UserId, UsageTotals>,
+    pub by_channel: HashMap<ChannelId, UsageTotals>,
+    pub by_guild: HashMap<GuildId, UsageTotals>,
+}
+
+/// Persistent usage aggregates per day
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageLedger {
+    pub days: BTreeMap<NaiveDate, DailyUsage>,
+
+    /// Last known names, used to make reports readable
+    #[serde(default)]
+    pub user_names: HashMap<UserId, String>,
+    #[serde(default)]
+    pub channel_names: HashMap<ChannelId, String>,
+    #[serde(default)]
+    pub guild_names: HashMap<GuildId, String>,
+}
+
+/// Time range of a usage report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum_macros::Display)]
+pub enum UsagePeriod {
+    Today,
+    #[strum(serialize = "This week")]
+    Week,
+    #[strum(serialize = "This month")]
+    Month,
+}
+
+impl UsagePeriod {
+    /// First day of the period that contains `today` (weeks start on Monday)
+    pub fn start_date(&self, today: NaiveDate) -> NaiveDate {
+        match self {
+            UsagePeriod::Today => today,
+            UsagePeriod::Week => {
+                today - Duration::days(i64::from(today.weekday().num_days_from_monday()))
+            }
+            UsagePeriod::Month => today.with_day(1).unwrap_or(today),
+        }
+    }
+}
+
+/// Usage summed over a range of days
+#[derive(Debug, Clone, Default)]
+pub struct UsageSummary {
+    pub total: UsageTotals,
+    pub by_user: HashMap<UserId, UsageTotals>,
+    pub by_channel: HashMap<ChannelId, UsageTotals>,
+    pub by_guild: HashMap<GuildId, UsageTotals>,
+}
+
+fn add_to<K: Eq + Hash + Copy>(map: &mut HashMap<K, UsageTotals>, key: K, totals: &UsageTotals) {
+    map.entry(key).or_default().add(totals);
+}
+
+impl UsageLedger {
+    /// Record the usage of a response made for the given message
+    pub fn record(&mut self, date: NaiveDate, msg_ctx: &MsgContextInfo, totals: UsageTotals) {
+        let day = self.days.entry(date).or_default();
+        day.total.add(&totals);
+        add_to(&mut day.by_user, msg_ctx.author_id, &totals);
+        add_to(&mut day.by_channel, msg_ctx.channel_id, &totals);
+        if let Some(guild_id) = msg_ctx.guild_id {
+            add_to(&mut day.by_guild, guild_id, &totals);
+        }
+
+        // Remember names for reports
+        self.user_names
+            .insert(msg_ctx.author_id, msg_ctx.author.name.clone());
+        if let Some(channel_name) = &msg_ctx.channel_name {
+            self.channel_names
+                .insert(msg_ctx.channel_id, channel_name.clone());
+        }
+        if let (Some(guild_id), Some(guild_name)) = (msg_ctx.guild_id, &msg_ctx.guild_name) {
+            self.guild_names.insert(guild_id, guild_name.clone());
+        }
+
+        self.prune(date);
+    }
+
+    /// Sum the usage of all days from `from` to `to` (inclusive)
+    pub fn summarize(&self, from: NaiveDate, to: NaiveDate) -> UsageSummary {
+        let mut summary = UsageSummary::default();
+
+        for day in self.days.range(from..=to).map(|(_, day)| day) {
+            summary.total.add(&day.total);
+            for (user_id, totals) in &day.by_user {
+                add_to(&mut summary.by_user, *user_id, totals);
+            }
+            for (channel_id, totals) in &day.by_channel {
+                add_to(&mut summary.by_channel, *channel_id, totals);
+            }
+            for (guild_id, totals) in &day.by_guild {
+                add_to(&mut summary.by_guild, *guild_id, totals);
+            }
+        }
+
+        summary
+    }
+
+    /// Readable name of a user for reports
+    pub fn user_label(&self, user_id: UserId) -> String {
+        self.user_names
+            .get(&user_id)
+            .cloned()
+            .unwrap_or_else(|| user_id.to_string())
+    }
+
+    /// Readable name of a channel for reports
+    pub fn channel_label(&self, channel_id: ChannelId) -> String {
+        self.channel_names
+            .get(&channel_id)
+            .map(|name| format!("#{name}"))
+            .unwrap_or_else(|| channel_id.to_string())
+    }
+
+    /// Readable name of a guild for reports
+    pub fn guild_label(&self, guild_id: GuildId) -> String {
+        self.guild_names
+            .get(&guild_id)
+            .cloned()
+            .unwrap_or_else(|| guild_id.to_string())
+    }
+
+    /// Drop days that are older than the retention period
+    fn prune(&mut self, today: NaiveDate) {
+        let oldest = today - Duration::days(USAGE_RETENTION_DAYS);
+        self.days = self.days.split_off(&oldest);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::openai_schema::{InputTokensDetails, OutputTokensDetails};
+
+    fn totals(tokens: u64, cost_usd: f64) -> UsageTotals {
+        UsageTotals {
+            requests: 1,
+            input_tokens: tokens,
+            output_tokens: tokens,
+            cost_usd,
+            ..Default::default()
+        }
+    }
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, day).unwrap()
+    }
+
+    #[test]
+    fn test_record_and_summarize() {
+        let mut ledger = UsageLedger::default();
+        ledger.record(
+            date(10),
+            &MsgContextInfo::for_test(1, 10, Some(100)),
+            totals(100, 0.5),
+        );
+        ledger.record(
+            date(10),
+            &MsgContextInfo::for_test(2, 10, Some(100)),
+            totals(50, 0.25),
+        );
+        ledger.record(
+            date(11),
+            &MsgContextInfo::for_test(1, 20, None),
+            totals(10, 0.125),
+        );
+
+        let day = ledger.summarize(date(10), date(10));
+        assert_eq!(day.total.requests, 2);
+        assert_eq!(day.total.total_tokens(), 300);
+        assert_eq!(day.by_user[&UserId::new(1)].cost_usd, 0.5);
+        assert_eq!(day.by_guild[&GuildId::new(100)].requests, 2);
+
+        let both = ledger.summarize(date(10), date(11));
+        assert_eq!(both.total.requests, 3);
+        assert_eq!(both.by_user[&UserId::new(1)].cost_usd, 0.625);
+        assert_eq!(both.by_channel.len(), 2);
+        assert_eq!(both.by_guild.len(), 1);
+
+        assert_eq!(ledger.user_names[&UserId::new(2)], "user2");
+        assert_eq!(ledger.guild_names[&GuildId::new(100)], "guild100");
+    }
+
+    #[test]
+    fn test_prompt_cache_stats() {
+        let usage = |input: u32, cached: u32| ResponsesUsage {
+            input_tokens: input,
+            input_tokens_details: InputTokensDetails {
+                cached_tokens: cached,
+            },
+            output_tokens: 10,
+            output_tokens_details: OutputTokensDetails {
+                reasoning_tokens: 0,
+            },
+            total_tokens: input + 10,
+        };
+
+        let mut stats = PromptCacheStats::default();
+        assert_eq!(stats.hit_rate(), 0.0);
+
+        stats.record(&usage(1000, 0));
+        stats.record(&usage(1000, 500));
+        stats.record(&usage(2000, 1500));
+        assert_eq!(stats.requests, 3);
+        assert_eq!(stats.hit_rate(), 0.5);
+        assert_eq!(stats.last_hit_rate, 0.75);
+    }
+
+    #[test]
+    fn test_prune_old_days() {
+        let mut ledger = UsageLedger::default();
+        ledger.record(
+            date(1),
+            &MsgContextInfo::for_test(1, 10, None),
+            totals(1, 0.0),
+        );
+
+        let much_later = date(1) + Duration::days(USAGE_RETENTION_DAYS + 1);
+        ledger.record(
+            much_later,
+            &MsgContextInfo::for_test(1, 10, None),
+            totals(1, 0.0),
+        );
+
+        assert_eq!(ledger.days.len(), 1);
+        assert!(ledger.days.contains_key(&much_later));
+    }
+
+    #[test]
+    fn test_period_start_date() {
+        // 2025-03-13 is a Thursday
+        let today = date(13);
+        assert_eq!(UsagePeriod::Today.start_date(today), today);
+        assert_eq!(UsagePeriod::Week.start_date(today), date(10));
+        assert_eq!(UsagePeriod::Month.start_date(today), date(1));
+    }
+}